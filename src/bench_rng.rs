@@ -0,0 +1,49 @@
+use rand::{Error, RngCore};
+
+
+static GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// SplitMix64 generator. Its whole state is one number, so it can be saved in a
+/// checkpoint and a resumed run draws the same values as an uninterrupted one.
+#[derive(Clone, Copy, Debug)]
+pub struct BenchRng {
+    state: u64
+}
+
+impl BenchRng {
+    pub fn new(state: u64) -> BenchRng {
+        return BenchRng {
+            state: state
+        }
+    }
+    
+    pub fn get_state(&self) -> u64 {
+        return self.state
+    }
+}
+
+impl RngCore for BenchRng {
+    fn next_u32(&mut self) -> u32 {
+        return (self.next_u64() >> 32) as u32;
+    }
+    
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        return value ^ (value >> 31);
+    }
+    
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+    
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        return Ok(());
+    }
+}
@@ -0,0 +1,204 @@
+use {compute_percentile, compute_time_diff_ms, get_current_time, write_log, write_reconnect_events, Config, Executor};
+use db::db_raw::query_db::{json_to_timestamp, DBError, PoolConfig, QueryBase, Request};
+use std::fs::File;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+
+static TOPIC_PREFIX: &str = "topic";
+
+pub enum ChangeEvent {
+    Ready,
+    Change(u128)
+}
+
+pub trait ChangesExecutor: Executor {
+    /// Blocks and calls `on_event` for every notification on `topic` until it returns
+    /// `false`, or returns the error that ended the subscription.
+    fn subscribe(&mut self, topic: &str, on_event: &mut dyn FnMut(ChangeEvent) -> bool) -> Option<DBError>;
+    /// Writes an event stamped with `stamp` to `topic` and returns the write time.
+    fn publish(&mut self, topic: &str, stamp: u128) -> Result<i32, DBError>;
+}
+
+/// Subscribes through the common data-access layer and reports the `field` stamp
+/// of every change. Returns the error that ended the subscription.
+pub fn subscribe_changes(database: &dyn QueryBase,
+    request: Request,
+    field: &str,
+    on_event: &mut dyn FnMut(ChangeEvent) -> bool) -> Option<DBError> {
+    
+    let mut error = None;
+    database.changes(request, &mut |change_res| {
+        let change = match change_res {
+            Ok(change) => change,
+            Err(err) => {
+                error = Some(err);
+                return false
+            }
+        };
+        if change["state"] == "ready" {
+            return on_event(ChangeEvent::Ready);
+        }
+        
+        return match json_to_timestamp(&change["new_val"][field]) {
+            Some(stamp) => on_event(ChangeEvent::Change(stamp as u128)),
+            None => true
+        }
+    });
+    return error;
+}
+
+pub fn start_changes_benchmark<E>(db_name: String, new_executor: fn(PoolConfig) -> Result<E, DBError>, config: &Config)
+    where E: ChangesExecutor + 'static {
+    
+    let subscribers = config.get_subscribers();
+    let topics = if config.get_topics() > 0 { config.get_topics() } else { 1 };
+    let events = config.get_events();
+    let timeout = Duration::from_millis(config.get_changes_timeout());
+    
+    println!("Begin changes benchmark, subscribers: {}", subscribers);
+    let mut log_file = File::create(format!("{}_changes_log.txt", db_name))
+        .expect("Can't create log file");
+    
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let (latency_tx, latency_rx) = mpsc::channel();
+    let mut expected_events = 0;
+    
+    for i in 0..subscribers {
+        let topic_pos = i % topics;
+        let expected = (0..events).filter(|event| event % topics == topic_pos).count();
+        expected_events += expected;
+        start_subscriber(new_executor, config.get_pool_config(), get_topic(topic_pos), expected, ready_tx.clone(), latency_tx.clone());
+    }
+    
+    write_log(&mut log_file, format_args!("# Begin subscribe\n"));
+    for i in 0..subscribers {
+        if ready_rx.recv_timeout(timeout).is_err() {
+            write_log(&mut log_file, format_args!("# Only {} of {} subscribers are ready\n", i, subscribers));
+            break
+        }
+    }
+    
+    write_log(&mut log_file, format_args!("# Begin publish events\n"));
+    let mut executor = match new_executor(config.get_pool_config()) {
+        Ok(executor) => executor,
+        Err(err) => {
+            write_log(&mut log_file, format_args!("# Failed to connect the publisher: {:?}\n", err));
+            return
+        }
+    };
+    let mut publish_time_ms: i32 = 0;
+    let mut failed = 0;
+    let publish_start_time = get_current_time();
+    
+    for i in 0..events {
+        match executor.publish(&get_topic(i % topics), get_current_time()) {
+            Ok(publish_time) => publish_time_ms += publish_time,
+            Err(err) => {
+                failed += 1;
+                write_log(&mut log_file, format_args!("Publish of event {} failed: {:?}\n", i, err));
+            }
+        }
+        write_reconnect_events(&mut log_file, executor.take_reconnect_events());
+        if config.get_publish_interval() > 0 {
+            thread::sleep(Duration::from_millis(config.get_publish_interval()));
+        }
+    }
+    write_log(&mut log_file,
+        format_args!("# Publish {} events for {} ms, failed: {}\n",
+        events-failed,
+        publish_time_ms,
+        failed));
+    
+    let latencies = collect_latencies(&mut log_file, latency_rx, expected_events, timeout);
+    let delivery_time_ms = compute_time_diff_ms(publish_start_time, get_current_time());
+    write_summary(&mut log_file, latencies, expected_events, delivery_time_ms);
+}
+
+fn start_subscriber<E>(new_executor: fn(PoolConfig) -> Result<E, DBError>,
+    pool_config: PoolConfig,
+    topic: String,
+    expected: usize,
+    ready_tx: Sender<()>,
+    latency_tx: Sender<i32>)
+    where E: ChangesExecutor + 'static {
+    
+    thread::spawn(move || {
+        if expected == 0 {
+            let _ = ready_tx.send(());
+            return
+        }
+        
+        let mut executor = match new_executor(pool_config) {
+            Ok(executor) => executor,
+            Err(err) => {
+                println!("# Subscriber of {} failed to connect: {:?}", topic, err);
+                return
+            }
+        };
+        let mut received = 0;
+        let err = executor.subscribe(&topic, &mut |event| {
+            match event {
+                ChangeEvent::Ready => ready_tx.send(()).is_ok(),
+                ChangeEvent::Change(stamp) => {
+                    received += 1;
+                    let latency = compute_time_diff_ms(stamp, get_current_time());
+                    latency_tx.send(latency).is_ok() && received < expected
+                }
+            }
+        });
+        if let Some(err) = err {
+            println!("# Subscriber of {} failed: {:?}", topic, err);
+        }
+    });
+}
+
+fn collect_latencies(log_file: &mut File,
+    latency_rx: Receiver<i32>,
+    expected_events: usize,
+    timeout: Duration) -> Vec<i32> {
+    
+    let mut latencies = Vec::with_capacity(expected_events);
+    while latencies.len() < expected_events {
+        match latency_rx.recv_timeout(timeout) {
+            Ok(latency) => {
+                latencies.push(latency);
+                write_log(log_file,
+                    format_args!("Change №{} latency: {} ms\n",
+                    latencies.len(),
+                    latency));
+            },
+            Err(_) => break
+        }
+    }
+    return latencies
+}
+
+fn write_summary(log_file: &mut File, mut latencies: Vec<i32>, expected_events: usize, delivery_time_ms: i32) {
+    if latencies.is_empty() {
+        write_log(log_file, format_args!("# Delivered 0 of {} events\n", expected_events));
+        return
+    }
+    
+    latencies.sort();
+    let average_latency = latencies.iter().sum::<i32>()/latencies.len() as i32;
+    let throughput = if delivery_time_ms > 0 { latencies.len() as i32*1000/delivery_time_ms } else { 0 };
+    write_log(log_file,
+        format_args!("# Delivered {} of {} events for {} ms, {} events/s\n",
+        latencies.len(),
+        expected_events,
+        delivery_time_ms,
+        throughput));
+    write_log(log_file,
+        format_args!("# Latency average: {} ms, p50: {} ms, p99: {} ms, max: {} ms\n",
+        average_latency,
+        compute_percentile(&latencies, 50.0),
+        compute_percentile(&latencies, 99.0),
+        latencies[latencies.len()-1]));
+}
+
+fn get_topic(topic_pos: usize) -> String {
+    return format!("{}{}", TOPIC_PREFIX, topic_pos)
+}
@@ -0,0 +1,364 @@
+use bench_rng::BenchRng;
+use results::PhaseRecorder;
+use scheduler::PointScheduler;
+use serde_json::Value;
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Series,
+    Points,
+    Select,
+    Done
+}
+
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        return match *self {
+            Phase::Series => "series",
+            Phase::Points => "points",
+            Phase::Select => "select",
+            Phase::Done => "done"
+        }
+    }
+    
+    fn from_str(phase: &str) -> Phase {
+        return match phase {
+            "series" => Phase::Series,
+            "points" => Phase::Points,
+            "select" => Phase::Select,
+            "done" => Phase::Done,
+            _ => panic!("Invalid checkpoint phase: {}", phase)
+        }
+    }
+}
+
+/// Statistics of the current phase, accumulated over resumed runs. The counts
+/// are in records, a batch of points is one operation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PhaseStats {
+    pub completed: usize,
+    pub timed_out: usize,
+    pub failed: usize,
+    /// Completed operations, the query time is their sum
+    pub operations: usize,
+    pub queries_time_ms: i64
+}
+
+impl PhaseStats {
+    pub fn get_attempted(&self) -> usize {
+        return self.completed+self.timed_out+self.failed
+    }
+}
+
+/// Results of the operations of one kind in a scenario phase.
+pub struct OperationStats {
+    pub kind: String,
+    pub stats: PhaseStats,
+    pub recorder: PhaseRecorder
+}
+
+impl OperationStats {
+    pub fn new(kind: &str) -> OperationStats {
+        return OperationStats {
+            kind: kind.to_string(),
+            stats: PhaseStats::default(),
+            recorder: PhaseRecorder::new()
+        }
+    }
+    
+    fn to_json(&self) -> Value {
+        return json!({
+            "kind": self.kind,
+            "completed": self.stats.completed,
+            "timed_out": self.stats.timed_out,
+            "failed": self.stats.failed,
+            "operations": self.stats.operations,
+            "queries_time_ms": self.stats.queries_time_ms,
+            "recorder": self.recorder.to_json()
+        });
+    }
+    
+    fn from_json(json: &Value) -> OperationStats {
+        let count = |key: &str| json[key].as_u64().expect("Invalid checkpoint operation count param") as usize;
+        return OperationStats {
+            kind: json["kind"].as_str().expect("Invalid checkpoint operation kind param").to_string(),
+            stats: PhaseStats {
+                completed: count("completed"),
+                timed_out: count("timed_out"),
+                failed: count("failed"),
+                operations: count("operations"),
+                queries_time_ms: json["queries_time_ms"].as_i64().expect("Invalid checkpoint operation queries_time_ms param")
+            },
+            recorder: PhaseRecorder::from_json(&json["recorder"])
+        }
+    }
+}
+
+/// Progress of a test case, everything that is needed to continue it without
+/// inserting the same data again.
+pub struct Checkpoint {
+    pub test_case_id: usize,
+    /// Repetition of the test case, from 0
+    pub repetition: usize,
+    pub phase: Phase,
+    pub stats: PhaseStats,
+    pub recorder: PhaseRecorder,
+    /// Seed of the series tags
+    pub seed: u64,
+    /// Time of the first point of every series in milliseconds
+    pub start_timestamp: i64,
+    pub rng: BenchRng,
+    /// Number of series attempted in the series phase
+    pub series_count: usize,
+    /// Schedule of the points phase, or of the load or mixed phase of a scenario
+    pub scheduler: Option<PointScheduler>,
+    /// Phase of a scenario, from 0
+    pub step: usize,
+    /// Results of every kind of operation in the current phase of a scenario
+    pub operation_stats: Vec<OperationStats>,
+    /// Points of every series written by the finished phases of a scenario
+    pub written_points: usize,
+    /// Oldest points of every series deleted by the finished phases of a scenario
+    pub deleted_points: usize
+}
+
+impl Checkpoint {
+    pub fn new(test_case_id: usize, repetition: usize, seed: u64, start_timestamp: i64) -> Checkpoint {
+        return Checkpoint {
+            test_case_id: test_case_id,
+            repetition: repetition,
+            phase: Phase::Series,
+            stats: PhaseStats::default(),
+            recorder: PhaseRecorder::new(),
+            seed: seed,
+            start_timestamp: start_timestamp,
+            rng: BenchRng::new(seed),
+            series_count: 0,
+            scheduler: None,
+            step: 0,
+            operation_stats: Vec::new(),
+            written_points: 0,
+            deleted_points: 0
+        }
+    }
+    
+    pub fn start_phase(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.stats = PhaseStats::default();
+        self.recorder = PhaseRecorder::new();
+        if phase != Phase::Points {
+            self.scheduler = None;
+        }
+    }
+    
+    pub fn is_started(&self) -> bool {
+        return self.phase != Phase::Series || self.stats.get_attempted() > 0 || self.step > 0 || self.scheduler.is_some()
+    }
+    
+    pub fn get_phase_name(&self) -> &'static str {
+        return self.phase.as_str()
+    }
+    
+    fn to_json(&self) -> Value {
+        return json!({
+            "test_case_id": self.test_case_id,
+            "repetition": self.repetition,
+            "phase": self.phase.as_str(),
+            "completed": self.stats.completed,
+            "timed_out": self.stats.timed_out,
+            "failed": self.stats.failed,
+            "operations": self.stats.operations,
+            "queries_time_ms": self.stats.queries_time_ms,
+            "recorder": self.recorder.to_json(),
+            "seed": self.seed,
+            "start_timestamp": self.start_timestamp,
+            "rng_state": self.rng.get_state(),
+            "series_count": self.series_count,
+            "scheduler": self.scheduler.as_ref().map(PointScheduler::to_json),
+            "step": self.step,
+            "operation_stats": self.operation_stats.iter().map(OperationStats::to_json).collect::<Vec<Value>>(),
+            "written_points": self.written_points,
+            "deleted_points": self.deleted_points
+        });
+    }
+    
+    fn from_json(json: &Value) -> Checkpoint {
+        let completed = json["completed"].as_u64().expect("Invalid checkpoint completed param") as usize;
+        let stats = PhaseStats {
+            completed: completed,
+            timed_out: json["timed_out"].as_u64().expect("Invalid checkpoint timed_out param") as usize,
+            failed: json["failed"].as_u64().unwrap_or(0) as usize,
+            operations: json["operations"].as_u64().map_or(completed, |operations| operations as usize),
+            queries_time_ms: json["queries_time_ms"].as_i64().expect("Invalid checkpoint queries_time_ms param")
+        };
+        
+        return Checkpoint {
+            test_case_id: json["test_case_id"].as_u64().expect("Invalid checkpoint test_case_id param") as usize,
+            repetition: json["repetition"].as_u64().unwrap_or(0) as usize,
+            phase: Phase::from_str(json["phase"].as_str().expect("Invalid checkpoint phase param")),
+            stats: stats,
+            recorder: PhaseRecorder::from_json(&json["recorder"]),
+            seed: json["seed"].as_u64().expect("Invalid checkpoint seed param"),
+            start_timestamp: json["start_timestamp"].as_i64().expect("Invalid checkpoint start_timestamp param"),
+            rng: BenchRng::new(json["rng_state"].as_u64().expect("Invalid checkpoint rng_state param")),
+            series_count: json["series_count"].as_u64().expect("Invalid checkpoint series_count param") as usize,
+            scheduler: if json["scheduler"].is_null() { None } else { Some(PointScheduler::from_json(&json["scheduler"])) },
+            step: json["step"].as_u64().unwrap_or(0) as usize,
+            operation_stats: json["operation_stats"].as_array().map_or(Vec::new(), |operations| operations.iter().map(OperationStats::from_json).collect()),
+            written_points: json["written_points"].as_u64().unwrap_or(0) as usize,
+            deleted_points: json["deleted_points"].as_u64().unwrap_or(0) as usize
+        }
+    }
+}
+
+/// Writes checkpoints of a run to one file, at most once per interval unless forced.
+pub struct Checkpointer {
+    file_path: String,
+    interval: Duration,
+    last_save_time: Instant
+}
+
+impl Checkpointer {
+    pub fn new(file_path: String, interval_ms: u64) -> Checkpointer {
+        return Checkpointer {
+            file_path: file_path,
+            interval: Duration::from_millis(interval_ms),
+            last_save_time: Instant::now()
+        }
+    }
+    
+    pub fn load(&self) -> Checkpoint {
+        let mut file = File::open(&self.file_path).expect("Can't open checkpoint file");
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).expect("Can't read checkpoint file");
+        
+        let json: Value = serde_json::from_str(&contents).expect("Invalid checkpoint json format");
+        return Checkpoint::from_json(&json);
+    }
+    
+    pub fn exists(&self) -> bool {
+        return fs::metadata(&self.file_path).is_ok()
+    }
+    
+    pub fn is_due(&self) -> bool {
+        return self.last_save_time.elapsed() >= self.interval
+    }
+    
+    pub fn save_if_due(&mut self, checkpoint: &Checkpoint) {
+        if self.is_due() {
+            self.save(checkpoint);
+        }
+    }
+    
+    /// The checkpoint is written next to the old one and then renamed, so a crash
+    /// while saving keeps the previous checkpoint.
+    pub fn save(&mut self, checkpoint: &Checkpoint) {
+        let temp_file_path = format!("{}.tmp", self.file_path);
+        let mut file = File::create(&temp_file_path).expect("Can't create checkpoint file");
+        file.write_all(checkpoint.to_json().to_string().as_bytes()).expect("Can't write checkpoint file");
+        file.sync_all().expect("Can't write checkpoint file");
+        fs::rename(&temp_file_path, &self.file_path).expect("Can't replace checkpoint file");
+        self.last_save_time = Instant::now();
+    }
+    
+    /// Removes the checkpoint of a finished run.
+    pub fn remove(&self) {
+        let _ = fs::remove_file(&self.file_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scheduler::ScheduleOrder;
+    use std::env;
+    use std::process;
+    
+    fn get_checkpointer(name: &str) -> Checkpointer {
+        let file_path = env::temp_dir().join(format!("influx_bench_{}_{}.json", name, process::id()));
+        return Checkpointer::new(file_path.to_string_lossy().into_owned(), 0);
+    }
+    
+    #[test]
+    fn checkpoint_resumes_the_saved_phase() {
+        let mut checkpointer = get_checkpointer("checkpoint");
+        let mut checkpoint = Checkpoint::new(3, 1, 42, 1000);
+        checkpoint.series_count = 4;
+        checkpoint.start_phase(Phase::Points);
+        checkpoint.stats = PhaseStats {
+            completed: 120,
+            timed_out: 2,
+            failed: 1,
+            operations: 12,
+            // Past the range of i32
+            queries_time_ms: 5000000000
+        };
+        checkpoint.recorder.record(Some(15), 10);
+        checkpoint.step = 2;
+        let mut operation_stats = OperationStats::new("select");
+        operation_stats.stats.completed = 5;
+        operation_stats.recorder.record(Some(3), 5);
+        checkpoint.operation_stats.push(operation_stats);
+        let mut scheduler = PointScheduler::new(ScheduleOrder::Random, 4, 50, 7);
+        scheduler.by_ref().take(123).count();
+        checkpoint.scheduler = Some(scheduler);
+        checkpointer.save(&checkpoint);
+        assert!(checkpointer.exists());
+        
+        let mut resumed = checkpointer.load();
+        checkpointer.remove();
+        assert!(!checkpointer.exists());
+        assert_eq!(resumed.test_case_id, 3);
+        assert_eq!(resumed.repetition, 1);
+        assert_eq!(resumed.phase, Phase::Points);
+        assert_eq!(resumed.get_phase_name(), "points");
+        assert_eq!(resumed.stats.get_attempted(), 123);
+        assert_eq!(resumed.stats.operations, 12);
+        assert_eq!(resumed.stats.queries_time_ms, 5000000000);
+        assert_eq!(resumed.seed, 42);
+        assert_eq!(resumed.start_timestamp, 1000);
+        assert_eq!(resumed.rng.get_state(), checkpoint.rng.get_state());
+        assert_eq!(resumed.series_count, 4);
+        assert_eq!(resumed.step, 2);
+        assert_eq!(resumed.operation_stats.len(), 1);
+        assert_eq!(resumed.operation_stats[0].kind, "select");
+        assert_eq!(resumed.operation_stats[0].stats.completed, 5);
+        assert!(resumed.is_started());
+        
+        let rest: Vec<(usize, usize)> = checkpoint.scheduler.take().expect("No scheduler").collect();
+        assert_eq!(rest.len(), 77);
+        assert_eq!(resumed.scheduler.take().expect("No resumed scheduler").collect::<Vec<(usize, usize)>>(), rest);
+        assert_eq!(resumed.recorder.finish("points", 200).get_completed(), 10);
+    }
+    
+    #[test]
+    fn new_phase_resets_the_stats() {
+        let mut checkpoint = Checkpoint::new(1, 0, 1, 0);
+        assert!(!checkpoint.is_started());
+        checkpoint.start_phase(Phase::Points);
+        checkpoint.scheduler = Some(PointScheduler::new(ScheduleOrder::RoundRobin, 1, 1, 1));
+        checkpoint.stats.completed = 5;
+        
+        checkpoint.start_phase(Phase::Select);
+        assert_eq!(checkpoint.stats.get_attempted(), 0);
+        assert!(checkpoint.scheduler.is_none());
+        assert!(checkpoint.is_started());
+    }
+    
+    #[test]
+    fn checkpoint_is_saved_after_its_interval() {
+        let mut checkpointer = get_checkpointer("due");
+        checkpointer.save_if_due(&Checkpoint::new(1, 0, 1, 0));
+        assert!(fs::metadata(&checkpointer.file_path).is_ok());
+        checkpointer.remove();
+        
+        let mut checkpointer = Checkpointer::new(checkpointer.file_path.clone(), 60000);
+        checkpointer.save_if_due(&Checkpoint::new(1, 0, 1, 0));
+        assert!(fs::metadata(&checkpointer.file_path).is_err());
+    }
+}
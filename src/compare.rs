@@ -0,0 +1,172 @@
+use results::{PhaseResult, Results, RunResult};
+
+
+/// Whether a higher value of a metric is better or worse.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Direction {
+    HigherIsBetter,
+    LowerIsBetter
+}
+
+/// A run of a results file.
+struct ComparedRun<'a> {
+    label: String,
+    run: &'a RunResult
+}
+
+/// Prints the throughput and latency percentiles of every run next to the first
+/// run, aligned by test case and phase. Returns whether any run is more than
+/// `threshold` percent worse than the first one in any of them, the threshold is
+/// never negative.
+pub fn compare(threshold: f64, result_file_paths: &[String]) -> bool {
+    assert!(threshold >= 0.0, "Negative regression threshold: {}", threshold);
+    let results: Vec<Results> = result_file_paths.iter().map(|path| Results::load(path)).collect();
+    let mut runs = Vec::new();
+    for (result_file_path, file_results) in result_file_paths.iter().zip(results.iter()) {
+        for run in file_results.get_runs() {
+            runs.push(ComparedRun {
+                label: format!("{} ({})", run.get_database(), result_file_path),
+                run: run
+            });
+        }
+    }
+    if runs.len() < 2 {
+        println!("# Nothing to compare, {} runs found", runs.len());
+        return false
+    }
+    
+    let baseline = &runs[0];
+    println!("Baseline: {}", baseline.label);
+    println!("{:<10} {:<8} {:<40} {:>22} {:>18} {:>18} {:>18}",
+        "Test case", "Phase", "Run", "Throughput, records/s", "p50, ms", "p95, ms", "p99, ms");
+    
+    let mut regressions = 0;
+    for test_case in baseline.run.get_test_cases() {
+        for base_phase in test_case.get_phases() {
+            for compared in runs.iter().skip(1) {
+                let phase_res = compared.run.get_test_case(test_case.get_id())
+                    .and_then(|compared_test_case| compared_test_case.get_phase(base_phase.get_phase()));
+                let phase = match phase_res {
+                    Some(phase) => phase,
+                    None => {
+                        println!("{:<10} {:<8} {:<40} missing", test_case.get_id(), base_phase.get_phase(), compared.label);
+                        continue
+                    }
+                };
+                
+                let (row, row_regressions) = format_deltas(base_phase, phase, threshold);
+                regressions += row_regressions;
+                println!("{:<10} {:<8} {:<40} {}", test_case.get_id(), base_phase.get_phase(), compared.label, row);
+            }
+        }
+    }
+    
+    println!("# {} regressions over {}%", regressions, threshold);
+    return regressions > 0
+}
+
+/// Returns the table cells and the number of regressed values.
+fn format_deltas(base: &PhaseResult, phase: &PhaseResult, threshold: f64) -> (String, usize) {
+    let mut regressions = 0;
+    let throughput_delta = compute_delta(base.get_throughput(), phase.get_throughput());
+    let throughput_cell = format_cell(phase.get_throughput(), throughput_delta, Direction::HigherIsBetter, threshold, &mut regressions);
+    
+    let base_latency = base.get_latency();
+    let latency = phase.get_latency();
+    let mut latency_cells = Vec::new();
+    let percentiles = [
+        (base_latency.get_p50(), latency.get_p50()),
+        (base_latency.get_p95(), latency.get_p95()),
+        (base_latency.get_p99(), latency.get_p99())
+    ];
+    for &(base_value, value) in percentiles.iter() {
+        // Phases without latency samples have no percentiles
+        let (base_value, value) = match (base_value, value) {
+            (Some(base_value), Some(value)) => (base_value, value),
+            (_, value) => {
+                latency_cells.push(value.map_or(String::from("- "), |value| format!("{:.1} (n/a) ", value as f64)));
+                continue
+            }
+        };
+        
+        let delta = compute_delta(base_value as f64, value as f64);
+        latency_cells.push(format_cell(value as f64, delta, Direction::LowerIsBetter, threshold, &mut regressions));
+    }
+    
+    let row = format!("{:>22} {:>18} {:>18} {:>18}", throughput_cell, latency_cells[0], latency_cells[1], latency_cells[2]);
+    return (row, regressions)
+}
+
+/// Formats the value with its delta in percent, marked with `!` when the value is
+/// more than `threshold` percent worse in the `direction` of the metric.
+fn format_cell(value: f64, delta: Option<f64>, direction: Direction, threshold: f64, regressions: &mut usize) -> String {
+    return match delta {
+        Some(delta) => {
+            let is_regression = match direction {
+                Direction::HigherIsBetter => delta < -threshold,
+                Direction::LowerIsBetter => delta > threshold
+            };
+            if is_regression {
+                *regressions += 1;
+            }
+            format!("{:.1} ({:+.1}%){}", value, delta, if is_regression { "!" } else { " " })
+        },
+        None => format!("{:.1} (n/a) ", value)
+    }
+}
+
+fn compute_delta(base: f64, value: f64) -> Option<f64> {
+    if base == 0.0 {
+        return None
+    }
+    return Some((value-base)/base*100.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use results::LatencyHistogram;
+    
+    /// Phase of `completed` records in a second, every latency is `latency`.
+    fn get_phase(completed: u64, latency: Option<i32>) -> PhaseResult {
+        let mut histogram = LatencyHistogram::new();
+        if let Some(latency) = latency {
+            histogram.record_count(latency, 10);
+        }
+        return PhaseResult::new(String::from("select"), completed, completed, 0, 1000, histogram, Vec::new())
+    }
+    
+    #[test]
+    fn lower_throughput_is_a_regression() {
+        let base = get_phase(1000, Some(10));
+        assert_eq!(format_deltas(&base, &get_phase(800, Some(10)), 10.0).1, 1);
+        assert_eq!(format_deltas(&base, &get_phase(1200, Some(10)), 10.0).1, 0);
+        assert_eq!(format_deltas(&base, &get_phase(800, Some(10)), 25.0).1, 0);
+    }
+    
+    #[test]
+    fn higher_latency_is_a_regression() {
+        let base = get_phase(1000, Some(10));
+        let (row, regressions) = format_deltas(&base, &get_phase(1000, Some(12)), 10.0);
+        assert_eq!(regressions, 3);
+        assert!(row.contains("12.0 (+20.0%)!"));
+        assert_eq!(format_deltas(&base, &get_phase(1000, Some(8)), 10.0).1, 0);
+    }
+    
+    #[test]
+    fn phases_without_latency_samples_are_not_compared() {
+        let (row, regressions) = format_deltas(&get_phase(1000, None), &get_phase(1000, Some(50)), 0.0);
+        assert_eq!(regressions, 0);
+        assert!(row.contains("50.0 (n/a)"));
+        
+        let (row, regressions) = format_deltas(&get_phase(1000, Some(5)), &get_phase(1000, None), 0.0);
+        assert_eq!(regressions, 0);
+        assert!(row.trim_end().ends_with('-'));
+    }
+    
+    #[test]
+    #[should_panic(expected = "Negative regression threshold")]
+    fn negative_threshold_is_rejected() {
+        compare(-1.0, &[]);
+    }
+}
@@ -0,0 +1,334 @@
+use super::*;
+use influent::create_client;
+use influent::client::{Client, Credentials, Precision};
+use influent::client::http::HttpClient;
+use influent::measurement::{Measurement, Value as InfluxValue};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use tokio_core::reactor::Core;
+use get_current_time;
+
+
+static HOST: &str = "http://localhost:8086";
+static DATABASE: &str = "bench";
+static USERNAME: &str = "root";
+static PASSWORD: &str = "root";
+static FIELD_TIME: &str = "time";
+static POLL_INTERVAL_MS: u64 = 100;
+
+struct Connection {
+    reactor: RefCell<Core>,
+    client: HttpClient<'static>
+}
+
+impl Connection {
+    fn query(&self, query: String, epoch: Option<Precision>) -> Result<Vec<Value>, DBError> {
+        let request = self.client.query(query, epoch);
+        let value = self.reactor.borrow_mut().run(request)?;
+        
+        return Database::parse_series(&value);
+    }
+}
+
+/// InfluxDB only indexes tags, so indexes created through `create_index` are
+/// written as tags and every other key is written as a field.
+pub struct Database {
+    connection: Rc<Connection>,
+    tags: RefCell<HashSet<String>>
+}
+
+impl Database {
+    /// `host` is the URL of the HTTP API.
+    pub fn new(host: Option<&'static str>) -> Database {
+        let credentials = Credentials {
+            username: USERNAME,
+            password: PASSWORD,
+            database: DATABASE
+        };
+        let connection = Connection {
+            reactor: RefCell::new(Core::new().unwrap()),
+            client: create_client(credentials, vec![host.unwrap_or(HOST)])
+        };
+        
+        return Database {
+            connection: Rc::new(connection),
+            tags: RefCell::new(HashSet::new())
+        }
+    }
+    
+    fn query(&self, query: String, epoch: Option<Precision>) -> Result<Vec<Value>, DBError> {
+        return self.connection.query(query, epoch);
+    }
+    
+    /// Maps every row of every series in the query response to a document. An
+    /// error of the request or of any of its statements, or a malformed series,
+    /// fails the whole query.
+    fn parse_series(value: &str) -> Result<Vec<Value>, DBError> {
+        let json: Value = serde_json::from_str(value)
+            .map_err(|err| ClientError::Unexpected(format!("Invalid query response: {}", err)))?;
+        if let Some(err) = json["error"].as_str() {
+            return Err(DBError::from(ClientError::CouldNotComplete(err.to_string())));
+        }
+        let results = json["results"].as_array()
+            .ok_or_else(|| ClientError::Unexpected(format!("Query response without results: {}", value)))?;
+        
+        let mut documents = Vec::new();
+        for result in results {
+            if let Some(err) = result["error"].as_str() {
+                return Err(DBError::from(ClientError::CouldNotComplete(err.to_string())));
+            }
+            for series in result["series"].as_array().unwrap_or(&Vec::new()) {
+                let columns = series["columns"].as_array()
+                    .ok_or_else(|| ClientError::Unexpected(format!("Series without columns: {}", series)))?;
+                let rows = series["values"].as_array()
+                    .ok_or_else(|| ClientError::Unexpected(format!("Series without values: {}", series)))?;
+                for row in rows {
+                    let values = row.as_array()
+                        .ok_or_else(|| ClientError::Unexpected(format!("Invalid series row: {}", row)))?;
+                    let document: serde_json::Map<String, Value> = columns.iter()
+                        .zip(values)
+                        .map(|(column, value)| (column.as_str().unwrap_or_default().to_string(), value.clone()))
+                        .collect();
+                    documents.push(Value::Object(document));
+                }
+            }
+        }
+        return Ok(documents);
+    }
+    
+    fn timestamp_to_json(millis: i64) -> Value {
+        return Value::from(millis);
+    }
+    
+    fn quote(text: &str) -> String {
+        return format!("'{}'", text.replace("'", "\\'"));
+    }
+    
+    /// Tags are always strings, so a tag value is the text of the value.
+    fn value_to_tag(value: &DBValue) -> String {
+        return match *value {
+            DBValue::Str(ref value) => value.clone(),
+            _ => value.to_json(Database::timestamp_to_json).to_string()
+        }
+    }
+    
+    fn value_to_literal(&self, key: &str, value: &DBValue) -> String {
+        if key == FIELD_TIME {
+            if let DBValue::Int(millis) | DBValue::Timestamp(millis) = *value {
+                return format!("{}ms", millis)
+            }
+        }
+        if self.tags.borrow().contains(key) {
+            return Database::quote(&Database::value_to_tag(value))
+        }
+        return match *value {
+            DBValue::Str(ref value) => Database::quote(value),
+            DBValue::Nested(_) => Database::quote(&value.to_json(Database::timestamp_to_json).to_string()),
+            _ => value.to_json(Database::timestamp_to_json).to_string()
+        }
+    }
+    
+    fn condition_to_expression(&self, key: &str, condition: &Condition) -> String {
+        return match *condition {
+            Condition::Eq(ref value) => format!("\"{}\" = {}", key, self.value_to_literal(key, value)),
+            Condition::Ne(ref value) => format!("\"{}\" != {}", key, self.value_to_literal(key, value)),
+            Condition::Lt(ref value) => format!("\"{}\" < {}", key, self.value_to_literal(key, value)),
+            Condition::Gt(ref value) => format!("\"{}\" > {}", key, self.value_to_literal(key, value)),
+            Condition::Range(ref from, ref to) => format!("(\"{0}\" >= {1} and \"{0}\" < {2})",
+                key,
+                self.value_to_literal(key, from),
+                self.value_to_literal(key, to)),
+            // InfluxQL has no IN operator
+            Condition::In(ref values) if values.is_empty() => String::from("false"),
+            Condition::In(ref values) => format!("({})", values.iter()
+                .map(|value| format!("\"{}\" = {}", key, self.value_to_literal(key, value)))
+                .collect::<Vec<String>>()
+                .join(" or "))
+        }
+    }
+    
+    /// Keys that are indexes are written as tags, the time key is the timestamp of
+    /// the point in milliseconds.
+    fn data_to_measurement<'a>(&self, table: &'a str, data: &'a Args) -> Measurement<'a> {
+        let tags = self.tags.borrow();
+        let mut measurement = Measurement::new(table);
+        for (key, value) in data.iter() {
+            // The time of a point is its timestamp and can't be a field
+            if key == FIELD_TIME {
+                if let DBValue::Int(millis) | DBValue::Timestamp(millis) = *value {
+                    measurement.set_timestamp(millis);
+                    continue
+                }
+            }
+            if tags.contains(key) {
+                measurement.add_tag(key.as_str(), Database::value_to_tag(value));
+                continue
+            }
+            let field = match *value {
+                DBValue::Int(value) => InfluxValue::Integer(value),
+                DBValue::Float(value) => InfluxValue::Float(value),
+                DBValue::Bool(value) => InfluxValue::Boolean(value),
+                DBValue::Str(ref value) => InfluxValue::String(value),
+                DBValue::Timestamp(value) => InfluxValue::Integer(value),
+                DBValue::Nested(_) => continue
+            };
+            measurement.add_field(key.as_str(), field);
+        }
+        return measurement;
+    }
+    
+    fn condition_to_where(&self, condition: &Conditions) -> String {
+        return condition.iter()
+            .map(|(key, condition)| self.condition_to_expression(key, condition))
+            .collect::<Vec<String>>()
+            .join(" and ");
+    }
+}
+
+impl QueryBase for Database {
+    fn get_indexes(&self, table: &str) -> Result<Value, DBError> {
+        let tag_keys = self.query(format!("show tag keys from \"{}\"", table), None)?;
+        let mut tags = self.tags.borrow_mut();
+        for tag_key in tag_keys {
+            if let Some(tag_key) = tag_key["tagKey"].as_str() {
+                tags.insert(tag_key.to_string());
+            }
+        }
+        return Ok(tags.iter().map(|tag| Value::from(tag.as_str())).collect());
+    }
+    
+    fn create_index(&self, _table: &str, index: &str) -> Option<DBError> {
+        self.tags.borrow_mut().insert(index.to_string());
+        return None
+    }
+    
+    fn insert(&self, request: Request) -> Option<DBError> {
+        return self.insert_many(&request.table, vec![request.data.unwrap_or_default()]);
+    }
+    
+    /// The points are written with one request.
+    fn insert_many(&self, table: &str, data: Vec<Args>) -> Option<DBError> {
+        // Fields can't be nested, so nested documents are written as JSON text
+        let data: Vec<Args> = data.into_iter()
+            .map(|data| data.into_iter()
+                .map(|(key, value)| match value {
+                    DBValue::Nested(_) => (key, DBValue::Str(value.to_json(Database::timestamp_to_json).to_string())),
+                    _ => (key, value)
+                })
+                .collect())
+            .collect();
+        let measurements: Vec<Measurement> = data.iter()
+            .map(|data| self.data_to_measurement(table, data))
+            .collect();
+        let has_time = data.iter().any(|data| data.contains_key(FIELD_TIME));
+        let precision = if has_time { Some(Precision::Milliseconds) } else { None };
+        
+        let res = self.connection.client.write_many(&measurements, precision);
+        return self.connection.reactor.borrow_mut().run(res)
+            .err()
+            .map(DBError::from);
+    }
+    
+    fn update(&self, _request: Request) -> Option<DBError> {
+        return Some(DBError::Unsupported(String::from("InfluxDB does not support updates")))
+    }
+    
+    /// Points are always ordered by time, so it is the only supported order field.
+    fn select(&self, request: Request) -> Result<Documents, DBError> {
+        let fields = match request.fields {
+            Some(ref fields) => fields.iter()
+                .map(|field| format!("\"{}\"", field))
+                .collect::<Vec<String>>()
+                .join(", "),
+            None => String::from("*")
+        };
+        let mut query = format!("select {} from \"{}\"", fields, request.table);
+        let condition = self.condition_to_where(&request.get_conditions());
+        if !condition.is_empty() {
+            query = format!("{} where {}", query, condition);
+        }
+        
+        match request.order {
+            Some((ref field, _)) if field != FIELD_TIME => {
+                return Err(DBError::Unsupported(format!("InfluxDB can't order by {}", field)))
+            },
+            Some((_, Order::Desc)) => query = format!("{} order by time desc", query),
+            _ => {}
+        }
+        
+        // The client can't read chunked responses
+        if let Some(batch_size) = request.batch_size {
+            let connection = self.connection.clone();
+            return Ok(Box::new(Pages::new(&request, batch_size, move |skip, limit| {
+                let query = format!("{} limit {} offset {}", query, limit, skip);
+                return connection.query(query, Some(Precision::Milliseconds));
+            })));
+        }
+        
+        if let Some(limit) = request.limit {
+            query = format!("{} limit {}", query, limit);
+        }
+        if let Some(skip) = request.skip {
+            query = format!("{} offset {}", query, skip);
+        }
+        let points = self.query(query, Some(Precision::Milliseconds))?;
+        return Ok(Box::new(points.into_iter().map(Ok)));
+    }
+    
+    fn delete(&self, request: Request) -> Option<DBError> {
+        let mut query = format!("delete from \"{}\"", request.table);
+        let condition = self.condition_to_where(&request.get_conditions());
+        if !condition.is_empty() {
+            query = format!("{} where {}", query, condition);
+        }
+        return self.query(query, None).err();
+    }
+    
+    fn distinct(&self, table: &str, index: &str, limit: usize) -> Result<Vec<Value>, DBError> {
+        let query = format!("show tag values from \"{}\" with key = \"{}\" limit {}", table, index, limit);
+        let tag_values = self.query(query, None)?;
+        
+        return Ok(tag_values.into_iter()
+            .map(|mut tag_value| tag_value["value"].take())
+            .collect());
+    }
+    
+    /// The client doesn't support the `/ping` endpoint, so it is the cheapest query.
+    fn ping(&self) -> Option<DBError> {
+        return self.query(String::from("show databases"), None).err();
+    }
+    
+    /// InfluxDB has no push notifications for clients, so the subscription polls
+    /// for points written after the last one it has seen.
+    fn changes(&self, request: Request, on_each: &mut dyn FnMut(Result<Value, DBError>) -> bool) {
+        let mut last_time = get_current_time() as i64*1000000;
+        let condition = self.condition_to_where(&request.get_conditions());
+        let condition = if condition.is_empty() { condition } else { format!("{} and ", condition) };
+        
+        if !on_each(Ok(json!({ "state": "ready" }))) {
+            return
+        }
+        
+        loop {
+            let query = format!("select * from \"{}\" where {}time > {}", request.table, condition, last_time);
+            let points = match self.query(query, Some(Precision::Nanoseconds)) {
+                Ok(points) => points,
+                Err(err) => {
+                    on_each(Err(err));
+                    return
+                }
+            };
+            
+            for point in points {
+                last_time = point[FIELD_TIME].as_i64().unwrap_or(last_time);
+                if !on_each(Ok(json!({ "new_val": point }))) {
+                    return
+                }
+            }
+            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+        }
+    }
+}
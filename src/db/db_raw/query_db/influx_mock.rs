@@ -0,0 +1,300 @@
+use query_executor::{FIELD_ONE, FIELD_THREE, FIELD_TIME, FIELD_TWO, INFLUX_TABLE, TAG};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+
+static LOCALHOST: &str = "127.0.0.1";
+static EMPTY_RESULT: &str = "{\"results\":[{\"statement_id\":0}]}";
+
+/// Requests the mock has answered.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InfluxMockCounts {
+    pub writes: u64,
+    pub lines: u64,
+    pub queries: u64
+}
+
+struct MockState {
+    latency: Duration,
+    is_recording: AtomicBool,
+    /// Canned series by the query prefix they answer, the first match wins
+    responses: Mutex<Vec<(String, Vec<Value>)>>,
+    /// Statement errors by the query prefix they answer, before any series
+    errors: Mutex<Vec<(String, String)>>,
+    lines: Mutex<Vec<String>>,
+    queries: Mutex<Vec<String>>,
+    counts: Mutex<InfluxMockCounts>
+}
+
+/// Server of the `/write` and `/query` endpoints that `influent::HttpClient`
+/// uses. It keeps no points: written lines are recorded as they arrive and every
+/// query gets the canned series of its prefix, or an empty result. With no
+/// latency the times of `QueryExecutor` against it are the overhead of the
+/// client side alone.
+pub struct InfluxMock {
+    host: &'static str,
+    state: Arc<MockState>
+}
+
+impl InfluxMock {
+    /// Starts the server on a free local port, it runs until the process exits.
+    /// Every response is sent `latency_ms` after its request was read.
+    pub fn start(latency_ms: u64) -> InfluxMock {
+        let listener = TcpListener::bind((LOCALHOST, 0)).expect("Failed to start influx mock");
+        let port = listener.local_addr().expect("No local address").port();
+        let state = Arc::new(MockState {
+            latency: Duration::from_millis(latency_ms),
+            is_recording: AtomicBool::new(true),
+            responses: Mutex::new(Vec::new()),
+            errors: Mutex::new(Vec::new()),
+            lines: Mutex::new(Vec::new()),
+            queries: Mutex::new(Vec::new()),
+            counts: Mutex::new(InfluxMockCounts::default())
+        });
+        
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let state = server_state.clone();
+                thread::spawn(move || serve_http(stream, |request| state.answer(request)));
+            }
+        });
+        
+        return InfluxMock {
+            host: Box::leak(format!("http://{}:{}", LOCALHOST, port).into_boxed_str()),
+            state: state
+        }
+    }
+    
+    /// Answers the queries that start with `prefix`, ignoring case, with `series`.
+    pub fn with_response(self, prefix: &str, series: Vec<Value>) -> InfluxMock {
+        self.state.responses.lock()
+            .expect("Influx mock responses poisoned")
+            .push((prefix.to_lowercase(), series));
+        return self
+    }
+    
+    /// Answers the queries of the InfluxDB `QueryExecutor`: every select gets `rows`
+    /// points and the sample of hashes gets as many series.
+    pub fn with_executor_responses(self, rows: usize) -> InfluxMock {
+        let hashes: Vec<String> = (0..rows).map(|i| format!("mock{}", i)).collect();
+        let points = hashes.iter()
+            .enumerate()
+            .map(|(i, hash)| vec![
+                json!(i),
+                json!(hash),
+                json!(i),
+                json!(format!("{}from", hash)),
+                json!(format!("{}to", hash))
+            ])
+            .collect();
+        let tag_values = hashes.iter()
+            .map(|hash| vec![json!(TAG), json!(hash)])
+            .collect();
+        
+        return self
+            .with_response("select", vec![get_series(INFLUX_TABLE, &[FIELD_TIME, TAG, FIELD_THREE, FIELD_ONE, FIELD_TWO], points)])
+            .with_response("show tag values", vec![get_series(INFLUX_TABLE, &["key", "value"], tag_values)])
+            .with_response("show tag keys", vec![get_series(INFLUX_TABLE, &["tagKey"], vec![vec![json!(TAG)]])])
+    }
+    
+    /// Fails the queries that start with `prefix`, ignoring case, with a statement
+    /// error like the server sends for a query it can't run.
+    #[cfg(test)]
+    pub fn with_error(self, prefix: &str, message: &str) -> InfluxMock {
+        self.state.errors.lock()
+            .expect("Influx mock errors poisoned")
+            .push((prefix.to_lowercase(), message.to_string()));
+        return self
+    }
+    
+    /// Only counts the requests, for long runs that would fill the memory with lines.
+    pub fn without_recording(self) -> InfluxMock {
+        self.state.is_recording.store(false, Ordering::SeqCst);
+        return self
+    }
+    
+    /// URL of the HTTP API.
+    pub fn get_host(&self) -> &'static str {
+        return self.host
+    }
+    
+    pub fn get_counts(&self) -> InfluxMockCounts {
+        return *self.state.counts.lock().expect("Influx mock counts poisoned")
+    }
+    
+    /// Line protocol received since the last call, one entry per point.
+    #[cfg(test)]
+    pub fn take_lines(&self) -> Vec<String> {
+        return self.state.lines.lock()
+            .expect("Influx mock lines poisoned")
+            .split_off(0)
+    }
+    
+    /// Queries received since the last call.
+    #[cfg(test)]
+    pub fn take_queries(&self) -> Vec<String> {
+        return self.state.queries.lock()
+            .expect("Influx mock queries poisoned")
+            .split_off(0)
+    }
+}
+
+impl MockState {
+    fn answer(&self, request: &HttpRequest) -> (u16, String) {
+        thread::sleep(self.latency);
+        return match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/write") => self.write(request),
+            ("GET", "/query") | ("POST", "/query") => self.query(request),
+            _ => (404, String::from("not found"))
+        }
+    }
+    
+    fn write(&self, request: &HttpRequest) -> (u16, String) {
+        let lines: Vec<&str> = request.body.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        {
+            let mut counts = self.counts.lock().expect("Influx mock counts poisoned");
+            counts.writes += 1;
+            counts.lines += lines.len() as u64;
+        }
+        if self.is_recording.load(Ordering::SeqCst) {
+            self.lines.lock()
+                .expect("Influx mock lines poisoned")
+                .extend(lines.into_iter().map(String::from));
+        }
+        return (204, String::new())
+    }
+    
+    fn query(&self, request: &HttpRequest) -> (u16, String) {
+        let query = request.params.get("q").map(String::as_str).unwrap_or_default().trim();
+        self.counts.lock().expect("Influx mock counts poisoned").queries += 1;
+        if self.is_recording.load(Ordering::SeqCst) {
+            self.queries.lock().expect("Influx mock queries poisoned").push(query.to_string());
+        }
+        
+        let query = query.to_lowercase();
+        let errors = self.errors.lock().expect("Influx mock errors poisoned");
+        if let Some((_, message)) = errors.iter().find(|(prefix, _)| query.starts_with(prefix.as_str())) {
+            return (200, json!({ "results": [{ "statement_id": 0, "error": message }] }).to_string())
+        }
+        let responses = self.responses.lock().expect("Influx mock responses poisoned");
+        return match responses.iter().find(|(prefix, _)| query.starts_with(prefix.as_str())) {
+            Some((_, series)) => (200, json!({ "results": [{ "statement_id": 0, "series": series }] }).to_string()),
+            None => (200, String::from(EMPTY_RESULT))
+        }
+    }
+}
+
+/// Series of a query response, `rows` hold the values of the `columns` in order.
+pub fn get_series(name: &str, columns: &[&str], rows: Vec<Vec<Value>>) -> Value {
+    return json!({
+        "name": name,
+        "columns": columns,
+        "values": rows
+    })
+}
+
+/// Request of the HTTP API with its query string decoded.
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub params: HashMap<String, String>,
+    pub body: String
+}
+
+/// Answers the requests of a keep-alive connection with the status and JSON body
+/// that `answer` returns, until the connection is closed.
+pub fn serve_http<F>(stream: TcpStream, mut answer: F)
+    where F: FnMut(&HttpRequest) -> (u16, String) {
+    
+    let mut reader = BufReader::new(stream.try_clone().expect("Failed to clone http stream"));
+    let mut writer = stream;
+    while let Some(request) = read_request(&mut reader) {
+        let (status, body) = answer(&request);
+        let response = format!("HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body);
+        if writer.write_all(response.as_bytes()).is_err() {
+            return
+        }
+    }
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<HttpRequest> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).ok()? == 0 {
+        return None
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let header = header.trim();
+        if header.is_empty() {
+            break
+        }
+        let mut pair = header.splitn(2, ':');
+        let name = pair.next()?.trim().to_lowercase();
+        if name == "content-length" {
+            content_length = pair.next()?.trim().parse().ok()?;
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+    
+    let mut target = target.splitn(2, '?');
+    let path = target.next()?.to_string();
+    let params = target.next()
+        .map(|query| query.split('&')
+            .filter_map(|pair| {
+                let mut pair = pair.splitn(2, '=');
+                return Some((decode_param(pair.next()?), decode_param(pair.next().unwrap_or_default())))
+            })
+            .collect())
+        .unwrap_or_default();
+    
+    return Some(HttpRequest {
+        method: method,
+        path: path,
+        params: params,
+        body: String::from_utf8_lossy(&body).into_owned()
+    })
+}
+
+/// Decodes an `application/x-www-form-urlencoded` value.
+fn decode_param(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            u8::from_str_radix(&text[i + 1..i + 3], 16).ok()
+        } else {
+            None
+        };
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            },
+            (b'+', None) => decoded.push(b' '),
+            (byte, None) => decoded.push(byte)
+        }
+        i += 1;
+    }
+    return String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,377 @@
+use super::*;
+use rand;
+use rand::Rng;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+
+static DEFAULT_STORE: &str = "default";
+static POLL_INTERVAL_MS: u64 = 100;
+static STORES: OnceLock<Mutex<HashMap<String, Arc<Store>>>> = OnceLock::new();
+
+/// Artificial latency and failures of the in-memory database.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryConfig {
+    latency: u64,
+    jitter: u64,
+    error_rate: f64
+}
+
+impl MemoryConfig {
+    /// Every request waits `latency` plus up to `jitter` milliseconds and fails with
+    /// an injected connection error at `error_rate`, from 0 to 1.
+    pub fn new(latency: u64, jitter: u64, error_rate: f64) -> MemoryConfig {
+        return MemoryConfig {
+            latency: latency,
+            jitter: jitter,
+            error_rate: error_rate
+        }
+    }
+    
+    pub fn get_latency(&self) -> u64 {
+        return self.latency
+    }
+    
+    pub fn get_jitter(&self) -> u64 {
+        return self.jitter
+    }
+    
+    pub fn get_error_rate(&self) -> f64 {
+        return self.error_rate
+    }
+}
+
+#[derive(Default)]
+struct MemoryTable {
+    /// Documents by insertion sequence
+    documents: BTreeMap<u64, Value>,
+    /// Sequences of the documents by index field and value text
+    indexes: HashMap<String, HashMap<String, BTreeSet<u64>>>,
+    next_seq: u64
+}
+
+impl MemoryTable {
+    fn insert(&mut self, document: Value) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        for (index, values) in self.indexes.iter_mut() {
+            values.entry(document[index].to_string()).or_default().insert(seq);
+        }
+        self.documents.insert(seq, document);
+    }
+    
+    fn remove(&mut self, seq: u64) {
+        if let Some(document) = self.documents.remove(&seq) {
+            for (index, values) in self.indexes.iter_mut() {
+                let key = document[index].to_string();
+                let is_empty = values.get_mut(&key).is_some_and(|seqs| {
+                    seqs.remove(&seq);
+                    seqs.is_empty()
+                });
+                if is_empty {
+                    values.remove(&key);
+                }
+            }
+        }
+    }
+    
+    fn create_index(&mut self, index: &str) {
+        if self.indexes.contains_key(index) {
+            return
+        }
+        let mut values: HashMap<String, BTreeSet<u64>> = HashMap::new();
+        for (seq, document) in self.documents.iter() {
+            values.entry(document[index].to_string()).or_default().insert(*seq);
+        }
+        self.indexes.insert(index.to_string(), values);
+    }
+    
+    /// Sequences of the documents matching `conditions` after `after_seq`, an equality
+    /// condition on an index only visits the documents with that value.
+    fn find(&self, conditions: &Conditions, after_seq: Option<u64>) -> Vec<u64> {
+        let indexed = conditions.iter().find_map(|(key, condition)| match *condition {
+            Condition::Eq(ref value) => self.indexes.get(key).map(|values| values.get(&value_to_json(value).to_string())),
+            _ => None
+        });
+        let candidates: Box<dyn Iterator<Item = u64>> = match indexed {
+            Some(Some(seqs)) => Box::new(seqs.iter().cloned()),
+            Some(None) => return Vec::new(),
+            None => Box::new(self.documents.keys().cloned())
+        };
+        
+        return candidates
+            .filter(|seq| after_seq.is_none_or(|after_seq| *seq > after_seq))
+            .filter(|seq| is_match(&self.documents[seq], conditions))
+            .collect();
+    }
+}
+
+/// Tables of one in-memory database, every connection to it shares them.
+#[derive(Default)]
+struct Store {
+    tables: Mutex<HashMap<Table, MemoryTable>>,
+    /// Notified on every insert, for the change subscriptions
+    inserted: Condvar
+}
+
+/// A database that keeps its documents in process, so the benchmark runs without
+/// a server. Documents are stored as JSON with timestamps in epoch milliseconds.
+pub struct Database {
+    store: Arc<Store>,
+    config: MemoryConfig
+}
+
+impl Database {
+    /// `host` names the store, databases with the same name share their documents
+    /// until the process exits.
+    pub fn new(host: Option<&'static str>, config: MemoryConfig) -> Database {
+        let mut stores = STORES.get_or_init(|| Mutex::new(HashMap::new())).lock().expect("Memory store poisoned");
+        let store = stores.entry(host.unwrap_or(DEFAULT_STORE).to_string()).or_default().clone();
+        
+        return Database {
+            store: store,
+            config: config
+        }
+    }
+    
+    /// Waits the artificial latency and fails at the configured error rate.
+    fn simulate(&self) -> Result<(), DBError> {
+        let mut rng = rand::thread_rng();
+        let jitter = if self.config.jitter > 0 { rng.gen_range(0, self.config.jitter+1) } else { 0 };
+        if self.config.latency+jitter > 0 {
+            thread::sleep(Duration::from_millis(self.config.latency+jitter));
+        }
+        if self.config.error_rate > 0.0 && rng.gen::<f64>() < self.config.error_rate {
+            return Err(DBError::Injected(String::from("Injected memory database error")))
+        }
+        return Ok(())
+    }
+    
+    fn lock(&self) -> MutexGuard<'_, HashMap<Table, MemoryTable>> {
+        return self.store.tables.lock().expect("Memory store poisoned")
+    }
+    
+    fn timestamp_to_json(millis: i64) -> Value {
+        return Value::from(millis);
+    }
+    
+    fn select_documents(&self, request: &Request) -> Vec<Value> {
+        let tables = self.lock();
+        let table = match tables.get(&request.table) {
+            Some(table) => table,
+            None => return Vec::new()
+        };
+        let mut documents: Vec<&Value> = table.find(&request.get_conditions(), None).iter()
+            .map(|seq| &table.documents[seq])
+            .collect();
+        if let Some((ref field, ref order)) = request.order {
+            // The sort is stable, so equal values keep the insertion order
+            documents.sort_by(|a, b| {
+                let ordering = compare_json(&a[field], &b[field]).unwrap_or(Ordering::Equal);
+                return match *order {
+                    Order::Asc => ordering,
+                    Order::Desc => ordering.reverse()
+                }
+            });
+        }
+        
+        return documents.into_iter()
+            .skip(request.skip.unwrap_or(0))
+            .take(request.limit.unwrap_or(usize::MAX))
+            .map(|document| match request.fields {
+                Some(ref fields) => Value::Object(fields.iter()
+                    .filter(|field| !document[field.as_str()].is_null())
+                    .map(|field| (field.clone(), document[field.as_str()].clone()))
+                    .collect()),
+                None => document.clone()
+            })
+            .collect();
+    }
+}
+
+impl QueryBase for Database {
+    fn get_indexes(&self, table: &str) -> Result<Value, DBError> {
+        self.simulate()?;
+        let tables = self.lock();
+        return Ok(tables.get(table)
+            .map(|table| table.indexes.keys().map(|index| Value::from(index.as_str())).collect())
+            .unwrap_or_else(|| Value::Array(Vec::new())));
+    }
+    
+    fn create_index(&self, table: &str, index: &str) -> Option<DBError> {
+        if let Err(err) = self.simulate() {
+            return Some(err)
+        }
+        self.lock().entry(table.to_string()).or_default().create_index(index);
+        return None
+    }
+    
+    fn insert(&self, request: Request) -> Option<DBError> {
+        let data = request.data.unwrap_or_default();
+        return self.insert_many(&request.table, vec![data]);
+    }
+    
+    fn insert_many(&self, table: &str, data: Vec<Args>) -> Option<DBError> {
+        if let Err(err) = self.simulate() {
+            return Some(err)
+        }
+        let documents: Vec<Value> = data.iter()
+            .map(|data| args_to_json(data, Database::timestamp_to_json))
+            .collect();
+        
+        let mut tables = self.lock();
+        let memory_table = tables.entry(table.to_string()).or_default();
+        for document in documents {
+            memory_table.insert(document);
+        }
+        self.store.inserted.notify_all();
+        return None
+    }
+    
+    fn update(&self, request: Request) -> Option<DBError> {
+        if let Err(err) = self.simulate() {
+            return Some(err)
+        }
+        let data = request.data_to_json(Database::timestamp_to_json);
+        let data = data.as_object().expect("Invalid update data");
+        
+        let mut tables = self.lock();
+        let table = tables.get_mut(&request.table)?;
+        for seq in table.find(&request.get_conditions(), None) {
+            let mut document = table.documents[&seq].clone();
+            for (key, value) in data.iter() {
+                document[key] = value.clone();
+            }
+            // Reinserting keeps the indexes up to date
+            table.remove(seq);
+            table.insert(document);
+        }
+        return None
+    }
+    
+    fn select(&self, request: Request) -> Result<Documents, DBError> {
+        self.simulate()?;
+        let documents = self.select_documents(&request);
+        return Ok(Box::new(documents.into_iter().map(Ok)));
+    }
+    
+    fn delete(&self, request: Request) -> Option<DBError> {
+        if let Err(err) = self.simulate() {
+            return Some(err)
+        }
+        let mut tables = self.lock();
+        if let Some(table) = tables.get_mut(&request.table) {
+            for seq in table.find(&request.get_conditions(), None) {
+                table.remove(seq);
+            }
+        }
+        return None
+    }
+    
+    fn compact(&self, _table: &str) -> Option<DBError> {
+        return self.simulate().err();
+    }
+    
+    fn distinct(&self, table: &str, index: &str, limit: usize) -> Result<Vec<Value>, DBError> {
+        self.simulate()?;
+        let tables = self.lock();
+        let table = match tables.get(table) {
+            Some(table) => table,
+            None => return Ok(Vec::new())
+        };
+        let mut seen = HashSet::new();
+        return Ok(table.documents.values()
+            .map(|document| &document[index])
+            .filter(|value| !value.is_null() && seen.insert(value.to_string()))
+            .take(limit)
+            .cloned()
+            .collect());
+    }
+    
+    fn ping(&self) -> Option<DBError> {
+        return self.simulate().err();
+    }
+    
+    /// Only documents inserted after the subscription are reported, it waits for
+    /// inserts up to the poll interval before it checks again.
+    fn changes(&self, request: Request, on_each: &mut dyn FnMut(Result<Value, DBError>) -> bool) {
+        if let Err(err) = self.simulate() {
+            on_each(Err(err));
+            return
+        }
+        let conditions = request.get_conditions();
+        let mut last_seq = self.lock().get(&request.table).map(|table| table.next_seq);
+        
+        if !on_each(Ok(json!({ "state": "ready" }))) {
+            return
+        }
+        
+        loop {
+            let changes: Vec<Value> = {
+                let tables = self.lock();
+                let (tables, _) = self.store.inserted
+                    .wait_timeout_while(tables, Duration::from_millis(POLL_INTERVAL_MS), |tables| {
+                        return tables.get(&request.table).map(|table| table.next_seq) == last_seq
+                    })
+                    .expect("Memory store poisoned");
+                match tables.get(&request.table) {
+                    Some(table) => {
+                        let after_seq = last_seq.and_then(|next_seq| next_seq.checked_sub(1));
+                        let changes = table.find(&conditions, after_seq).iter()
+                            .map(|seq| json!({ "new_val": table.documents[seq] }))
+                            .collect();
+                        last_seq = Some(table.next_seq);
+                        changes
+                    },
+                    None => Vec::new()
+                }
+            };
+            
+            for change in changes {
+                if !on_each(Ok(change)) {
+                    return
+                }
+            }
+        }
+    }
+}
+
+fn value_to_json(value: &DBValue) -> Value {
+    return value.to_json(Database::timestamp_to_json);
+}
+
+fn is_match(document: &Value, conditions: &Conditions) -> bool {
+    return conditions.iter().all(|(key, condition)| {
+        let field = &document[key.as_str()];
+        return match *condition {
+            Condition::Eq(ref value) => compare_json(field, &value_to_json(value)) == Some(Ordering::Equal),
+            Condition::Ne(ref value) => compare_json(field, &value_to_json(value)) != Some(Ordering::Equal),
+            Condition::Lt(ref value) => compare_json(field, &value_to_json(value)) == Some(Ordering::Less),
+            Condition::Gt(ref value) => compare_json(field, &value_to_json(value)) == Some(Ordering::Greater),
+            Condition::Range(ref from, ref to) => {
+                compare_json(field, &value_to_json(from)).is_some_and(|ordering| ordering != Ordering::Less)
+                    && compare_json(field, &value_to_json(to)) == Some(Ordering::Less)
+            },
+            Condition::In(ref values) => values.iter()
+                .any(|value| compare_json(field, &value_to_json(value)) == Some(Ordering::Equal))
+        }
+    });
+}
+
+/// Numbers compare by value and strings by text, other values only when they are
+/// equal. `None` when they can't be compared.
+pub fn compare_json(a: &Value, b: &Value) -> Option<Ordering> {
+    return match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => a.as_f64()?.partial_cmp(&b.as_f64()?)
+        },
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (a, b) if !a.is_null() && a == b => Some(Ordering::Equal),
+        _ => None
+    }
+}
@@ -0,0 +1,576 @@
+use super::*;
+use super::stand_in::{compare_values, distinct_values, new_tables, serve, sort_documents, MockTables};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::{Arc, Mutex};
+use get_current_time;
+use super::influx_mock::{get_series, serve_http, HttpRequest};
+
+
+static DATABASE: &str = "bench";
+static FIELD_TIME: &str = "time";
+static NANOS_PER_MILLI: i64 = 1000000;
+
+/// Mock of the `/write` and `/query` endpoints of the InfluxDB HTTP API. Unlike
+/// `InfluxMock` it keeps the points, with their time in nanoseconds, and queries
+/// them with the subset of InfluxQL that the facade writes. Like the server it
+/// drops the points whose fields don't have the type they were first written with.
+struct MockInflux {
+    tables: MockTables,
+    tags: Mutex<HashMap<String, BTreeSet<String>>>,
+    /// Type of every field by table
+    fields: Mutex<HashMap<String, HashMap<String, &'static str>>>
+}
+
+/// Starts a mock server and returns its port.
+pub fn start() -> u16 {
+    let mock = Arc::new(MockInflux {
+        tables: new_tables(),
+        tags: Mutex::new(HashMap::new()),
+        fields: Mutex::new(HashMap::new())
+    });
+    return serve(move |stream| serve_http(stream, |request| mock.answer(request)))
+}
+
+impl MockInflux {
+    fn answer(&self, request: &HttpRequest) -> (u16, String) {
+        return match (request.method.as_str(), request.path.as_str()) {
+            ("POST", "/write") => self.write(request),
+            ("GET", "/query") | ("POST", "/query") => self.query(request),
+            _ => (404, String::from("not found"))
+        }
+    }
+    
+    fn write(&self, request: &HttpRequest) -> (u16, String) {
+        if request.params.get("db").map(String::as_str) != Some(DATABASE) {
+            return (404, json!({ "error": "database not found" }).to_string())
+        }
+        let nanos_per_unit = get_nanos_per_unit(request.params.get("precision"));
+        let mut tables = self.tables.lock().expect("Mock tables poisoned");
+        let mut tags = self.tags.lock().expect("Mock tags poisoned");
+        let mut fields = self.fields.lock().expect("Mock fields poisoned");
+        let mut dropped = 0;
+        let mut first_conflict = None;
+        
+        for line in request.body.lines().filter(|line| !line.trim().is_empty()) {
+            let (table, point_tags, mut point) = match parse_line(line, nanos_per_unit) {
+                Some(parsed) => parsed,
+                None => return (400, json!({ "error": format!("unable to parse '{}'", line) }).to_string())
+            };
+            let point_fields: Vec<(String, &'static str)> = point.as_object().into_iter().flatten()
+                .filter(|(key, _)| *key != FIELD_TIME && !point_tags.contains(key))
+                .map(|(key, value)| (key.clone(), get_field_type(value)))
+                .collect();
+            let table_fields = fields.entry(table.clone()).or_default();
+            let conflict = point_fields.iter()
+                .find_map(|(key, field_type)| table_fields.get(key)
+                    .filter(|existing_type| *existing_type != field_type)
+                    .map(|existing_type| (key, field_type, existing_type)));
+            if let Some((key, field_type, existing_type)) = conflict {
+                dropped += 1;
+                first_conflict.get_or_insert_with(|| format!("field type conflict: input field \"{}\" on measurement \"{}\" is type {}, already exists as type {}",
+                    key, table, field_type, existing_type));
+                continue
+            }
+            
+            table_fields.extend(point_fields);
+            if point[FIELD_TIME].is_null() {
+                point[FIELD_TIME] = Value::from(get_current_time() as i64*NANOS_PER_MILLI);
+            }
+            tags.entry(table.clone()).or_default().extend(point_tags);
+            tables.entry(table).or_default().push(point);
+        }
+        if let Some(conflict) = first_conflict {
+            return (400, json!({ "error": format!("partial write: {} dropped={}", conflict, dropped) }).to_string())
+        }
+        return (204, String::new())
+    }
+    
+    fn query(&self, request: &HttpRequest) -> (u16, String) {
+        let query = request.params.get("q").map(String::as_str).unwrap_or_default();
+        let nanos_per_unit = request.params.get("epoch").map(|epoch| get_nanos_per_unit(Some(epoch)));
+        let series = match Statement::parse(query) {
+            Some(statement) => self.execute(statement, nanos_per_unit),
+            None => return (400, json!({ "error": format!("error parsing query: {}", query) }).to_string())
+        };
+        
+        let mut result = json!({ "statement_id": 0 });
+        if !series.is_empty() {
+            result["series"] = Value::Array(series);
+        }
+        return (200, json!({ "results": [result] }).to_string())
+    }
+    
+    fn execute(&self, statement: Statement, nanos_per_unit: Option<i64>) -> Vec<Value> {
+        let mut tables = self.tables.lock().expect("Mock tables poisoned");
+        let tags = self.tags.lock().expect("Mock tags poisoned");
+        
+        return match statement {
+            Statement::ShowDatabases => vec![get_series("databases", &["name"], vec![vec![Value::from(DATABASE)]])],
+            Statement::ShowTagKeys(table) => match tags.get(&table) {
+                Some(keys) => vec![get_series(&table, &["tagKey"], keys.iter().map(|key| vec![Value::from(key.as_str())]).collect())],
+                None => Vec::new()
+            },
+            Statement::ShowTagValues(table, key, limit) => {
+                let points = tables.get(&table).map(Vec::as_slice).unwrap_or_default();
+                let mut values = distinct_values(points, &key);
+                values.sort_by(|a, b| compare_values(a, b).unwrap_or(Ordering::Equal));
+                let rows: Vec<Vec<Value>> = values.into_iter()
+                    .take(limit.unwrap_or(usize::MAX))
+                    .map(|value| vec![Value::from(key.as_str()), value])
+                    .collect();
+                if rows.is_empty() { Vec::new() } else { vec![get_series(&table, &["key", "value"], rows)] }
+            },
+            Statement::Delete(table, condition) => {
+                if let Some(points) = tables.get_mut(&table) {
+                    points.retain(|point| !condition.is_match(point));
+                }
+                Vec::new()
+            },
+            Statement::Select(select) => {
+                let mut points: Vec<Value> = tables.get(&select.table)
+                    .map(|points| points.iter().filter(|point| select.condition.is_match(point)).cloned().collect())
+                    .unwrap_or_default();
+                sort_documents(&mut points, FIELD_TIME, select.is_descending);
+                let points: Vec<Value> = points.into_iter()
+                    .skip(select.offset.unwrap_or(0))
+                    .take(select.limit.unwrap_or(usize::MAX))
+                    .collect();
+                if points.is_empty() {
+                    return Vec::new()
+                }
+                
+                let mut columns = vec![String::from(FIELD_TIME)];
+                match select.fields {
+                    Some(fields) => columns.extend(fields.into_iter().filter(|field| field != FIELD_TIME)),
+                    None => {
+                        let keys: BTreeSet<&String> = points.iter()
+                            .filter_map(Value::as_object)
+                            .flat_map(|point| point.keys())
+                            .filter(|key| *key != FIELD_TIME)
+                            .collect();
+                        columns.extend(keys.into_iter().cloned());
+                    }
+                }
+                let rows = points.iter()
+                    .map(|point| columns.iter()
+                        .map(|column| match column.as_str() {
+                            "time" => time_to_json(&point[FIELD_TIME], nanos_per_unit),
+                            column => point[column].clone()
+                        })
+                        .collect())
+                    .collect();
+                let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+                vec![get_series(&select.table, &columns, rows)]
+            }
+        }
+    }
+}
+
+fn get_field_type(value: &Value) -> &'static str {
+    return match *value {
+        Value::String(_) => "string",
+        Value::Bool(_) => "boolean",
+        Value::Number(ref number) if number.is_f64() => "float",
+        _ => "integer"
+    }
+}
+
+/// Times without an epoch are returned as nanoseconds, not as RFC3339 text.
+fn time_to_json(nanos: &Value, nanos_per_unit: Option<i64>) -> Value {
+    return match nanos.as_i64() {
+        Some(nanos) => Value::from(nanos/nanos_per_unit.unwrap_or(1)),
+        None => Value::Null
+    }
+}
+
+fn get_nanos_per_unit(precision: Option<&String>) -> i64 {
+    return match precision.map(String::as_str) {
+        Some("ms") => NANOS_PER_MILLI,
+        Some("u") => 1000,
+        Some("s") => 1000*NANOS_PER_MILLI,
+        Some("m") => 60*1000*NANOS_PER_MILLI,
+        Some("h") => 60*60*1000*NANOS_PER_MILLI,
+        _ => 1
+    }
+}
+
+/// Splits `text` into at most `limit` parts at the unescaped `separator`s that
+/// aren't in double quotes, the escapes are kept.
+fn split_unescaped(text: &str, separator: char, limit: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut is_quoted = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                part.push(c);
+                part.extend(chars.next());
+            },
+            '"' => {
+                is_quoted = !is_quoted;
+                part.push(c);
+            },
+            c if c == separator && !is_quoted && parts.len() + 1 < limit => parts.push(part.split_off(0)),
+            c => part.push(c)
+        }
+    }
+    parts.push(part);
+    return parts
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c)
+        }
+    }
+    return unescaped
+}
+
+/// Parses a line of the line protocol into its measurement, its tag keys and a
+/// document of its tags, fields and time in nanoseconds.
+fn parse_line(line: &str, nanos_per_unit: i64) -> Option<(String, Vec<String>, Value)> {
+    let parts = split_unescaped(line.trim(), ' ', 3);
+    if parts.len() < 2 {
+        return None
+    }
+    let mut point = serde_json::Map::new();
+    let mut tags = Vec::new();
+    
+    let mut series = split_unescaped(&parts[0], ',', usize::MAX).into_iter();
+    let table = unescape(&series.next()?);
+    for tag in series {
+        let pair = split_unescaped(&tag, '=', 2);
+        let key = unescape(pair.first()?);
+        point.insert(key.clone(), Value::from(unescape(pair.get(1)?)));
+        tags.push(key);
+    }
+    
+    for field in split_unescaped(&parts[1], ',', usize::MAX) {
+        let pair = split_unescaped(&field, '=', 2);
+        let value = pair.get(1)?;
+        let value = if value.starts_with('"') && value.ends_with('"') && value.len() >= 2 {
+            Value::from(unescape(&value[1..value.len() - 1]))
+        } else if let Some(integer) = value.strip_suffix('i') {
+            Value::from(integer.parse::<i64>().ok()?)
+        } else if value == "t" || value == "true" || value == "T" || value == "TRUE" {
+            Value::from(true)
+        } else if value == "f" || value == "false" || value == "F" || value == "FALSE" {
+            Value::from(false)
+        } else {
+            Value::from(value.parse::<f64>().ok()?)
+        };
+        point.insert(unescape(pair.first()?), value);
+    }
+    
+    if let Some(time) = parts.get(2) {
+        point.insert(String::from(FIELD_TIME), Value::from(time.trim().parse::<i64>().ok()?*nanos_per_unit));
+    }
+    return Some((table, tags, Value::Object(point)))
+}
+
+#[derive(Debug)]
+enum Statement {
+    ShowDatabases,
+    ShowTagKeys(String),
+    ShowTagValues(String, String, Option<usize>),
+    Select(Select),
+    Delete(String, Expression)
+}
+
+#[derive(Debug)]
+struct Select {
+    table: String,
+    fields: Option<Vec<String>>,
+    condition: Expression,
+    is_descending: bool,
+    limit: Option<usize>,
+    offset: Option<usize>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Identifier(String),
+    Text(String),
+    /// A number with its unit suffix, if any
+    Number(f64, String),
+    Operator(String),
+    Symbol(char)
+}
+
+#[derive(Debug)]
+enum Expression {
+    Bool(bool),
+    And(Vec<Expression>),
+    Or(Vec<Expression>),
+    Compare(String, String, Value)
+}
+
+impl Expression {
+    fn is_match(&self, point: &Value) -> bool {
+        return match *self {
+            Expression::Bool(value) => value,
+            Expression::And(ref expressions) => expressions.iter().all(|expression| expression.is_match(point)),
+            Expression::Or(ref expressions) => expressions.iter().any(|expression| expression.is_match(point)),
+            Expression::Compare(ref key, ref operator, ref value) => {
+                let ordering = compare_values(&point[key.as_str()], value);
+                match operator.as_str() {
+                    "=" => ordering == Some(Ordering::Equal),
+                    "!=" | "<>" => ordering != Some(Ordering::Equal),
+                    "<" => ordering == Some(Ordering::Less),
+                    "<=" => ordering.is_some_and(|ordering| ordering != Ordering::Greater),
+                    ">" => ordering == Some(Ordering::Greater),
+                    ">=" => ordering.is_some_and(|ordering| ordering != Ordering::Less),
+                    _ => false
+                }
+            }
+        }
+    }
+}
+
+impl Statement {
+    fn parse(query: &str) -> Option<Statement> {
+        let mut parser = Parser { tokens: tokenize(query)?.into_iter().peekable() };
+        let statement = parser.statement()?;
+        return if parser.tokens.next().is_none() { Some(statement) } else { None }
+    }
+}
+
+struct Parser {
+    tokens: Peekable<vec::IntoIter<Token>>
+}
+
+impl Parser {
+    fn is_word(&mut self, word: &str) -> bool {
+        if let Some(Token::Word(ref next)) = self.tokens.peek() {
+            if next.eq_ignore_ascii_case(word) {
+                self.tokens.next();
+                return true
+            }
+        }
+        return false
+    }
+    
+    fn expect_word(&mut self, word: &str) -> Option<()> {
+        return if self.is_word(word) { Some(()) } else { None }
+    }
+    
+    fn name(&mut self) -> Option<String> {
+        return match self.tokens.next()? {
+            Token::Identifier(name) | Token::Word(name) => Some(name),
+            _ => None
+        }
+    }
+    
+    fn number(&mut self) -> Option<usize> {
+        return match self.tokens.next()? {
+            Token::Number(number, ref unit) if unit.is_empty() => Some(number as usize),
+            _ => None
+        }
+    }
+    
+    fn statement(&mut self) -> Option<Statement> {
+        if self.is_word("show") {
+            if self.is_word("databases") {
+                return Some(Statement::ShowDatabases)
+            }
+            self.expect_word("tag")?;
+            if self.is_word("keys") {
+                self.expect_word("from")?;
+                return Some(Statement::ShowTagKeys(self.name()?))
+            }
+            self.expect_word("values")?;
+            self.expect_word("from")?;
+            let table = self.name()?;
+            self.expect_word("with")?;
+            self.expect_word("key")?;
+            if self.tokens.next()? != Token::Operator(String::from("=")) {
+                return None
+            }
+            let key = self.name()?;
+            let limit = if self.is_word("limit") { Some(self.number()?) } else { None };
+            return Some(Statement::ShowTagValues(table, key, limit))
+        }
+        if self.is_word("delete") {
+            self.expect_word("from")?;
+            let table = self.name()?;
+            let condition = if self.is_word("where") { self.or()? } else { Expression::Bool(true) };
+            return Some(Statement::Delete(table, condition))
+        }
+        
+        self.expect_word("select")?;
+        let fields = if self.tokens.peek() == Some(&Token::Operator(String::from("*"))) {
+            self.tokens.next();
+            None
+        } else {
+            let mut fields = vec![self.name()?];
+            while self.tokens.peek() == Some(&Token::Symbol(',')) {
+                self.tokens.next();
+                fields.push(self.name()?);
+            }
+            Some(fields)
+        };
+        self.expect_word("from")?;
+        let table = self.name()?;
+        let condition = if self.is_word("where") { self.or()? } else { Expression::Bool(true) };
+        let mut is_descending = false;
+        if self.is_word("order") {
+            self.expect_word("by")?;
+            if self.name()? != FIELD_TIME {
+                return None
+            }
+            is_descending = self.is_word("desc");
+            if !is_descending {
+                self.is_word("asc");
+            }
+        }
+        let limit = if self.is_word("limit") { Some(self.number()?) } else { None };
+        let offset = if self.is_word("offset") { Some(self.number()?) } else { None };
+        
+        return Some(Statement::Select(Select {
+            table: table,
+            fields: fields,
+            condition: condition,
+            is_descending: is_descending,
+            limit: limit,
+            offset: offset
+        }))
+    }
+    
+    fn or(&mut self) -> Option<Expression> {
+        let mut expressions = vec![self.and()?];
+        while self.is_word("or") {
+            expressions.push(self.and()?);
+        }
+        return Some(Expression::Or(expressions))
+    }
+    
+    fn and(&mut self) -> Option<Expression> {
+        let mut expressions = vec![self.primary()?];
+        while self.is_word("and") {
+            expressions.push(self.primary()?);
+        }
+        return Some(Expression::And(expressions))
+    }
+    
+    fn primary(&mut self) -> Option<Expression> {
+        if self.tokens.peek() == Some(&Token::Symbol('(')) {
+            self.tokens.next();
+            let expression = self.or()?;
+            return if self.tokens.next()? == Token::Symbol(')') { Some(expression) } else { None }
+        }
+        if self.is_word("true") {
+            return Some(Expression::Bool(true))
+        }
+        if self.is_word("false") {
+            return Some(Expression::Bool(false))
+        }
+        
+        let key = self.name()?;
+        let operator = match self.tokens.next()? {
+            Token::Operator(operator) => operator,
+            _ => return None
+        };
+        let value = match self.tokens.next()? {
+            Token::Text(text) => Value::from(text),
+            Token::Word(ref word) if word.eq_ignore_ascii_case("true") => Value::from(true),
+            Token::Word(ref word) if word.eq_ignore_ascii_case("false") => Value::from(false),
+            // Durations and bare integers compared with the time are nanoseconds
+            Token::Number(number, unit) => match unit.as_str() {
+                "" if key != FIELD_TIME => Value::from(number),
+                "" | "ns" => Value::from(number as i64),
+                "u" | "µ" => Value::from(number as i64*1000),
+                "ms" => Value::from(number as i64*NANOS_PER_MILLI),
+                "s" => Value::from(number as i64*1000*NANOS_PER_MILLI),
+                _ => return None
+            },
+            _ => return None
+        };
+        return Some(Expression::Compare(key, operator, value))
+    }
+}
+
+fn read_quoted(chars: &mut Peekable<Chars>, quote: char) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        match chars.next()? {
+            '\\' => text.push(chars.next()?),
+            c if c == quote => return Some(text),
+            c => text.push(c)
+        }
+    }
+}
+
+fn tokenize(query: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            },
+            '"' | '\'' => {
+                chars.next();
+                let text = read_quoted(&mut chars, c)?;
+                tokens.push(if c == '"' { Token::Identifier(text) } else { Token::Text(text) });
+            },
+            '(' | ')' | ',' | ';' => {
+                chars.next();
+                if c != ';' {
+                    tokens.push(Token::Symbol(c));
+                }
+            },
+            '=' | '!' | '<' | '>' | '*' => {
+                let mut operator = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !"=!<>*".contains(c) {
+                        break
+                    }
+                    operator.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Operator(operator));
+            },
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '-' || c == '.' || c == 'e') {
+                        break
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                let mut unit = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_alphabetic() {
+                        break
+                    }
+                    unit.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Number(number.parse().ok()?, unit));
+            },
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            },
+            _ => return None
+        }
+    }
+    return Some(tokens)
+}
@@ -0,0 +1,392 @@
+use super::*;
+use super::stand_in::{compare_values, distinct_values, new_tables, pluck, serve, sort_documents, MockTables};
+use mongodb::{decode_document, encode_document, Bson, Document};
+use mongodb::spec::BinarySubtype;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+
+static OP_REPLY: i32 = 1;
+static OP_QUERY: i32 = 2004;
+static OP_GET_MORE: i32 = 2005;
+static SERVER_VERSION: &str = "3.4.0";
+static CLIENT_NONCE_KEY: &str = ",r=";
+
+/// Mock of a standalone MongoDB server that speaks the `OP_QUERY` wire protocol
+/// of the driver. Documents are kept as extended JSON and every command the
+/// facade sends is answered; authentication always succeeds.
+struct MockMongo {
+    tables: MockTables,
+    indexes: Mutex<HashMap<String, Vec<String>>>,
+    cursors: Mutex<HashMap<i64, (usize, VecDeque<Document>)>>,
+    next_cursor_id: Mutex<i64>
+}
+
+/// Starts a mock server and returns its port.
+pub fn start() -> u16 {
+    let mock = Arc::new(MockMongo {
+        tables: new_tables(),
+        indexes: Mutex::new(HashMap::new()),
+        cursors: Mutex::new(HashMap::new()),
+        next_cursor_id: Mutex::new(1)
+    });
+    return serve(move |stream| mock.handle(stream))
+}
+
+fn read_i32(bytes: &mut &[u8]) -> Option<i32> {
+    let mut buffer = [0; 4];
+    bytes.read_exact(&mut buffer).ok()?;
+    return Some(i32::from_le_bytes(buffer))
+}
+
+fn read_i64(bytes: &mut &[u8]) -> Option<i64> {
+    let mut buffer = [0; 8];
+    bytes.read_exact(&mut buffer).ok()?;
+    return Some(i64::from_le_bytes(buffer))
+}
+
+fn read_cstring(bytes: &mut &[u8]) -> Option<String> {
+    let end = bytes.iter().position(|byte| *byte == 0)?;
+    let text = String::from_utf8_lossy(&bytes[..end]).into_owned();
+    *bytes = &bytes[end + 1..];
+    return Some(text)
+}
+
+fn document_to_json(document: Document) -> Value {
+    return Value::from(Bson::Document(document))
+}
+
+fn json_to_document(value: Value) -> Document {
+    return match Bson::from(value) {
+        Bson::Document(document) => document,
+        _ => Document::new()
+    }
+}
+
+fn get_table(namespace: &str) -> String {
+    return namespace.split_once('.').map(|(_, table)| table).unwrap_or_default().to_string()
+}
+
+/// Compares the field of a document with the operators of a filter.
+fn is_operator_match(field: &Value, operator: &str, value: &Value) -> bool {
+    let ordering = compare_values(field, value);
+    return match operator {
+        "$eq" => ordering == Some(Ordering::Equal),
+        "$ne" => ordering != Some(Ordering::Equal),
+        "$lt" => ordering == Some(Ordering::Less),
+        "$lte" => ordering.is_some_and(|ordering| ordering != Ordering::Greater),
+        "$gt" => ordering == Some(Ordering::Greater),
+        "$gte" => ordering.is_some_and(|ordering| ordering != Ordering::Less),
+        "$in" => value.as_array()
+            .is_some_and(|values| values.iter().any(|value| compare_values(field, value) == Some(Ordering::Equal))),
+        _ => false
+    }
+}
+
+fn is_match(document: &Value, filter: &Value) -> bool {
+    let filter = match filter.as_object() {
+        Some(filter) => filter,
+        None => return true
+    };
+    return filter.iter().all(|(key, condition)| {
+        if key == "$or" {
+            return condition.as_array().is_some_and(|filters| filters.iter().any(|filter| is_match(document, filter)))
+        }
+        let field = &document[key.as_str()];
+        return match condition.as_object() {
+            Some(operators) if operators.keys().all(|operator| operator.starts_with('$')) && json_to_timestamp(condition).is_none() => {
+                operators.iter().all(|(operator, value)| is_operator_match(field, operator, value))
+            },
+            _ => compare_values(field, condition) == Some(Ordering::Equal)
+        }
+    });
+}
+
+impl MockMongo {
+    fn handle(&self, mut stream: TcpStream) {
+        loop {
+            let mut header = [0; 16];
+            if stream.read_exact(&mut header).is_err() {
+                return
+            }
+            let mut header_bytes = &header[..];
+            let length = read_i32(&mut header_bytes).unwrap_or_default();
+            let request_id = read_i32(&mut header_bytes).unwrap_or_default();
+            let _response_to = read_i32(&mut header_bytes);
+            let op_code = read_i32(&mut header_bytes).unwrap_or_default();
+            let mut body = vec![0; (length as usize).saturating_sub(header.len())];
+            if stream.read_exact(&mut body).is_err() {
+                return
+            }
+            
+            let reply = match op_code {
+                code if code == OP_QUERY => self.query(&body),
+                code if code == OP_GET_MORE => self.get_more(&body),
+                _ => None
+            };
+            let (cursor_id, documents) = match reply {
+                Some(reply) => reply,
+                None => return
+            };
+            if stream.write_all(&get_reply(request_id, cursor_id, &documents)).is_err() {
+                return
+            }
+        }
+    }
+    
+    fn query(&self, body: &[u8]) -> Option<(i64, Vec<Document>)> {
+        let mut bytes = body;
+        let _flags = read_i32(&mut bytes)?;
+        let namespace = read_cstring(&mut bytes)?;
+        let skip = read_i32(&mut bytes)?;
+        let number_to_return = read_i32(&mut bytes)?;
+        let query = decode_document(&mut bytes).ok()?;
+        let projection = if bytes.is_empty() { None } else { decode_document(&mut bytes).ok() };
+        
+        if namespace.ends_with(".$cmd") {
+            return Some((0, vec![self.command(&namespace, query)]))
+        }
+        let documents = self.find(&get_table(&namespace), query, skip as usize, projection);
+        return Some(self.open_cursor(documents, number_to_return))
+    }
+    
+    fn get_more(&self, body: &[u8]) -> Option<(i64, Vec<Document>)> {
+        let mut bytes = body;
+        let _zero = read_i32(&mut bytes)?;
+        let _namespace = read_cstring(&mut bytes)?;
+        let _number_to_return = read_i32(&mut bytes)?;
+        let cursor_id = read_i64(&mut bytes)?;
+        
+        let mut cursors = self.cursors.lock().expect("Mock cursors poisoned");
+        let (batch_size, mut documents) = match cursors.remove(&cursor_id) {
+            Some(cursor) => cursor,
+            None => return Some((0, Vec::new()))
+        };
+        let batch: Vec<Document> = documents.drain(..batch_size.min(documents.len())).collect();
+        if documents.is_empty() {
+            return Some((0, batch))
+        }
+        cursors.insert(cursor_id, (batch_size, documents));
+        return Some((cursor_id, batch))
+    }
+    
+    /// Returns the first batch, the rest is kept for `OP_GET_MORE` when the
+    /// batch is limited.
+    fn open_cursor(&self, mut documents: VecDeque<Document>, number_to_return: i32) -> (i64, Vec<Document>) {
+        let batch_size = number_to_return.unsigned_abs() as usize;
+        if batch_size == 0 || batch_size >= documents.len() || number_to_return < 0 {
+            documents.truncate(if batch_size == 0 { documents.len() } else { batch_size });
+            return (0, documents.into_iter().collect())
+        }
+        
+        let batch = documents.drain(..batch_size).collect();
+        let mut next_cursor_id = self.next_cursor_id.lock().expect("Mock cursor ids poisoned");
+        let cursor_id = *next_cursor_id;
+        *next_cursor_id += 1;
+        self.cursors.lock().expect("Mock cursors poisoned").insert(cursor_id, (batch_size, documents));
+        return (cursor_id, batch)
+    }
+    
+    fn find(&self, table: &str, query: Document, skip: usize, projection: Option<Document>) -> VecDeque<Document> {
+        let query = document_to_json(query);
+        let (filter, order) = if query.get("$query").is_some() {
+            (query["$query"].clone(), query["$orderby"].clone())
+        } else {
+            (query, Value::Null)
+        };
+        
+        let mut documents: Vec<Value> = self.tables.lock().expect("Mock tables poisoned")
+            .get(table)
+            .map(|documents| documents.iter().filter(|document| is_match(document, &filter)).cloned().collect())
+            .unwrap_or_default();
+        if let Some((field, direction)) = order.as_object().and_then(|order| order.iter().next()) {
+            sort_documents(&mut documents, field, direction.as_i64() == Some(-1));
+        }
+        
+        let fields: Option<Vec<String>> = projection.map(|projection| projection.iter()
+            .filter(|(_, include)| !matches!(**include, Bson::I32(0) | Bson::I64(0) | Bson::Boolean(false)))
+            .map(|(field, _)| field.clone())
+            .collect());
+        return documents.into_iter()
+            .skip(skip)
+            .map(|document| match fields {
+                Some(ref fields) => pluck(&document, fields),
+                None => document
+            })
+            .map(json_to_document)
+            .collect()
+    }
+    
+    fn command(&self, namespace: &str, command: Document) -> Document {
+        let command = match command.get("$query") {
+            Some(Bson::Document(query)) => query.clone(),
+            _ => command
+        };
+        let name = command.keys().next().cloned().unwrap_or_default();
+        let database = namespace.split('.').next().unwrap_or_default();
+        let table = command.get_str(&name).unwrap_or_default().to_string();
+        
+        return match name.as_str() {
+            "isMaster" | "ismaster" => doc! {
+                "ismaster": true,
+                "maxBsonObjectSize": 16777216,
+                "maxMessageSizeBytes": 48000000,
+                "maxWriteBatchSize": 1000,
+                "minWireVersion": 0i64,
+                "maxWireVersion": 5i64,
+                "ok": 1.0
+            },
+            "buildinfo" | "buildInfo" => doc! {
+                "version": SERVER_VERSION,
+                "versionArray": [3, 4, 0, 0],
+                "ok": 1.0
+            },
+            "ping" | "compact" => doc! { "ok": 1.0 },
+            "saslStart" => {
+                let payload = match command.get("payload") {
+                    Some(Bson::Binary(_, payload)) => String::from_utf8_lossy(payload).into_owned(),
+                    _ => String::new()
+                };
+                let client_nonce = payload.rsplit(CLIENT_NONCE_KEY).next().unwrap_or_default();
+                let server_first = format!("r={}mock,s=c2FsdA==,i=1", client_nonce);
+                doc! {
+                    "conversationId": 1,
+                    "done": false,
+                    "payload": Bson::Binary(BinarySubtype::Generic, server_first.into_bytes()),
+                    "ok": 1.0
+                }
+            },
+            // Without a server signature the driver skips its verification
+            "saslContinue" => doc! { "conversationId": 1, "done": true, "ok": 1.0 },
+            "insert" => {
+                let documents: Vec<Value> = command.get_array("documents")
+                    .map(|documents| documents.iter().cloned().map(Value::from).collect())
+                    .unwrap_or_default();
+                let count = documents.len() as i32;
+                self.tables.lock().expect("Mock tables poisoned")
+                    .entry(table)
+                    .or_default()
+                    .extend(documents);
+                doc! { "n": count, "ok": 1.0 }
+            },
+            "update" => {
+                let mut count = 0;
+                let mut tables = self.tables.lock().expect("Mock tables poisoned");
+                let documents = tables.entry(table).or_default();
+                for update in command.get_array("updates").map(Vec::as_slice).unwrap_or_default() {
+                    let update = Value::from(update.clone());
+                    for document in documents.iter_mut().filter(|document| is_match(document, &update["q"])) {
+                        for (key, value) in update["u"]["$set"].as_object().into_iter().flatten() {
+                            document[key.as_str()] = value.clone();
+                        }
+                        count += 1;
+                    }
+                }
+                doc! { "n": count, "nModified": count, "ok": 1.0 }
+            },
+            "delete" => {
+                let mut tables = self.tables.lock().expect("Mock tables poisoned");
+                let documents = tables.entry(table).or_default();
+                let count_before = documents.len();
+                for delete in command.get_array("deletes").map(Vec::as_slice).unwrap_or_default() {
+                    let delete = Value::from(delete.clone());
+                    documents.retain(|document| !is_match(document, &delete["q"]));
+                }
+                doc! { "n": (count_before - documents.len()) as i32, "ok": 1.0 }
+            },
+            // Only the `$match`, `$group` on one field and `$limit` stages of `distinct`
+            "aggregate" => {
+                let tables = self.tables.lock().expect("Mock tables poisoned");
+                let mut documents: Vec<Value> = tables.get(&table).cloned().unwrap_or_default();
+                let mut values = Vec::new();
+                let mut limit = usize::MAX;
+                for stage in command.get_array("pipeline").map(Vec::as_slice).unwrap_or_default() {
+                    let stage = Value::from(stage.clone());
+                    if stage.get("$match").is_some() {
+                        documents.retain(|document| is_match(document, &stage["$match"]));
+                    } else if let Some(key) = stage["$group"]["_id"].as_str().and_then(|key| key.strip_prefix('$')) {
+                        values = distinct_values(&documents, key);
+                    } else if let Some(stage_limit) = stage["$limit"].as_u64() {
+                        limit = stage_limit as usize;
+                    } else {
+                        return doc! { "ok": 0.0, "errmsg": "unsupported pipeline stage", "code": 40324 }
+                    }
+                }
+                let batch: Vec<Bson> = values.into_iter()
+                    .take(limit)
+                    .map(|value| Bson::Document(doc! { "_id": Bson::from(value) }))
+                    .collect();
+                doc! {
+                    "cursor": {
+                        "id": 0i64,
+                        "ns": format!("{}.{}", database, table),
+                        "firstBatch": batch
+                    },
+                    "ok": 1.0
+                }
+            },
+            "createIndexes" => {
+                let mut indexes = self.indexes.lock().expect("Mock indexes poisoned");
+                let table_indexes = indexes.entry(table).or_default();
+                for index in command.get_array("indexes").map(Vec::as_slice).unwrap_or_default() {
+                    if let Bson::Document(index) = index {
+                        let keys: Vec<String> = index.get_document("key").map(|keys| keys.keys().cloned().collect()).unwrap_or_default();
+                        for key in keys {
+                            if !table_indexes.contains(&key) {
+                                table_indexes.push(key);
+                            }
+                        }
+                    }
+                }
+                doc! { "ok": 1.0 }
+            },
+            "listIndexes" => {
+                let indexes = self.indexes.lock().expect("Mock indexes poisoned");
+                let batch: Vec<Bson> = Some(String::from("_id")).iter()
+                    .chain(indexes.get(&table).into_iter().flatten())
+                    .map(|key| {
+                        let mut keys = Document::new();
+                        keys.insert(key.as_str(), 1);
+                        Bson::Document(doc! { "key": keys, "name": format!("{}_1", key) })
+                    })
+                    .collect();
+                doc! {
+                    "cursor": {
+                        "id": 0i64,
+                        "ns": format!("{}.{}", database, table),
+                        "firstBatch": batch
+                    },
+                    "ok": 1.0
+                }
+            },
+            _ => doc! {
+                "ok": 0.0,
+                "errmsg": format!("no such command: '{}'", name),
+                "code": 59
+            }
+        }
+    }
+}
+
+/// An `OP_REPLY` to the request `request_id`.
+fn get_reply(request_id: i32, cursor_id: i64, documents: &[Document]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(&0i32.to_le_bytes());
+    body.extend(&cursor_id.to_le_bytes());
+    body.extend(&0i32.to_le_bytes());
+    body.extend(&(documents.len() as i32).to_le_bytes());
+    for document in documents {
+        encode_document(&mut body, document).expect("Failed to encode mock reply");
+    }
+    
+    let mut reply = Vec::new();
+    reply.extend(&(body.len() as i32 + 16).to_le_bytes());
+    reply.extend(&request_id.to_le_bytes());
+    reply.extend(&request_id.to_le_bytes());
+    reply.extend(&OP_REPLY.to_le_bytes());
+    reply.extend(body);
+    return reply
+}
@@ -0,0 +1,453 @@
+use super::*;
+use super::stand_in::{compare_values, distinct_values, new_tables, pluck, serve, sort_documents, MockTables};
+use std::cmp::Ordering;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+
+static QUERY_START: i64 = 1;
+static SUCCESS_ATOM: i64 = 1;
+static SUCCESS_SEQUENCE: i64 = 2;
+static RUNTIME_ERROR: i64 = 18;
+static ERROR_QUERY_LOGIC: i64 = 3000000;
+static SERVER_VERSION: &str = "2.3.0";
+/// Database of the tables, the default of the driver
+static DATABASE: &str = "test";
+static CLIENT_NONCE_KEY: &str = ",r=";
+
+static TT_MAKE_ARRAY: i64 = 2;
+static TT_VAR: i64 = 10;
+static TT_DB: i64 = 14;
+static TT_TABLE: i64 = 15;
+static TT_EQ: i64 = 17;
+static TT_NE: i64 = 18;
+static TT_LT: i64 = 19;
+static TT_LE: i64 = 20;
+static TT_GT: i64 = 21;
+static TT_GE: i64 = 22;
+static TT_GET_FIELD: i64 = 31;
+static TT_PLUCK: i64 = 33;
+static TT_FILTER: i64 = 39;
+static TT_ORDER_BY: i64 = 41;
+static TT_DISTINCT: i64 = 42;
+static TT_UPDATE: i64 = 53;
+static TT_DELETE: i64 = 54;
+static TT_INSERT: i64 = 56;
+static TT_OR: i64 = 66;
+static TT_AND: i64 = 67;
+static TT_FUNC: i64 = 69;
+static TT_SKIP: i64 = 70;
+static TT_LIMIT: i64 = 71;
+static TT_ASC: i64 = 73;
+static TT_DESC: i64 = 74;
+static TT_INDEX_CREATE: i64 = 75;
+static TT_INDEX_LIST: i64 = 77;
+static TT_CONTAINS: i64 = 93;
+static TT_INDEX_WAIT: i64 = 140;
+
+/// Mock of a RethinkDB server: the handshake of the driver, whose server
+/// signature isn't checked when it is left out, and an interpreter of the ReQL
+/// terms that the facade builds. Like the server it only knows the tables it was
+/// started with. Change feeds aren't supported.
+struct MockRethink {
+    tables: MockTables,
+    indexes: Mutex<HashMap<String, Vec<String>>>,
+    next_id: Mutex<u64>
+}
+
+/// Starts a mock server with empty `tables` and returns its port.
+pub fn start(tables: &[&str]) -> u16 {
+    let mock_tables = new_tables();
+    mock_tables.lock().expect("Mock tables poisoned")
+        .extend(tables.iter().map(|table| (table.to_string(), Vec::new())));
+    let mock = Arc::new(MockRethink {
+        tables: mock_tables,
+        indexes: Mutex::new(HashMap::new()),
+        next_id: Mutex::new(1)
+    });
+    return serve(move |stream| mock.handle(stream))
+}
+
+/// Value of a term: a table and the documents selected from it can be written to.
+enum Datum {
+    Value(Value),
+    Table(String),
+    Selection(String, Vec<Value>),
+    Order(String, bool)
+}
+
+impl Datum {
+    fn into_value(self, mock: &MockRethink) -> Value {
+        return match self {
+            Datum::Value(value) => value,
+            Datum::Table(table) => Value::Array(mock.get_documents(&table)),
+            Datum::Selection(_, documents) => Value::Array(documents),
+            Datum::Order(field, _) => Value::from(field)
+        }
+    }
+}
+
+fn read_message(reader: &mut BufReader<TcpStream>) -> Option<Value> {
+    let mut message = Vec::new();
+    reader.read_until(0, &mut message).ok()?;
+    if message.pop() != Some(0) {
+        return None
+    }
+    return serde_json::from_slice(&message).ok()
+}
+
+fn write_message(stream: &mut TcpStream, message: Value) -> Option<()> {
+    let mut bytes = message.to_string().into_bytes();
+    bytes.push(0);
+    return stream.write_all(&bytes).ok()
+}
+
+impl MockRethink {
+    fn handle(&self, stream: TcpStream) {
+        let mut writer = stream.try_clone().expect("Failed to clone mock stream");
+        let mut reader = BufReader::new(stream);
+        if self.handshake(&mut reader, &mut writer).is_none() {
+            return
+        }
+        
+        loop {
+            let mut header = [0; 12];
+            if reader.read_exact(&mut header).is_err() {
+                return
+            }
+            let mut length = [0; 4];
+            length.copy_from_slice(&header[8..]);
+            let mut query = vec![0; u32::from_le_bytes(length) as usize];
+            if reader.read_exact(&mut query).is_err() {
+                return
+            }
+            
+            let response = match serde_json::from_slice::<Value>(&query) {
+                Ok(ref query) if query[0].as_i64() == Some(QUERY_START) => self.run(&query[1]),
+                _ => get_error(String::from("Only started queries are supported by the mock"))
+            };
+            let response = response.to_string().into_bytes();
+            let mut message = header[..8].to_vec();
+            message.extend(&(response.len() as u32).to_le_bytes());
+            message.extend(response);
+            if writer.write_all(&message).is_err() {
+                return
+            }
+        }
+    }
+    
+    fn handshake(&self, reader: &mut BufReader<TcpStream>, writer: &mut TcpStream) -> Option<()> {
+        let mut version = [0; 4];
+        reader.read_exact(&mut version).ok()?;
+        write_message(writer, json!({
+            "success": true,
+            "min_protocol_version": 0,
+            "max_protocol_version": 0,
+            "server_version": SERVER_VERSION
+        }))?;
+        
+        let client_first = read_message(reader)?;
+        let client_nonce = client_first["authentication"].as_str()?.rsplit(CLIENT_NONCE_KEY).next()?.to_string();
+        write_message(writer, json!({
+            "success": true,
+            "authentication": format!("r={}mock,s=c2FsdA==,i=1", client_nonce)
+        }))?;
+        
+        read_message(reader)?;
+        return write_message(writer, json!({ "success": true }))
+    }
+    
+    fn run(&self, term: &Value) -> Value {
+        return match self.eval(term, &HashMap::new()) {
+            Ok(Datum::Value(value)) => json!({ "t": SUCCESS_ATOM, "r": [value] }),
+            Ok(datum) => json!({ "t": SUCCESS_SEQUENCE, "r": datum.into_value(self) }),
+            Err(message) => get_error(message)
+        }
+    }
+    
+    fn get_documents(&self, table: &str) -> Vec<Value> {
+        return self.tables.lock().expect("Mock tables poisoned")
+            .get(table)
+            .cloned()
+            .unwrap_or_default()
+    }
+    
+    fn eval_value(&self, term: &Value, variables: &HashMap<i64, Value>) -> Result<Value, String> {
+        return self.eval(term, variables).map(|datum| datum.into_value(self))
+    }
+    
+    /// Evaluates a term, `variables` are the arguments of the enclosing functions.
+    fn eval(&self, term: &Value, variables: &HashMap<i64, Value>) -> Result<Datum, String> {
+        let (term_type, args, options) = match *term {
+            Value::Array(ref term) => (
+                term.first().and_then(Value::as_i64).ok_or("Invalid term")?,
+                term.get(1).and_then(Value::as_array).cloned().unwrap_or_default(),
+                term.get(2).cloned().unwrap_or(Value::Null)
+            ),
+            Value::Object(ref object) => {
+                let mut evaluated = serde_json::Map::new();
+                for (key, value) in object {
+                    evaluated.insert(key.clone(), self.eval_value(value, variables)?);
+                }
+                return Ok(Datum::Value(Value::Object(evaluated)))
+            },
+            ref value => return Ok(Datum::Value(value.clone()))
+        };
+        let arg = |index: usize| args.get(index).ok_or_else(|| format!("Missing argument of term {}", term_type));
+        let value = |index: usize| self.eval_value(arg(index)?, variables);
+        
+        if term_type == TT_MAKE_ARRAY {
+            return args.iter()
+                .map(|arg| self.eval_value(arg, variables))
+                .collect::<Result<Vec<Value>, String>>()
+                .map(|values| Datum::Value(Value::Array(values)))
+        }
+        if term_type == TT_VAR {
+            let variable = value(0)?.as_i64().ok_or("Invalid variable")?;
+            return variables.get(&variable).cloned().map(Datum::Value).ok_or_else(|| format!("Unknown variable {}", variable))
+        }
+        if term_type == TT_DB {
+            return value(0).map(Datum::Value)
+        }
+        if term_type == TT_TABLE {
+            let name = value(args.len().saturating_sub(1))?;
+            let name = name.as_str().ok_or("Invalid table name")?;
+            if !self.tables.lock().expect("Mock tables poisoned").contains_key(name) {
+                return Err(format!("Table `{}.{}` does not exist.", DATABASE, name))
+            }
+            return Ok(Datum::Table(name.to_string()))
+        }
+        if term_type == TT_GET_FIELD {
+            let field = value(1)?;
+            return Ok(Datum::Value(value(0)?[field.as_str().ok_or("Invalid field name")?].clone()))
+        }
+        if [TT_EQ, TT_NE, TT_LT, TT_LE, TT_GT, TT_GE].contains(&term_type) {
+            let ordering = compare_values(&value(0)?, &value(1)?);
+            let is_true = match term_type {
+                t if t == TT_EQ => ordering == Some(Ordering::Equal),
+                t if t == TT_NE => ordering != Some(Ordering::Equal),
+                t if t == TT_LT => ordering == Some(Ordering::Less),
+                t if t == TT_LE => ordering.is_some_and(|ordering| ordering != Ordering::Greater),
+                t if t == TT_GT => ordering == Some(Ordering::Greater),
+                _ => ordering.is_some_and(|ordering| ordering != Ordering::Less)
+            };
+            return Ok(Datum::Value(Value::from(is_true)))
+        }
+        if term_type == TT_AND || term_type == TT_OR {
+            let is_and = term_type == TT_AND;
+            for arg in args.iter() {
+                if self.eval_value(arg, variables)?.as_bool() != Some(is_and) {
+                    return Ok(Datum::Value(Value::from(!is_and)))
+                }
+            }
+            return Ok(Datum::Value(Value::from(is_and)))
+        }
+        if term_type == TT_CONTAINS {
+            let values = value(0)?;
+            let item = value(1)?;
+            let is_contained = values.as_array()
+                .is_some_and(|values| values.iter().any(|value| compare_values(value, &item) == Some(Ordering::Equal)));
+            return Ok(Datum::Value(Value::from(is_contained)))
+        }
+        if term_type == TT_FUNC {
+            // Functions are only applied by the terms that take them
+            return Ok(Datum::Value(term.clone()))
+        }
+        if term_type == TT_FILTER {
+            let (table, documents) = self.eval_selection(arg(0)?, variables)?;
+            let function = arg(1)?;
+            let mut selected = Vec::new();
+            for document in documents {
+                if self.apply(function, &document, variables)?.as_bool() == Some(true) {
+                    selected.push(document);
+                }
+            }
+            return Ok(Datum::Selection(table, selected))
+        }
+        if term_type == TT_ASC || term_type == TT_DESC {
+            let field = value(0)?;
+            return Ok(Datum::Order(field.as_str().ok_or("Invalid order field")?.to_string(), term_type == TT_DESC))
+        }
+        if term_type == TT_ORDER_BY {
+            let (table, mut documents) = self.eval_selection(arg(0)?, variables)?;
+            let (field, is_descending) = match self.eval(arg(1)?, variables)? {
+                Datum::Order(field, is_descending) => (field, is_descending),
+                Datum::Value(Value::String(field)) => (field, false),
+                _ => return Err(String::from("Invalid order"))
+            };
+            sort_documents(&mut documents, &field, is_descending);
+            return Ok(Datum::Selection(table, documents))
+        }
+        if term_type == TT_SKIP || term_type == TT_LIMIT {
+            let (table, documents) = self.eval_selection(arg(0)?, variables)?;
+            let count = value(1)?.as_u64().ok_or("Invalid count")? as usize;
+            let documents = if term_type == TT_SKIP {
+                documents.into_iter().skip(count).collect()
+            } else {
+                documents.into_iter().take(count).collect()
+            };
+            return Ok(Datum::Selection(table, documents))
+        }
+        if term_type == TT_PLUCK {
+            let documents = self.eval_value(arg(0)?, variables)?;
+            let fields = args[1..].iter()
+                .map(|arg| self.eval_value(arg, variables).map(|field| field.as_str().unwrap_or_default().to_string()))
+                .collect::<Result<Vec<String>, String>>()?;
+            return Ok(Datum::Value(match documents {
+                Value::Array(documents) => Value::Array(documents.iter().map(|document| pluck(document, &fields)).collect()),
+                document => pluck(&document, &fields)
+            }))
+        }
+        if term_type == TT_DISTINCT {
+            let (_, documents) = self.eval_selection(arg(0)?, variables)?;
+            let index = self.eval_value(&options["index"], variables)?;
+            let mut values = distinct_values(&documents, index.as_str().ok_or("Distinct needs an index")?);
+            values.sort_by(|a, b| compare_values(a, b).unwrap_or(Ordering::Equal));
+            return Ok(Datum::Selection(String::new(), values))
+        }
+        if term_type == TT_INSERT {
+            return self.insert(&self.eval_table(arg(0)?, variables)?, value(1)?)
+        }
+        if term_type == TT_UPDATE {
+            let (table, documents) = self.eval_selection(arg(0)?, variables)?;
+            let data = value(1)?;
+            return Ok(self.write(&table, &documents, |document| {
+                for (key, value) in data.as_object().into_iter().flatten() {
+                    document[key.as_str()] = value.clone();
+                }
+                return true
+            }, "replaced"))
+        }
+        if term_type == TT_DELETE {
+            let (table, documents) = self.eval_selection(arg(0)?, variables)?;
+            return Ok(self.write(&table, &documents, |_| false, "deleted"))
+        }
+        if term_type == TT_INDEX_LIST {
+            let table = self.eval_table(arg(0)?, variables)?;
+            let indexes = self.indexes.lock().expect("Mock indexes poisoned");
+            return Ok(Datum::Value(json!(indexes.get(&table).cloned().unwrap_or_default())))
+        }
+        if term_type == TT_INDEX_CREATE {
+            let table = self.eval_table(arg(0)?, variables)?;
+            let index = value(1)?.as_str().ok_or("Invalid index name")?.to_string();
+            let mut indexes = self.indexes.lock().expect("Mock indexes poisoned");
+            let table_indexes = indexes.entry(table.clone()).or_default();
+            if table_indexes.contains(&index) {
+                return Err(format!("Index `{}` already exists on table `{}`.", index, table))
+            }
+            table_indexes.push(index);
+            return Ok(Datum::Value(json!({ "created": 1 })))
+        }
+        if term_type == TT_INDEX_WAIT {
+            let table = self.eval_table(arg(0)?, variables)?;
+            let indexes = self.indexes.lock().expect("Mock indexes poisoned");
+            let statuses = indexes.get(&table).into_iter().flatten()
+                .map(|index| json!({ "index": index, "ready": true }))
+                .collect();
+            return Ok(Datum::Value(Value::Array(statuses)))
+        }
+        return Err(format!("Term {} is not supported by the mock", term_type))
+    }
+    
+    fn eval_table(&self, term: &Value, variables: &HashMap<i64, Value>) -> Result<String, String> {
+        return match self.eval(term, variables)? {
+            Datum::Table(table) => Ok(table),
+            _ => Err(String::from("Expected a table"))
+        }
+    }
+    
+    /// The documents of a table or a selection from it.
+    fn eval_selection(&self, term: &Value, variables: &HashMap<i64, Value>) -> Result<(String, Vec<Value>), String> {
+        return match self.eval(term, variables)? {
+            Datum::Table(table) => {
+                let documents = self.get_documents(&table);
+                Ok((table, documents))
+            },
+            Datum::Selection(table, documents) => Ok((table, documents)),
+            Datum::Value(Value::Array(documents)) => Ok((String::new(), documents)),
+            _ => Err(String::from("Expected a sequence"))
+        }
+    }
+    
+    /// Applies a `FUNC` term of one argument.
+    fn apply(&self, function: &Value, argument: &Value, variables: &HashMap<i64, Value>) -> Result<Value, String> {
+        if function[0].as_i64() != Some(TT_FUNC) {
+            return self.eval_value(function, variables)
+        }
+        let parameters = self.eval_value(&function[1][0], variables)?;
+        let parameter = parameters[0].as_i64().ok_or("Invalid function parameter")?;
+        let mut variables = variables.clone();
+        variables.insert(parameter, argument.clone());
+        return self.eval_value(&function[1][1], &variables)
+    }
+    
+    /// Inserts a document or an array of them, documents without an id get one.
+    /// Like the server, a document whose id is taken is counted in the `errors`
+    /// of the result instead of failing the query.
+    fn insert(&self, table: &str, documents: Value) -> Result<Datum, String> {
+        let documents = match documents {
+            Value::Array(documents) => documents,
+            document => vec![document]
+        };
+        let mut inserted = 0;
+        let mut errors = 0;
+        let mut first_error = None;
+        let mut next_id = self.next_id.lock().expect("Mock ids poisoned");
+        let mut generated_keys = Vec::new();
+        let mut tables = self.tables.lock().expect("Mock tables poisoned");
+        let table_documents = tables.entry(table.to_string()).or_default();
+        for mut document in documents {
+            if !document.is_object() {
+                return Err(String::from("Expected type OBJECT"))
+            }
+            if document["id"].is_null() {
+                let id = format!("mock-{}", *next_id);
+                *next_id += 1;
+                document["id"] = Value::from(id.as_str());
+                generated_keys.push(id);
+            } else if let Some(existing) = table_documents.iter().find(|existing| existing["id"] == document["id"]) {
+                errors += 1;
+                first_error.get_or_insert_with(|| format!("Duplicate primary key `id`:\n{}\n{}", existing, document));
+                continue
+            }
+            table_documents.push(document);
+            inserted += 1;
+        }
+        
+        let mut result = json!({
+            "inserted": inserted,
+            "generated_keys": generated_keys,
+            "errors": errors
+        });
+        if let Some(first_error) = first_error {
+            result["first_error"] = Value::from(first_error);
+        }
+        return Ok(Datum::Value(result))
+    }
+    
+    /// Writes the `selected` documents of `table` with `write`, which returns
+    /// whether to keep the document. The count is returned as `count_key`.
+    fn write<F>(&self, table: &str, selected: &[Value], mut write: F, count_key: &str) -> Datum
+        where F: FnMut(&mut Value) -> bool {
+        
+        let mut count = 0;
+        let mut tables = self.tables.lock().expect("Mock tables poisoned");
+        if let Some(documents) = tables.get_mut(table) {
+            documents.retain_mut(|document| {
+                if !selected.iter().any(|selected| selected["id"] == document["id"]) {
+                    return true
+                }
+                count += 1;
+                return write(document)
+            });
+        }
+        let mut result = json!({ "errors": 0 });
+        result[count_key] = Value::from(count);
+        return Datum::Value(result)
+    }
+}
+
+fn get_error(message: String) -> Value {
+    return json!({ "t": RUNTIME_ERROR, "e": ERROR_QUERY_LOGIC, "r": [message], "b": [] })
+}
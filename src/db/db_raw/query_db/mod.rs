@@ -1,45 +1,233 @@
+mod influx_facade;
+pub mod influx_mock;
+mod memory_facade;
+#[cfg(test)]
+mod mock_influx;
+#[cfg(test)]
+mod mock_mongo;
+#[cfg(test)]
+mod mock_rethink;
+mod mongo_facade;
+mod pool;
 mod rethink_facade;
+#[cfg(test)]
+mod stand_in;
+#[cfg(test)]
+mod tests;
 
-use self::rethink_facade::Database;
-use db::reql::errors::Error;
+pub use self::memory_facade::MemoryConfig;
+pub use self::pool::{PoolConfig, ReconnectEvent};
+
+use DatabaseType;
+use db::reql::errors::{DriverError, Error as ReqlError};
 use db::serde_json;
 use db::serde_json::Value;
-use db::reql::Response;
+use influent::client::ClientError;
+use mongodb::Error as MongoError;
 use std::collections::HashMap;
+use std::vec;
 
 
-pub type Args = HashMap<String, String>;
-pub type DBError = Error;
+pub type Args = HashMap<String, DBValue>;
+pub type Conditions = HashMap<String, Condition>;
 pub type Table = String;
+/// The documents don't borrow the database, so a pool can replace a broken
+/// connection while they are read.
+pub type Documents = Box<dyn Iterator<Item = Result<Value, DBError>>>;
 //pub type Response = Value;
 
-pub fn get_database() -> Result<Box<QueryBase>, DBError> {
-    return match Database::new() {
-        Ok(db) => Ok(Box::new(db)),
-        Err(err) => Err(err)
+#[derive(Clone, Debug, PartialEq)]
+pub enum DBValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    /// Milliseconds since the Unix epoch
+    Timestamp(i64),
+    Nested(Args)
+}
+
+impl DBValue {
+    /// Every backend has its own time type, so timestamps are mapped by `timestamp_to_json`.
+    pub fn to_json(&self, timestamp_to_json: fn(i64) -> Value) -> Value {
+        return match *self {
+            DBValue::Int(value) => Value::from(value),
+            DBValue::Float(value) => Value::from(value),
+            DBValue::Bool(value) => Value::from(value),
+            DBValue::Str(ref value) => Value::from(value.as_str()),
+            DBValue::Timestamp(value) => timestamp_to_json(value),
+            DBValue::Nested(ref args) => args_to_json(args, timestamp_to_json)
+        }
+    }
+    
+    /// Reads a field of a result document back, `null` and arrays have no typed value.
+    pub fn from_json(value: &Value) -> Option<DBValue> {
+        return match *value {
+            Value::Bool(value) => Some(DBValue::Bool(value)),
+            Value::Number(ref number) => number.as_i64()
+                .map(DBValue::Int)
+                .or_else(|| number.as_f64().map(DBValue::Float)),
+            Value::String(ref value) => Some(DBValue::Str(value.clone())),
+            Value::Object(ref object) => {
+                if value["$date"].is_object() || value["$reql_type$"] == "TIME" {
+                    return json_to_timestamp(value).map(DBValue::Timestamp)
+                }
+                Some(DBValue::Nested(object.iter()
+                    .filter_map(|(key, value)| DBValue::from_json(value).map(|value| (key.clone(), value)))
+                    .collect()))
+            },
+            _ => None
+        }
+    }
+}
+
+impl From<i64> for DBValue {
+    fn from(value: i64) -> DBValue {
+        return DBValue::Int(value)
+    }
+}
+
+impl From<f64> for DBValue {
+    fn from(value: f64) -> DBValue {
+        return DBValue::Float(value)
+    }
+}
+
+impl From<bool> for DBValue {
+    fn from(value: bool) -> DBValue {
+        return DBValue::Bool(value)
+    }
+}
+
+impl From<String> for DBValue {
+    fn from(value: String) -> DBValue {
+        return DBValue::Str(value)
+    }
+}
+
+impl<'a> From<&'a str> for DBValue {
+    fn from(value: &'a str) -> DBValue {
+        return DBValue::Str(value.to_string())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Eq(DBValue),
+    Ne(DBValue),
+    Lt(DBValue),
+    Gt(DBValue),
+    /// Matches values from the first one inclusive to the second one exclusive
+    Range(DBValue, DBValue),
+    In(Vec<DBValue>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Order {
+    Asc,
+    Desc
+}
+
+#[derive(Debug)]
+pub enum DBError {
+    Rethink(ReqlError),
+    Mongo(Box<MongoError>),
+    Influx(ClientError),
+    Unsupported(String),
+    /// Failure simulated by the benchmark, it acts as a lost connection
+    Injected(String)
+}
+
+impl DBError {
+    /// Whether the connection is lost, the request itself may be fine. Errors the
+    /// server reported are never connection errors, retrying them fails again.
+    pub fn is_connection_error(&self) -> bool {
+        return match *self {
+            DBError::Rethink(ReqlError::Driver(ref err)) => matches!(**err, DriverError::Io(_) | DriverError::R2D2(_)),
+            DBError::Mongo(ref err) => matches!(**err, MongoError::IoError(_)),
+            DBError::Influx(ClientError::Communication(_)) => true,
+            DBError::Injected(_) => true,
+            _ => false
+        }
+    }
+}
+
+impl From<ReqlError> for DBError {
+    fn from(err: ReqlError) -> DBError {
+        return DBError::Rethink(err)
+    }
+}
+
+impl From<MongoError> for DBError {
+    fn from(err: MongoError) -> DBError {
+        return DBError::Mongo(Box::new(err))
+    }
+}
+
+impl From<ClientError> for DBError {
+    fn from(err: ClientError) -> DBError {
+        return DBError::Influx(err)
     }
 }
 
+/// Opens `config.get_size()` connections and reconnects the broken ones.
+pub fn get_pool(database_type: DatabaseType, config: PoolConfig) -> Result<Box<dyn QueryBase>, DBError> {
+    let pool = pool::Pool::new(config, move || get_database(&database_type, config))?;
+    return Ok(Box::new(pool));
+}
+
+/// Opens one connection to the database at `config.get_host()`.
+pub fn get_database(database_type: &DatabaseType, config: PoolConfig) -> Result<Box<dyn QueryBase>, DBError> {
+    let host = config.get_host();
+    return match *database_type {
+        DatabaseType::Memory => Ok(Box::new(memory_facade::Database::new(host, config.get_memory_config()))),
+        DatabaseType::Influxdb => Ok(Box::new(influx_facade::Database::new(host))),
+        DatabaseType::Mongodb => mongo_facade::Database::new(host)
+            .map(|db| Box::new(db) as Box<dyn QueryBase>),
+        DatabaseType::Rethinkdb => rethink_facade::Database::new(host)
+            .map(|db| Box::new(db) as Box<dyn QueryBase>)
+    }
+}
+
+#[derive(Clone)]
 pub struct Request {
     table: Table,
     data: Option<Args>,
-    condition: Option<Args>,
+    condition: Option<Conditions>,
+    fields: Option<Vec<String>>,
+    order: Option<(String, Order)>,
+    limit: Option<usize>,
+    skip: Option<usize>,
+    cursor: Option<DBValue>,
+    batch_size: Option<usize>
 }
 
 impl Request {
-    pub fn new(table: Table, data: Args, condition: Args) -> Request {
+    pub fn new(table: Table, data: Args, condition: Conditions) -> Request {
         return Request {
             table: table,
             data: Some(data),
-            condition: Some(condition)
+            condition: Some(condition),
+            fields: None,
+            order: None,
+            limit: None,
+            skip: None,
+            cursor: None,
+            batch_size: None
         }
     }
     
-    pub fn from_condition(table: Table, condition: Args) -> Request {
+    pub fn from_condition(table: Table, condition: Conditions) -> Request {
         return Request {
             table: table,
             data: None,
-            condition: Some(condition)
+            condition: Some(condition),
+            fields: None,
+            order: None,
+            limit: None,
+            skip: None,
+            cursor: None,
+            batch_size: None
         }
     }
     
@@ -47,32 +235,193 @@ impl Request {
         return Request {
             table: table,
             data: Some(data),
-            condition: None
+            condition: None,
+            fields: None,
+            order: None,
+            limit: None,
+            skip: None,
+            cursor: None,
+            batch_size: None
         }
     }
     
-    pub fn data_to_json(&self) -> Value {
-        return Request::args_to_json(&self.data.clone());
+    /// Selects only `fields` of every document.
+    pub fn with_fields(mut self, fields: Vec<String>) -> Request {
+        self.fields = Some(fields);
+        return self
+    }
+    
+    pub fn with_order(mut self, field: &str, order: Order) -> Request {
+        self.order = Some((field.to_string(), order));
+        return self
+    }
+    
+    pub fn with_limit(mut self, limit: usize) -> Request {
+        self.limit = Some(limit);
+        return self
+    }
+    
+    pub fn with_skip(mut self, skip: usize) -> Request {
+        self.skip = Some(skip);
+        return self
     }
     
-    pub fn condition_to_json(&self) -> Value {
-        return Request::args_to_json(&self.condition.clone());
+    /// Sets how many documents `select` fetches from the server at once, by default
+    /// it is up to the backend. Without a server side batch size the backend pages
+    /// through the documents with skip and limit instead.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Request {
+        self.batch_size = Some(batch_size);
+        return self
     }
     
-    fn args_to_json(args_opt: &Option<Args>) -> Value {
-        let args_clone = args_opt.clone();
-        let args = args_clone.unwrap_or(HashMap::new());
-        return serde_json::to_value(args).unwrap();
+    /// Moves an ordered request to the page after `last_document`, the last document
+    /// of the current page. Unlike `with_skip` the server doesn't scan the previous
+    /// pages, but the order field must be unique to not lose documents.
+    pub fn next_page(mut self, last_document: &Value) -> Request {
+        self.cursor = self.order.as_ref()
+            .and_then(|(field, _)| DBValue::from_json(&last_document[field]));
+        return self
+    }
+    
+    /// Returns the conditions together with the page cursor.
+    pub fn get_conditions(&self) -> Conditions {
+        let mut conditions = self.condition.clone().unwrap_or_default();
+        if let (Some(cursor), Some((field, order))) = (self.cursor.clone(), self.order.clone()) {
+            let condition = match order {
+                Order::Asc => Condition::Gt(cursor),
+                Order::Desc => Condition::Lt(cursor)
+            };
+            conditions.insert(field, condition);
+        }
+        return conditions;
+    }
+    
+    pub fn data_to_json(&self, timestamp_to_json: fn(i64) -> Value) -> Value {
+        return args_to_json(&self.data.clone().unwrap_or_default(), timestamp_to_json);
     }
 }
 
-pub trait QueryBase: Sync {
+fn args_to_json(args: &Args, timestamp_to_json: fn(i64) -> Value) -> Value {
+    return Value::Object(args.iter()
+        .map(|(key, value)| (key.clone(), value.to_json(timestamp_to_json)))
+        .collect());
+}
+
+/// Reads back a timestamp written as `DBValue::Timestamp` by any backend: epoch
+/// milliseconds, a MongoDB `$date` or a ReQL `TIME` document.
+pub fn json_to_timestamp(value: &Value) -> Option<i64> {
+    if let Some(millis) = value["$date"]["$numberLong"].as_i64() {
+        return Some(millis)
+    }
+    if value["$reql_type$"] == "TIME" {
+        return value["epoch_time"].as_f64().map(|seconds| (seconds*1000.0).round() as i64)
+    }
+    return value.as_i64().or_else(|| value.as_f64().map(|millis| millis as i64));
+}
+
+/// Pages through the documents with `fetch_page(skip, limit)`, for the backends
+/// whose clients can't set the size of a server batch.
+pub struct Pages {
+    fetch_page: Box<dyn FnMut(usize, usize) -> Result<Vec<Value>, DBError>>,
+    page_size: usize,
+    skip: usize,
+    remaining: Option<usize>,
+    page: vec::IntoIter<Value>,
+    is_done: bool
+}
+
+impl Pages {
+    pub fn new<F>(request: &Request, page_size: usize, fetch_page: F) -> Pages
+        where F: FnMut(usize, usize) -> Result<Vec<Value>, DBError> + 'static {
+        
+        return Pages {
+            fetch_page: Box::new(fetch_page),
+            page_size: page_size,
+            skip: request.skip.unwrap_or(0),
+            remaining: request.limit,
+            page: Vec::new().into_iter(),
+            is_done: false
+        }
+    }
+}
+
+impl Iterator for Pages {
+    type Item = Result<Value, DBError>;
+    
+    fn next(&mut self) -> Option<Result<Value, DBError>> {
+        loop {
+            if let Some(document) = self.page.next() {
+                return Some(Ok(document))
+            }
+            let limit = self.remaining.map_or(self.page_size, |remaining| remaining.min(self.page_size));
+            if self.is_done || limit == 0 {
+                return None
+            }
+            
+            match (self.fetch_page)(self.skip, limit) {
+                Ok(page) => {
+                    self.is_done = page.len() < limit;
+                    self.skip += page.len();
+                    self.remaining = self.remaining.map(|remaining| remaining - page.len());
+                    self.page = page.into_iter();
+                },
+                Err(err) => {
+                    self.is_done = true;
+                    return Some(Err(err))
+                }
+            }
+        }
+    }
+}
+
+pub trait QueryBase {
     fn get_indexes(&self, table: &str) -> Result<Value, DBError>;
     fn create_index(&self, table: &str, index: &str) -> Option<DBError>;
     fn insert(&self, request: Request) -> Option<DBError>;
+    /// Inserts every document of `data` into `table`, with one request when the
+    /// backend supports it.
+    fn insert_many(&self, table: &str, data: Vec<Args>) -> Option<DBError> {
+        for data in data {
+            if let Some(err) = self.insert(Request::from_data(table.to_string(), data)) {
+                return Some(err)
+            }
+        }
+        return None
+    }
     fn update(&self, request: Request) -> Option<DBError>;
-    fn select(&self, request: Request) -> Result<Value, DBError>;
+    /// Streams the documents matching the request, the next batch is only fetched
+    /// once the previous one has been consumed.
+    fn select(&self, request: Request) -> Result<Documents, DBError>;
     fn delete(&self, request: Request) -> Option<DBError>;
+    /// Compacts the storage of `table`, nothing to do for the backends that only
+    /// compact on their own.
+    fn compact(&self, _table: &str) -> Option<DBError> {
+        return None
+    }
+    fn distinct(&self, table: &str, index: &str, limit: usize) -> Result<Vec<Value>, DBError>;
+    /// Runs the cheapest request of the backend to check the connection.
+    fn ping(&self) -> Option<DBError>;
     
-    fn changes<F>(&self, request: Request, on_each: F) where F: Fn(Result<Response<Value>, Error>);
+    /// Subscribes to the changes matching `request.condition` and calls `on_each`
+    /// for every change document until it returns `false`. Every backend reports
+    /// changes as `{"new_val": ...}` documents and sends `{"state": "ready"}` once
+    /// the subscription is established.
+    fn changes(&self, request: Request, on_each: &mut dyn FnMut(Result<Value, DBError>) -> bool);
+    
+    fn ensure_index(&self, table: &str, index: &str) -> Option<DBError> {
+        let indexes = match self.get_indexes(table) {
+            Ok(indexes) => indexes,
+            Err(err) => return Some(err)
+        };
+        
+        if indexes.as_array().is_some_and(|indexes| indexes.contains(&Value::from(index))) {
+            return None
+        }
+        return self.create_index(table, index);
+    }
+    
+    /// Returns the reconnects since the last call, only a pool reconnects.
+    fn take_reconnect_events(&self) -> Vec<ReconnectEvent> {
+        return Vec::new()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,316 @@
+use super::*;
+use mongodb::{Bson, Client, CommandType, Document, ThreadedClient};
+use mongodb::coll::Collection;
+use mongodb::coll::options::{CursorType, FindOptions};
+use mongodb::cursor::Cursor;
+use mongodb::db::{Database as MongoDatabase, ThreadedDatabase};
+use mongodb::db::options::CreateCollectionOptions;
+
+
+static HOST: &str = "localhost";
+static PORT: u16 = 27017;
+static DATABASE: &str = "bench";
+static USERNAME: &str = "admin";
+static PASSWORD: &str = "password";
+static FIELD_SEED: &str = "seed";
+static CAPPED_COLLECTION_SIZE: i64 = 64*1024*1024;
+
+pub struct Database {
+    database: MongoDatabase
+}
+
+impl Database {
+    /// `host` is either a host name or a host name with a port.
+    pub fn new(host: Option<&'static str>) -> Result<Database, DBError> {
+        let (host, port) = match host.map(|host| host.rsplitn(2, ':').collect::<Vec<&str>>()) {
+            None => (HOST, PORT),
+            Some(ref parts) if parts.len() == 2 => (parts[1], parts[0].parse().expect("Invalid mongodb port")),
+            Some(parts) => (parts[0], PORT)
+        };
+        let client = Client::connect(host, port)?;
+        let database = client.db(DATABASE);
+        database.auth(USERNAME, PASSWORD)?;
+        
+        return Ok(Database {
+            database: database
+        })
+    }
+    
+    fn json_to_document(value: Value) -> Document {
+        return match Bson::from(value) {
+            Bson::Document(document) => document,
+            _ => Document::new()
+        }
+    }
+    
+    fn document_to_json(document: Document) -> Value {
+        return Value::from(Bson::Document(document));
+    }
+    
+    fn timestamp_to_json(millis: i64) -> Value {
+        return json!({ "$date": { "$numberLong": millis } });
+    }
+    
+    fn value_to_bson(value: &DBValue) -> Bson {
+        return Bson::from(value.to_json(Database::timestamp_to_json));
+    }
+    
+    fn data_to_document(request: &Request) -> Document {
+        return Database::json_to_document(request.data_to_json(Database::timestamp_to_json));
+    }
+    
+    fn condition_to_bson(condition: &Condition) -> Bson {
+        let operator = match *condition {
+            Condition::Eq(ref value) => doc! { "$eq": Database::value_to_bson(value) },
+            Condition::Ne(ref value) => doc! { "$ne": Database::value_to_bson(value) },
+            Condition::Lt(ref value) => doc! { "$lt": Database::value_to_bson(value) },
+            Condition::Gt(ref value) => doc! { "$gt": Database::value_to_bson(value) },
+            Condition::Range(ref from, ref to) => doc! {
+                "$gte": Database::value_to_bson(from),
+                "$lt": Database::value_to_bson(to)
+            },
+            Condition::In(ref values) => doc! {
+                "$in": values.iter().map(Database::value_to_bson).collect::<Vec<Bson>>()
+            }
+        };
+        return Bson::Document(operator);
+    }
+    
+    /// Builds the query filter, `prefix` addresses the fields of a nested document.
+    fn condition_to_filter(condition: &Conditions, prefix: &str) -> Document {
+        let mut filter = Document::new();
+        for (key, condition) in condition.iter() {
+            filter.insert(format!("{}{}", prefix, key), Database::condition_to_bson(condition));
+        }
+        return filter;
+    }
+    
+    /// Change streams are available since MongoDB 3.6, older servers fall back to
+    /// a tailable cursor on a capped collection.
+    fn is_change_streams_supported(&self) -> Result<bool, DBError> {
+        let version = self.database.version()?;
+        return Ok(version.major > 3 || (version.major == 3 && version.minor >= 6));
+    }
+    
+    fn open_change_stream(&self, request: &Request) -> Result<Cursor, DBError> {
+        let mut filter = Database::condition_to_filter(&request.get_conditions(), "fullDocument.");
+        filter.insert("operationType", "insert");
+        let pipeline = vec![
+            doc! { "$changeStream": {} },
+            doc! { "$match": filter }
+        ];
+        
+        return self.database.collection(&request.table)
+            .aggregate(pipeline, None)
+            .map_err(DBError::from);
+    }
+    
+    fn open_tailable_cursor(&self, request: &Request) -> Result<Cursor, DBError> {
+        let names = self.database.collection_names(None)?;
+        if !names.contains(&request.table) {
+            let mut options = CreateCollectionOptions::new();
+            options.capped = Some(true);
+            options.size = Some(CAPPED_COLLECTION_SIZE);
+            self.database.create_collection(&request.table, Some(options))?;
+        }
+        
+        // A tailable cursor dies when its query matches nothing, so the seed
+        // document keeps it alive until the first change arrives
+        let collection = self.database.collection(&request.table);
+        let seed = doc! { FIELD_SEED: true };
+        if collection.count(Some(seed.clone()), None)? == 0 {
+            collection.insert_one(seed.clone(), None)?;
+        }
+        
+        let condition = Database::condition_to_filter(&request.get_conditions(), "");
+        let filter = doc! { "$or": [seed, condition] };
+        let mut options = FindOptions::new();
+        options.cursor_type = CursorType::TailableAwait;
+        
+        let mut cursor = collection.find(Some(filter), Some(options))?;
+        // Skip the documents written before the subscription
+        while !cursor.drain_current_batch()?.is_empty() {}
+        return Ok(cursor);
+    }
+    
+    fn request_to_find_options(request: &Request) -> FindOptions {
+        let mut options = FindOptions::new();
+        options.skip = request.skip.map(|skip| skip as i64);
+        options.limit = request.limit.map(|limit| limit as i64);
+        options.batch_size = request.batch_size.map(|batch_size| batch_size as i32);
+        options.sort = request.order.as_ref().map(|(field, order)| {
+            match *order {
+                Order::Asc => doc! { field.as_str(): 1 },
+                Order::Desc => doc! { field.as_str(): -1 }
+            }
+        });
+        options.projection = request.fields.as_ref().map(|fields| {
+            // The other backends don't return the id unless it is asked for
+            let mut projection = doc! { "_id": 0 };
+            for field in fields {
+                projection.insert(field.as_str(), 1);
+            }
+            projection
+        });
+        return options;
+    }
+    
+    fn collection(&self, table: &str) -> Collection {
+        return self.database.collection(table);
+    }
+}
+
+impl QueryBase for Database {
+    fn get_indexes(&self, table: &str) -> Result<Value, DBError> {
+        let mut indexes = Vec::new();
+        for index_res in self.collection(table).list_indexes()? {
+            if let Ok(keys) = index_res?.get_document("key") {
+                indexes.extend(keys.keys().map(|key| Value::from(key.as_str())));
+            }
+        }
+        return Ok(Value::Array(indexes));
+    }
+    
+    fn create_index(&self, table: &str, index: &str) -> Option<DBError> {
+        return self.collection(table)
+            .create_index(doc! { index: 1 }, None)
+            .err()
+            .map(DBError::from);
+    }
+    
+    fn insert(&self, request: Request) -> Option<DBError> {
+        let document = Database::data_to_document(&request);
+        
+        return self.collection(&request.table)
+            .insert_one(document, None)
+            .err()
+            .map(DBError::from);
+    }
+    
+    fn insert_many(&self, table: &str, data: Vec<Args>) -> Option<DBError> {
+        let documents = data.iter()
+            .map(|data| Database::json_to_document(args_to_json(data, Database::timestamp_to_json)))
+            .collect();
+        
+        return self.collection(table)
+            .insert_many(documents, None)
+            .err()
+            .map(DBError::from);
+    }
+    
+    fn update(&self, request: Request) -> Option<DBError> {
+        let filter = Database::condition_to_filter(&request.get_conditions(), "");
+        let data = Database::data_to_document(&request);
+        
+        return self.collection(&request.table)
+            .update_many(filter, doc! { "$set": data }, None)
+            .err()
+            .map(DBError::from);
+    }
+    
+    fn select(&self, request: Request) -> Result<Documents, DBError> {
+        let filter = Database::condition_to_filter(&request.get_conditions(), "");
+        let options = Database::request_to_find_options(&request);
+        let cursor = self.collection(&request.table).find(Some(filter), Some(options))?;
+        
+        return Ok(Box::new(cursor.map(|document_res| {
+            return document_res
+                .map(Database::document_to_json)
+                .map_err(DBError::from);
+        })));
+    }
+    
+    fn delete(&self, request: Request) -> Option<DBError> {
+        let filter = Database::condition_to_filter(&request.get_conditions(), "");
+        
+        return self.collection(&request.table)
+            .delete_many(filter, None)
+            .err()
+            .map(DBError::from);
+    }
+    
+    fn compact(&self, table: &str) -> Option<DBError> {
+        return self.database
+            .command(doc! { "compact": table }, CommandType::Suppressed, None)
+            .err()
+            .map(DBError::from);
+    }
+    
+    /// The values are grouped on the server, so only `limit` of them are sent.
+    /// The reply of the `distinct` command holds every value and can exceed the
+    /// document size limit on large datasets.
+    fn distinct(&self, table: &str, index: &str, limit: usize) -> Result<Vec<Value>, DBError> {
+        let pipeline = vec![
+            doc! { "$match": { index: { "$ne": Bson::Null } } },
+            doc! { "$group": { "_id": format!("${}", index) } },
+            doc! { "$limit": limit as i64 }
+        ];
+        let mut values = Vec::new();
+        for document_res in self.collection(table).aggregate(pipeline, None)? {
+            if let Some(value) = document_res?.remove("_id") {
+                values.push(Value::from(value));
+            }
+        }
+        return Ok(values);
+    }
+    
+    fn ping(&self) -> Option<DBError> {
+        return self.database
+            .command(doc! { "ping": 1 }, CommandType::Suppressed, None)
+            .err()
+            .map(DBError::from);
+    }
+    
+    fn changes(&self, request: Request, on_each: &mut dyn FnMut(Result<Value, DBError>) -> bool) {
+        let is_change_streams = match self.is_change_streams_supported() {
+            Ok(is_change_streams) => is_change_streams,
+            Err(err) => {
+                on_each(Err(err));
+                return
+            }
+        };
+        let cursor_res = if is_change_streams {
+            self.open_change_stream(&request)
+        } else {
+            self.open_tailable_cursor(&request)
+        };
+        let mut cursor = match cursor_res {
+            Ok(cursor) => cursor,
+            Err(err) => {
+                on_each(Err(err));
+                return
+            }
+        };
+        
+        if !on_each(Ok(json!({ "state": "ready" }))) {
+            return
+        }
+        
+        loop {
+            let batch = match cursor.drain_current_batch() {
+                Ok(batch) => batch,
+                Err(err) => {
+                    on_each(Err(DBError::from(err)));
+                    return
+                }
+            };
+            
+            for mut document in batch {
+                let new_val = if is_change_streams {
+                    match document.remove("fullDocument") {
+                        Some(Bson::Document(full_document)) => full_document,
+                        _ => continue
+                    }
+                } else if document.contains_key(FIELD_SEED) {
+                    continue
+                } else {
+                    document
+                };
+                
+                if !on_each(Ok(json!({ "new_val": Database::document_to_json(new_val) }))) {
+                    return
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use super::*;
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use {compute_time_diff_ms, get_current_time};
+
+
+#[derive(Clone, Copy, Debug)]
+pub struct PoolConfig {
+    size: usize,
+    health_check_interval: u64,
+    reconnect_attempts: u32,
+    reconnect_backoff: u64,
+    reconnect_max_backoff: u64,
+    host: Option<&'static str>,
+    memory_config: MemoryConfig
+}
+
+impl PoolConfig {
+    /// Intervals are in milliseconds, `health_check_interval` 0 disables health checks.
+    /// The backoff doubles after every failed attempt up to `reconnect_max_backoff`.
+    pub fn new(size: usize,
+        health_check_interval: u64,
+        reconnect_attempts: u32,
+        reconnect_backoff: u64,
+        reconnect_max_backoff: u64) -> PoolConfig {
+        
+        return PoolConfig {
+            size: cmp::max(size, 1),
+            health_check_interval: health_check_interval,
+            reconnect_attempts: reconnect_attempts,
+            reconnect_backoff: reconnect_backoff,
+            reconnect_max_backoff: reconnect_max_backoff,
+            host: None,
+            memory_config: MemoryConfig::default()
+        }
+    }
+    
+    /// Address of the database, `None` connects to the local default one.
+    pub fn with_host(mut self, host: Option<&'static str>) -> PoolConfig {
+        self.host = host;
+        return self
+    }
+    
+    /// Latency and errors of the in-memory database.
+    pub fn with_memory_config(mut self, memory_config: MemoryConfig) -> PoolConfig {
+        self.memory_config = memory_config;
+        return self
+    }
+    
+    pub fn get_size(&self) -> usize {
+        return self.size
+    }
+    
+    pub fn get_health_check_interval(&self) -> u64 {
+        return self.health_check_interval
+    }
+    
+    pub fn get_reconnect_attempts(&self) -> u32 {
+        return self.reconnect_attempts
+    }
+    
+    pub fn get_reconnect_backoff(&self) -> u64 {
+        return self.reconnect_backoff
+    }
+    
+    pub fn get_reconnect_max_backoff(&self) -> u64 {
+        return self.reconnect_max_backoff
+    }
+    
+    pub fn get_host(&self) -> Option<&'static str> {
+        return self.host
+    }
+    
+    pub fn get_memory_config(&self) -> MemoryConfig {
+        return self.memory_config
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReconnectEvent {
+    time: u128,
+    attempts: u32,
+    downtime_ms: i32,
+    is_reconnected: bool,
+    error: String
+}
+
+impl ReconnectEvent {
+    /// `time` is when the connection was lost, `downtime_ms` how long it took to
+    /// reconnect or to give up.
+    pub fn new(time: u128, attempts: u32, downtime_ms: i32, is_reconnected: bool, error: String) -> ReconnectEvent {
+        return ReconnectEvent {
+            time: time,
+            attempts: attempts,
+            downtime_ms: downtime_ms,
+            is_reconnected: is_reconnected,
+            error: error
+        }
+    }
+    
+    pub fn get_time(&self) -> u128 {
+        return self.time
+    }
+    
+    pub fn get_attempts(&self) -> u32 {
+        return self.attempts
+    }
+    
+    pub fn get_downtime_ms(&self) -> i32 {
+        return self.downtime_ms
+    }
+    
+    pub fn is_reconnected(&self) -> bool {
+        return self.is_reconnected
+    }
+    
+    pub fn get_error(&self) -> &str {
+        return &self.error
+    }
+}
+
+/// Spreads the requests over the connections round-robin. A request that fails
+/// with a connection error reopens the connection. Reads, pings and index or
+/// compaction requests are then retried once, writes are never sent again.
+pub struct Pool {
+    config: PoolConfig,
+    new_connection: Box<dyn Fn() -> Result<Box<dyn QueryBase>, DBError>>,
+    connections: RefCell<Vec<Rc<dyn QueryBase>>>,
+    next_pos: Cell<usize>,
+    last_health_check: Cell<u128>,
+    events: RefCell<Vec<ReconnectEvent>>
+}
+
+impl Pool {
+    pub fn new<F>(config: PoolConfig, new_connection: F) -> Result<Pool, DBError>
+        where F: Fn() -> Result<Box<dyn QueryBase>, DBError> + 'static {
+        
+        let mut connections = Vec::with_capacity(config.get_size());
+        for _ in 0..config.get_size() {
+            connections.push(Rc::from(new_connection()?));
+        }
+        
+        return Ok(Pool {
+            config: config,
+            new_connection: Box::new(new_connection),
+            connections: RefCell::new(connections),
+            next_pos: Cell::new(0),
+            last_health_check: Cell::new(get_current_time()),
+            events: RefCell::new(Vec::new())
+        })
+    }
+    
+    fn get_connection(&self, pos: usize) -> Rc<dyn QueryBase> {
+        return self.connections.borrow()[pos].clone();
+    }
+    
+    fn take_pos(&self) -> usize {
+        let pos = self.next_pos.get();
+        self.next_pos.set((pos+1) % self.config.get_size());
+        return pos;
+    }
+    
+    /// Reopens the connection at `pos` with exponential backoff and records the
+    /// attempt whether it succeeds or not.
+    fn reconnect(&self, pos: usize, err: &DBError) -> Result<(), DBError> {
+        let start_time = get_current_time();
+        let mut backoff = self.config.get_reconnect_backoff();
+        let mut last_err = None;
+        let mut attempts = 0;
+        
+        while attempts < self.config.get_reconnect_attempts() {
+            attempts += 1;
+            thread::sleep(Duration::from_millis(backoff));
+            match (self.new_connection)() {
+                Ok(connection) => {
+                    self.connections.borrow_mut()[pos] = Rc::from(connection);
+                    last_err = None;
+                    break
+                },
+                Err(err) => last_err = Some(err)
+            }
+            backoff = cmp::min(backoff*2, self.config.get_reconnect_max_backoff());
+        }
+        
+        let is_reconnected = attempts > 0 && last_err.is_none();
+        self.events.borrow_mut().push(ReconnectEvent::new(start_time,
+            attempts,
+            compute_time_diff_ms(start_time, get_current_time()),
+            is_reconnected,
+            format!("{:?}", err)));
+        
+        return match last_err {
+            Some(err) => Err(err),
+            None if is_reconnected => Ok(()),
+            None => Err(DBError::Unsupported(String::from("Reconnects are disabled")))
+        }
+    }
+    
+    fn check_health(&self) {
+        let interval = self.config.get_health_check_interval();
+        if interval == 0 || get_current_time()-self.last_health_check.get() < interval as u128 {
+            return
+        }
+        
+        for pos in 0..self.config.get_size() {
+            if let Some(err) = self.get_connection(pos).ping() {
+                if err.is_connection_error() {
+                    // The next request on this connection reconnects again
+                    let _ = self.reconnect(pos, &err);
+                }
+            }
+        }
+        self.last_health_check.set(get_current_time());
+    }
+    
+    fn execute<T, F>(&self, operation: F) -> Result<T, DBError>
+        where F: Fn(&dyn QueryBase) -> Result<T, DBError> {
+        
+        self.check_health();
+        let pos = self.take_pos();
+        return match operation(self.get_connection(pos).as_ref()) {
+            Err(ref err) if err.is_connection_error() => {
+                self.reconnect(pos, err)?;
+                operation(self.get_connection(pos).as_ref())
+            },
+            res => res
+        }
+    }
+    
+    /// Reconnects like `execute`, but doesn't send the insert, update or delete
+    /// again: the server may have applied it, or a part of it, before the
+    /// connection was lost.
+    fn execute_write<F>(&self, operation: F) -> Option<DBError>
+        where F: Fn(&dyn QueryBase) -> Option<DBError> {
+        
+        self.check_health();
+        let pos = self.take_pos();
+        let err = operation(self.get_connection(pos).as_ref())?;
+        if err.is_connection_error() {
+            // The write fails either way, the next request gets the new connection
+            let _ = self.reconnect(pos, &err);
+        }
+        return Some(err);
+    }
+    
+    fn execute_opt<F>(&self, operation: F) -> Option<DBError>
+        where F: Fn(&dyn QueryBase) -> Option<DBError> {
+        
+        return self.execute(|connection| operation(connection).map_or(Ok(()), Err)).err();
+    }
+}
+
+impl QueryBase for Pool {
+    fn get_indexes(&self, table: &str) -> Result<Value, DBError> {
+        return self.execute(|connection| connection.get_indexes(table));
+    }
+    
+    fn create_index(&self, table: &str, index: &str) -> Option<DBError> {
+        return self.execute_opt(|connection| connection.create_index(table, index));
+    }
+    
+    fn insert(&self, request: Request) -> Option<DBError> {
+        return self.execute_write(|connection| connection.insert(request.clone()));
+    }
+    
+    fn insert_many(&self, table: &str, data: Vec<Args>) -> Option<DBError> {
+        return self.execute_write(|connection| connection.insert_many(table, data.clone()));
+    }
+    
+    fn update(&self, request: Request) -> Option<DBError> {
+        return self.execute_write(|connection| connection.update(request.clone()));
+    }
+    
+    fn select(&self, request: Request) -> Result<Documents, DBError> {
+        return self.execute(|connection| connection.select(request.clone()));
+    }
+    
+    fn delete(&self, request: Request) -> Option<DBError> {
+        return self.execute_write(|connection| connection.delete(request.clone()));
+    }
+    
+    fn compact(&self, table: &str) -> Option<DBError> {
+        return self.execute_opt(|connection| connection.compact(table));
+    }
+    
+    fn distinct(&self, table: &str, index: &str, limit: usize) -> Result<Vec<Value>, DBError> {
+        return self.execute(|connection| connection.distinct(table, index, limit));
+    }
+    
+    fn ping(&self) -> Option<DBError> {
+        return self.execute_opt(|connection| connection.ping());
+    }
+    
+    /// Subscribes again after a reconnect, so `on_each` receives one more ready state.
+    fn changes(&self, request: Request, on_each: &mut dyn FnMut(Result<Value, DBError>) -> bool) {
+        let pos = self.take_pos();
+        loop {
+            let mut connection_err = None;
+            self.get_connection(pos).changes(request.clone(), &mut |change_res| {
+                return match change_res {
+                    Err(err) => {
+                        if err.is_connection_error() {
+                            connection_err = Some(err);
+                            return false
+                        }
+                        on_each(Err(err))
+                    },
+                    change_res => on_each(change_res)
+                }
+            });
+            
+            match connection_err {
+                Some(err) => {
+                    if let Err(err) = self.reconnect(pos, &err) {
+                        on_each(Err(err));
+                        return
+                    }
+                },
+                None => return
+            }
+        }
+    }
+    
+    fn take_reconnect_events(&self) -> Vec<ReconnectEvent> {
+        return self.events.borrow_mut().drain(..).collect();
+    }
+}
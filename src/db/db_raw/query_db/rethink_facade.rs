@@ -1,7 +1,8 @@
 use super::*;
 use db::futures::stream::Stream;
-use db::reql::{Config, Connection, Client, Document, IntoArg, Run, Response};
-use db::reql::errors::{Error, DriverError};
+use db::reql::{Arg, Config, Connection, Client, Document, IntoArg, RepeatedField, Run, Response, Term, TT};
+use db::reql::errors::{Error, DriverError, RuntimeError};
+use std::net::ToSocketAddrs;
 use std::sync::Arc;
 
 
@@ -13,18 +14,27 @@ pub struct Database {
 }
 
 impl Database {
-    pub fn new() -> Result<Database, DBError> {
+    /// `host` is a host name with a port.
+    pub fn new(host: Option<&'static str>) -> Result<Database, DBError> {
         let client = Client::new();
+        let mut config = Config::default();
+        if let Some(host) = host {
+            config.servers = host.to_socket_addrs()
+                .expect("Invalid rethinkdb host")
+                .collect();
+        }
         
-        return client.connect(Config::default()).map(|connection| {
-            Database {
-                client: client,
-                connection: connection
-            }
-        })
+        return client.connect(config)
+            .map(|connection| {
+                Database {
+                    client: client,
+                    connection: connection
+                }
+            })
+            .map_err(DBError::from)
     }
     
-    fn map_response_to_json(response: ResponseRaw) -> Result<Value, DBError> {
+    fn map_response_to_json(response: ResponseRaw) -> Result<Value, Error> {
         let err = Error::Driver(Arc::new(DriverError::Other("Empty response".to_string())));
         return response.ok_or(err.clone())
             .and_then(|response_res| response_res)
@@ -37,68 +47,279 @@ impl Database {
                 }
             });
     }
+    
+    /// Write queries report the documents they could not write in their result
+    /// instead of failing, so a result with `errors` is turned into an error.
+    fn map_write_response(response: Result<Response<Value>, Error>) -> Option<DBError> {
+        let result = response.and_then(|response| Database::map_response_to_json(response.wait().next()));
+        let result = match result {
+            Ok(Value::Array(mut results)) if results.len() == 1 => results.remove(0),
+            Ok(result) => result,
+            Err(err) => return Some(DBError::from(err))
+        };
+        let errors = result["errors"].as_u64().unwrap_or_default();
+        if errors == 0 {
+            return None
+        }
+        let message = format!("Failed to write {} documents: {}", errors, result["first_error"].as_str().unwrap_or_default());
+        return Some(DBError::from(Error::Runtime(Arc::new(RuntimeError::QueryLogic(message)))));
+    }
+    
+    /// Every batch of the response arrives as one array document.
+    fn response_to_documents(response: Response<Value>) -> Documents {
+        return Box::new(response.wait().flat_map(|document_res| {
+            return match document_res {
+                Ok(Some(Document::Expected(Value::Array(batch)))) => batch.into_iter().map(Ok).collect(),
+                Ok(Some(Document::Expected(value))) => vec![Ok(value)],
+                Ok(Some(Document::Unexpected(value))) => vec![Ok(value)],
+                Ok(None) => Vec::new(),
+                Err(err) => vec![Err(DBError::from(err))]
+            }
+        }));
+    }
+    
+    fn timestamp_to_json(millis: i64) -> Value {
+        return json!({
+            "$reql_type$": "TIME",
+            "epoch_time": millis as f64/1000.0,
+            "timezone": "+00:00"
+        });
+    }
+    
+    fn make_term(term_type: TT, args: Vec<Term>) -> Term {
+        let mut term = Term::new();
+        term.set_field_type(term_type);
+        term.set_args(RepeatedField::from_vec(args));
+        return term;
+    }
+    
+    /// The driver encodes the value of a term pair as the value itself, so the
+    /// pair is the way to turn any JSON into a term.
+    fn make_value_term(value: Value) -> Result<Term, Error> {
+        return Arg::create_term_pair("value", value).map(|mut pair| pair.take_val());
+    }
+    
+    fn make_db_value_term(value: &DBValue) -> Result<Term, Error> {
+        return Database::make_value_term(value.to_json(Database::timestamp_to_json));
+    }
+    
+    /// Builds the `filter` predicate `function(row) { return row(key) <op> value && ... }`.
+    fn condition_to_filter(condition: &Conditions) -> Result<Term, Error> {
+        let row = Database::make_term(TT::VAR, vec![Database::make_value_term(json!(1))?]);
+        let mut predicates = Vec::new();
+        
+        for (key, condition) in condition.clone() {
+            let field = Database::make_term(TT::GET_FIELD, vec![row.clone(), Database::make_value_term(Value::from(key))?]);
+            let predicate = match condition {
+                Condition::Eq(value) => Database::make_term(TT::EQ, vec![field, Database::make_db_value_term(&value)?]),
+                Condition::Ne(value) => Database::make_term(TT::NE, vec![field, Database::make_db_value_term(&value)?]),
+                Condition::Lt(value) => Database::make_term(TT::LT, vec![field, Database::make_db_value_term(&value)?]),
+                Condition::Gt(value) => Database::make_term(TT::GT, vec![field, Database::make_db_value_term(&value)?]),
+                Condition::Range(from, to) => Database::make_term(TT::AND, vec![
+                    Database::make_term(TT::GE, vec![field.clone(), Database::make_db_value_term(&from)?]),
+                    Database::make_term(TT::LT, vec![field, Database::make_db_value_term(&to)?])
+                ]),
+                Condition::In(values) => {
+                    let values = values.iter()
+                        .map(|value| value.to_json(Database::timestamp_to_json))
+                        .collect();
+                    Database::make_term(TT::CONTAINS, vec![Database::make_value_term(Value::Array(values))?, field])
+                }
+            };
+            predicates.push(predicate);
+        }
+        
+        let params = Database::make_term(TT::MAKE_ARRAY, vec![Database::make_value_term(json!(1))?]);
+        return Ok(Database::make_term(TT::FUNC, vec![params, Database::make_term(TT::AND, predicates)]));
+    }
 }
 
 impl QueryBase for Database {
+    /// The list is an atom, so it arrives wrapped in the array of the response.
     fn get_indexes(&self, table: &str) -> Result<Value, DBError> {
         return self.client
             .table(table)
             .index_list()
             .run::<Value>(self.connection)
-            .map(|request| request.wait().next())
-            .and_then(|response| Database::map_response_to_json(response));
+            .map_err(DBError::from)
+            .and_then(|response| {
+                return Database::response_to_documents(response)
+                    .next()
+                    .unwrap_or_else(|| Ok(Value::Array(Vec::new())))
+            });
     }
     
     fn create_index(&self, table: &str, index: &str) -> Option<DBError> {
-        //r.table(table).indexCreate('postId').run(conn, callback)
-        None
+        return self.client
+            .table(table)
+            .index_create(index)
+            .run::<Value>(self.connection)
+            .map(|request| request.wait().next())
+            .and_then(Database::map_response_to_json)
+            .and_then(|_| self.client
+                .table(table)
+                .index_wait()
+                .run::<Value>(self.connection))
+            .map(|request| request.wait().next())
+            .and_then(Database::map_response_to_json)
+            .err()
+            .map(DBError::from);
     }
     
     fn insert(&self, request: Request) -> Option<DBError> {
-        return self.client
+        let response = self.client
             .table(&request.table)
-            .insert(request.data_to_json())
-            .run::<Value>(self.connection)
-            .map(|request| request.wait().next())
-            .err();
+            .insert(request.data_to_json(Database::timestamp_to_json))
+            .run::<Value>(self.connection);
+        
+        return Database::map_write_response(response);
+    }
+    
+    fn insert_many(&self, table: &str, data: Vec<Args>) -> Option<DBError> {
+        let documents: Vec<Value> = data.iter()
+            .map(|data| args_to_json(data, Database::timestamp_to_json))
+            .collect();
+        
+        let response = self.client
+            .table(table)
+            .insert(Value::Array(documents))
+            .run::<Value>(self.connection);
+        
+        return Database::map_write_response(response);
     }
     
     fn update(&self, request: Request) -> Option<DBError> {
-        return self.client
+        let filter = match Database::condition_to_filter(&request.get_conditions()) {
+            Ok(filter) => filter,
+            Err(err) => return Some(DBError::from(err))
+        };
+        
+        let response = self.client
             .table(&request.table)
-            .filter(request.condition_to_json())
-            .update(request.data_to_json())
-            .run::<Value>(self.connection)
-            .map(|request| request.wait().next())
-            .err();
+            .filter(filter)
+            .update(request.data_to_json(Database::timestamp_to_json))
+            .run::<Value>(self.connection);
+        
+        return Database::map_write_response(response);
     }
     
-    fn select(&self, request: Request) -> Result<Value, DBError> {
-        return self.client
+    fn select(&self, request: Request) -> Result<Documents, DBError> {
+        let filter = Database::condition_to_filter(&request.get_conditions())?;
+        let mut query = self.client
             .table(&request.table)
-            .filter(request.condition_to_json())
-            .run::<Value>(self.connection)
-            .map(|request| request.wait().next())
-            .and_then(|response| Database::map_response_to_json(response));
+            .filter(filter);
+        
+        if let Some((ref field, ref order)) = request.order {
+            let order_type = match *order {
+                Order::Asc => TT::ASC,
+                Order::Desc => TT::DESC
+            };
+            let field = Database::make_value_term(Value::from(field.as_str()))?;
+            query = query.order_by(Database::make_term(order_type, vec![field]));
+        }
+        if let Some(ref fields) = request.fields {
+            let mut args = Arg::new();
+            for field in fields {
+                args.add_arg(field.as_str().into_arg());
+            }
+            query = query.pluck(args);
+        }
+        
+        // The driver can't pass `max_batch_rows` to the server
+        if let Some(batch_size) = request.batch_size {
+            let connection = self.connection;
+            return Ok(Box::new(Pages::new(&request, batch_size, move |skip, limit| {
+                return query.skip(skip as u64)
+                    .limit(limit as u64)
+                    .run::<Value>(connection)
+                    .map_err(DBError::from)
+                    .and_then(|response| Database::response_to_documents(response).collect());
+            })));
+        }
+        
+        if let Some(skip) = request.skip {
+            query = query.skip(skip as u64);
+        }
+        if let Some(limit) = request.limit {
+            query = query.limit(limit as u64);
+        }
+        return query.run::<Value>(self.connection)
+            .map(Database::response_to_documents)
+            .map_err(DBError::from);
     }
     
     fn delete(&self, request: Request) -> Option<DBError> {
-        return self.client
+        let filter = match Database::condition_to_filter(&request.get_conditions()) {
+            Ok(filter) => filter,
+            Err(err) => return Some(DBError::from(err))
+        };
+        
+        let response = self.client
             .table(&request.table)
+            .filter(filter)
             .delete()
-            .with_args(request.condition_to_json())
+            .run::<Value>(self.connection);
+        
+        return Database::map_write_response(response);
+    }
+    
+    fn distinct(&self, table: &str, index: &str, limit: usize) -> Result<Vec<Value>, DBError> {
+        let mut args = Arg::new();
+        args.add_opt(Arg::create_term_pair("index", index)?);
+        
+        return self.client
+            .table(table)
+            .distinct()
+            .with_args(args)
+            .limit(limit as u64)
+            .run::<Value>(self.connection)
+            .map_err(DBError::from)
+            .and_then(|response| Database::response_to_documents(response).collect());
+    }
+    
+    fn ping(&self) -> Option<DBError> {
+        return self.client
+            .expr(1)
             .run::<Value>(self.connection)
             .map(|request| request.wait().next())
-            .err();
+            .and_then(Database::map_response_to_json)
+            .err()
+            .map(DBError::from);
     }
     
-    fn changes<F>(&self, request: Request, on_each: F)
-        where F: Fn(Result<Response<Value>, Error>) {
-            
-        self.client
+    fn changes(&self, request: Request, on_each: &mut dyn FnMut(Result<Value, DBError>) -> bool) {
+        let mut args = Arg::new();
+        let filter_res = Arg::create_term_pair("include_states", true)
+            .map(|pair| args.add_opt(pair))
+            .and_then(|_| Database::condition_to_filter(&request.get_conditions()));
+        let filter = match filter_res {
+            Ok(filter) => filter,
+            Err(err) => {
+                on_each(Err(DBError::from(err)));
+                return
+            }
+        };
+        
+        let response = self.client
             .table(&request.table)
-            .filter(request.condition_to_json())
-            .run::<Value>(self.connection)
-            .for_each(on_each);
+            .filter(filter)
+            .changes()
+            .with_args(args)
+            .run::<Value>(self.connection);
+        
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                on_each(Err(DBError::from(err)));
+                return
+            }
+        };
+        
+        for change_res in Database::response_to_documents(response) {
+            let is_err = change_res.is_err();
+            if !on_each(change_res) || is_err {
+                break
+            }
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,258 @@
+use super::*;
+use super::memory_facade::compare_json;
+use db::futures::stream::Stream;
+use db::reql::{Client as ReqlClient, Config as ReqlConfig, Run};
+use mongodb::{Client as MongoClient, ThreadedClient};
+use mongodb::db::ThreadedDatabase;
+use std::cmp::Ordering;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+static LOCALHOST: &str = "127.0.0.1";
+static START_TIMEOUT_MS: u64 = 30000;
+static POLL_INTERVAL_MS: u64 = 100;
+static INFLUX_DATABASE: &str = "bench";
+static MONGO_DATABASE: &str = "bench";
+static MONGO_USERNAME: &str = "admin";
+static MONGO_PASSWORD: &str = "password";
+
+/// Documents of every table of a mock server.
+pub type MockTables = Arc<Mutex<HashMap<String, Vec<Value>>>>;
+
+/// Database of the integration tests: the server process when its binary is on
+/// the `PATH`, otherwise a mock server of its wire protocol. Either one only
+/// lives as long as the stand-in.
+pub struct StandIn {
+    host: &'static str,
+    process: Option<Child>,
+    directory: Option<PathBuf>
+}
+
+impl StandIn {
+    /// `tables` are created up front on RethinkDB, the other backends create
+    /// them on the first write.
+    pub fn start(database_type: &DatabaseType, tables: &[&str]) -> StandIn {
+        return match *database_type {
+            DatabaseType::Influxdb => StandIn::start_influxdb(),
+            DatabaseType::Mongodb => StandIn::start_mongodb(),
+            DatabaseType::Rethinkdb => StandIn::start_rethinkdb(tables),
+            DatabaseType::Memory => panic!("The memory database needs no stand-in")
+        }
+    }
+    
+    pub fn get_host(&self) -> &'static str {
+        return self.host
+    }
+    
+    pub fn get_pool_config(&self) -> PoolConfig {
+        return PoolConfig::new(1, 0, 1, 0, 0).with_host(Some(self.host))
+    }
+    
+    pub fn is_mock(&self) -> bool {
+        return self.process.is_none()
+    }
+    
+    fn from_mock(host: String) -> StandIn {
+        return StandIn {
+            host: Box::leak(host.into_boxed_str()),
+            process: None,
+            directory: None
+        }
+    }
+    
+    /// Starts `command` with its data in a new temporary directory and waits for
+    /// `port` to accept connections.
+    fn from_process(name: &str, mut command: Command, port: u16, host: String) -> StandIn {
+        let directory = env::temp_dir().join(format!("influx_bench_{}_{}", name, port));
+        fs::create_dir_all(&directory).expect("Failed to create data directory");
+        let process = command.current_dir(&directory)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start database");
+        
+        let stand_in = StandIn {
+            host: Box::leak(host.into_boxed_str()),
+            process: Some(process),
+            directory: Some(directory)
+        };
+        wait_for_port(port);
+        return stand_in
+    }
+    
+    fn start_influxdb() -> StandIn {
+        if find_binary("influxd").is_none() {
+            let port = mock_influx::start();
+            return StandIn::from_mock(format!("http://{}:{}", LOCALHOST, port));
+        }
+        
+        let port = get_free_port();
+        let mut command = Command::new("influxd");
+        command.arg("run")
+            .env("INFLUXDB_REPORTING_DISABLED", "true")
+            .env("INFLUXDB_BIND_ADDRESS", format!("{}:{}", LOCALHOST, get_free_port()))
+            .env("INFLUXDB_HTTP_BIND_ADDRESS", format!("{}:{}", LOCALHOST, port))
+            .env("INFLUXDB_META_DIR", "meta")
+            .env("INFLUXDB_DATA_DIR", "data")
+            .env("INFLUXDB_DATA_WAL_DIR", "wal");
+        let stand_in = StandIn::from_process("influxdb", command, port, format!("http://{}:{}", LOCALHOST, port));
+        
+        let query = format!("q=CREATE+DATABASE+{}", INFLUX_DATABASE);
+        let request = format!("POST /query?{} HTTP/1.1\r\nHost: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", query, LOCALHOST);
+        let mut stream = TcpStream::connect((LOCALHOST, port)).expect("Failed to connect to influxdb");
+        stream.write_all(request.as_bytes()).expect("Failed to create influxdb database");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).expect("Failed to create influxdb database");
+        assert!(response.starts_with("HTTP/1.1 200"), "Failed to create influxdb database: {}", response);
+        return stand_in
+    }
+    
+    fn start_mongodb() -> StandIn {
+        if find_binary("mongod").is_none() {
+            let port = mock_mongo::start();
+            return StandIn::from_mock(format!("{}:{}", LOCALHOST, port));
+        }
+        
+        let port = get_free_port();
+        let mut command = Command::new("mongod");
+        command.args(["--bind_ip", LOCALHOST, "--port", &port.to_string(), "--dbpath", "."]);
+        let stand_in = StandIn::from_process("mongodb", command, port, format!("{}:{}", LOCALHOST, port));
+        
+        // The facade always authenticates
+        let client = MongoClient::connect(LOCALHOST, port).expect("Failed to connect to mongodb");
+        client.db(MONGO_DATABASE)
+            .create_user(MONGO_USERNAME, MONGO_PASSWORD, None)
+            .expect("Failed to create mongodb user");
+        return stand_in
+    }
+    
+    fn start_rethinkdb(tables: &[&str]) -> StandIn {
+        if find_binary("rethinkdb").is_none() {
+            let port = mock_rethink::start(tables);
+            return StandIn::from_mock(format!("{}:{}", LOCALHOST, port));
+        }
+        
+        let port = get_free_port();
+        let mut command = Command::new("rethinkdb");
+        command.args(["--directory", "data",
+            "--bind", LOCALHOST,
+            "--driver-port", &port.to_string(),
+            "--cluster-port", &get_free_port().to_string(),
+            "--no-http-admin"]);
+        let stand_in = StandIn::from_process("rethinkdb", command, port, format!("{}:{}", LOCALHOST, port));
+        
+        let client = ReqlClient::new();
+        let mut config = ReqlConfig::default();
+        config.servers = (LOCALHOST, port).to_socket_addrs().expect("Invalid rethinkdb host").collect();
+        let connection = client.connect(config).expect("Failed to connect to rethinkdb");
+        for table in tables {
+            let response = client.table_create(*table)
+                .run::<Value>(connection)
+                .expect("Failed to create rethinkdb table");
+            for document_res in response.wait() {
+                document_res.expect("Failed to create rethinkdb table");
+            }
+        }
+        return stand_in
+    }
+}
+
+impl Drop for StandIn {
+    fn drop(&mut self) {
+        if let Some(ref mut process) = self.process {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+        if let Some(ref directory) = self.directory {
+            let _ = fs::remove_dir_all(directory);
+        }
+    }
+}
+
+fn find_binary(name: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    return env::split_paths(&paths)
+        .map(|path| path.join(name))
+        .find(|path| path.is_file())
+}
+
+fn get_free_port() -> u16 {
+    let listener = TcpListener::bind((LOCALHOST, 0)).expect("No free port");
+    return listener.local_addr().expect("No local address").port()
+}
+
+fn wait_for_port(port: u16) {
+    let start_time = Instant::now();
+    while TcpStream::connect((LOCALHOST, port)).is_err() {
+        if start_time.elapsed() > Duration::from_millis(START_TIMEOUT_MS) {
+            panic!("Database didn't start on port {}", port);
+        }
+        thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+    }
+}
+
+/// Serves every connection to a free local port on its own thread and returns
+/// the port. The server runs until the tests exit.
+pub fn serve<F>(handle: F) -> u16
+    where F: Fn(TcpStream) + Send + Sync + 'static {
+    
+    let listener = TcpListener::bind((LOCALHOST, 0)).expect("Failed to start mock server");
+    let port = listener.local_addr().expect("No local address").port();
+    let handle = Arc::new(handle);
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(Result::ok) {
+            let handle = handle.clone();
+            thread::spawn(move || handle(stream));
+        }
+    });
+    return port
+}
+
+pub fn new_tables() -> MockTables {
+    return Arc::new(Mutex::new(HashMap::new()))
+}
+
+/// Compares values like the memory database, with the times of every backend
+/// compared as milliseconds.
+pub fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    let to_millis = |value: &Value| match *value {
+        Value::Object(_) => json_to_timestamp(value).map(Value::from).unwrap_or_else(|| value.clone()),
+        _ => value.clone()
+    };
+    return compare_json(&to_millis(a), &to_millis(b))
+}
+
+pub fn sort_documents(documents: &mut [Value], field: &str, is_descending: bool) {
+    documents.sort_by(|a, b| {
+        let ordering = compare_values(&a[field], &b[field]).unwrap_or(Ordering::Equal);
+        return if is_descending { ordering.reverse() } else { ordering }
+    });
+}
+
+/// Keeps the `fields` of the document that it has.
+pub fn pluck(document: &Value, fields: &[String]) -> Value {
+    return Value::Object(fields.iter()
+        .filter(|field| !document[field.as_str()].is_null())
+        .map(|field| (field.clone(), document[field.as_str()].clone()))
+        .collect())
+}
+
+/// Distinct values of `field` in the order they were first written.
+pub fn distinct_values(documents: &[Value], field: &str) -> Vec<Value> {
+    let mut values: Vec<Value> = Vec::new();
+    for document in documents {
+        let value = &document[field];
+        if !value.is_null() && !values.contains(value) {
+            values.push(value.clone());
+        }
+    }
+    return values
+}
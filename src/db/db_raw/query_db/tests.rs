@@ -0,0 +1,469 @@
+use super::*;
+use super::stand_in::StandIn;
+use super::influx_mock::{get_series, InfluxMock, InfluxMockCounts};
+use query_executor::QueryExecutor;
+use std::cell::Cell;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use {Executor, Point, Query};
+
+
+static TABLE: &str = "points";
+
+/// Every test gets its own in-memory store, the tests run at once.
+fn get_memory_config(store: &'static str, memory_config: MemoryConfig) -> PoolConfig {
+    return PoolConfig::new(1, 0, 1, 0, 0)
+        .with_host(Some(store))
+        .with_memory_config(memory_config)
+}
+
+fn get_memory_database(store: &'static str) -> Box<dyn QueryBase> {
+    return get_database(&DatabaseType::Memory, get_memory_config(store, MemoryConfig::default()))
+        .expect("Failed to open memory database")
+}
+
+fn get_point(tag: &str, time: i64) -> Args {
+    let mut data = HashMap::new();
+    data.insert(String::from("tag"), DBValue::from(tag));
+    data.insert(String::from("value"), DBValue::Int(time*10));
+    data.insert(String::from("time"), DBValue::Timestamp(time));
+    return data
+}
+
+fn insert_points(database: &dyn QueryBase, tags: &[&str], times: &[i64]) {
+    let data = tags.iter()
+        .flat_map(|tag| times.iter().map(move |time| get_point(tag, *time)))
+        .collect();
+    assert!(database.insert_many(TABLE, data).is_none());
+}
+
+fn select(database: &dyn QueryBase, request: Request) -> Vec<Value> {
+    return database.select(request).expect("Failed to select")
+        .map(|document_res| document_res.expect("Failed to read document"))
+        .collect()
+}
+
+fn get_times(documents: &[Value]) -> Vec<i64> {
+    return documents.iter().map(|document| json_to_timestamp(&document["time"]).expect("No time")).collect()
+}
+
+fn get_condition(key: &str, condition: Condition) -> Conditions {
+    let mut conditions = HashMap::new();
+    conditions.insert(String::from(key), condition);
+    return conditions
+}
+
+#[test]
+fn memory_select_matches_conditions() {
+    let database = get_memory_database("select_conditions");
+    assert!(database.ensure_index(TABLE, "tag").is_none());
+    insert_points(database.as_ref(), &["a", "b"], &[1, 2, 3, 4]);
+    
+    let mut conditions = get_condition("tag", Condition::Eq(DBValue::from("a")));
+    conditions.insert(String::from("time"), Condition::Range(DBValue::Timestamp(2), DBValue::Timestamp(4)));
+    let documents = select(database.as_ref(), Request::from_condition(String::from(TABLE), conditions));
+    assert_eq!(get_times(&documents), vec![2, 3]);
+    assert!(documents.iter().all(|document| document["tag"] == "a"));
+    
+    let conditions = get_condition("value", Condition::In(vec![DBValue::Int(10), DBValue::Int(40)]));
+    let documents = select(database.as_ref(), Request::from_condition(String::from(TABLE), conditions));
+    assert_eq!(get_times(&documents), vec![1, 4, 1, 4]);
+    
+    let conditions = get_condition("tag", Condition::Ne(DBValue::from("a")));
+    let documents = select(database.as_ref(), Request::from_condition(String::from(TABLE), conditions));
+    assert_eq!(documents.len(), 4);
+    
+    let conditions = get_condition("tag", Condition::Eq(DBValue::from("c")));
+    assert!(select(database.as_ref(), Request::from_condition(String::from(TABLE), conditions)).is_empty());
+}
+
+#[test]
+fn memory_select_orders_and_pages() {
+    let database = get_memory_database("select_order");
+    insert_points(database.as_ref(), &["a"], &[3, 1, 4, 2, 5]);
+    let request = Request::from_condition(String::from(TABLE), HashMap::new());
+    
+    let documents = select(database.as_ref(), request.clone().with_order("time", Order::Desc).with_skip(1).with_limit(2));
+    assert_eq!(get_times(&documents), vec![4, 3]);
+    
+    let documents = select(database.as_ref(), request.clone().with_order("time", Order::Asc).with_fields(vec![String::from("time")]));
+    assert_eq!(get_times(&documents), vec![1, 2, 3, 4, 5]);
+    assert!(documents.iter().all(|document| document["tag"].is_null()));
+    
+    let first_page = select(database.as_ref(), request.clone().with_order("time", Order::Asc).with_limit(2));
+    let next_page = select(database.as_ref(), request.with_order("time", Order::Asc).with_limit(2).next_page(&first_page[1]));
+    assert_eq!(get_times(&next_page), vec![3, 4]);
+}
+
+#[test]
+fn memory_update_and_delete() {
+    let database = get_memory_database("update_delete");
+    assert!(database.ensure_index(TABLE, "tag").is_none());
+    insert_points(database.as_ref(), &["a", "b"], &[1, 2, 3]);
+    
+    let mut data = HashMap::new();
+    data.insert(String::from("tag"), DBValue::from("c"));
+    let request_update = Request::new(String::from(TABLE), data, get_condition("time", Condition::Eq(DBValue::Timestamp(3))));
+    assert!(database.update(request_update).is_none());
+    let conditions = get_condition("tag", Condition::Eq(DBValue::from("c")));
+    assert_eq!(select(database.as_ref(), Request::from_condition(String::from(TABLE), conditions)).len(), 2);
+    
+    let mut conditions = get_condition("tag", Condition::Eq(DBValue::from("a")));
+    conditions.insert(String::from("time"), Condition::Lt(DBValue::Timestamp(2)));
+    assert!(database.delete(Request::from_condition(String::from(TABLE), conditions)).is_none());
+    let conditions = get_condition("tag", Condition::Eq(DBValue::from("a")));
+    let documents = select(database.as_ref(), Request::from_condition(String::from(TABLE), conditions));
+    assert_eq!(get_times(&documents), vec![2]);
+}
+
+#[test]
+fn memory_distinct_is_limited() {
+    let database = get_memory_database("distinct");
+    insert_points(database.as_ref(), &["a", "b", "c"], &[1, 2]);
+    
+    let values = database.distinct(TABLE, "tag", 2).expect("Failed to execute distinct");
+    assert_eq!(values, vec![Value::from("a"), Value::from("b")]);
+    assert!(database.distinct("missing", "tag", 2).expect("Failed to execute distinct").is_empty());
+}
+
+#[test]
+fn memory_connections_share_store() {
+    let database = get_memory_database("shared");
+    insert_points(database.as_ref(), &["a"], &[1]);
+    let other = get_memory_database("shared");
+    assert_eq!(select(other.as_ref(), Request::from_condition(String::from(TABLE), HashMap::new())).len(), 1);
+    let separate = get_memory_database("separate");
+    assert!(select(separate.as_ref(), Request::from_condition(String::from(TABLE), HashMap::new())).is_empty());
+}
+
+#[test]
+fn memory_injects_latency_and_errors() {
+    let config = get_memory_config("latency", MemoryConfig::new(20, 0, 0.0));
+    let database = get_database(&DatabaseType::Memory, config).expect("Failed to open memory database");
+    let start_time = Instant::now();
+    assert!(database.ping().is_none());
+    assert!(start_time.elapsed() >= Duration::from_millis(20));
+    
+    let config = get_memory_config("errors", MemoryConfig::new(0, 0, 1.0));
+    let database = get_database(&DatabaseType::Memory, config).expect("Failed to open memory database");
+    let err = database.insert_many(TABLE, vec![get_point("a", 1)]).expect("No injected error");
+    assert!(err.is_connection_error());
+    assert!(database.select(Request::from_condition(String::from(TABLE), HashMap::new())).is_err());
+}
+
+#[test]
+fn memory_pool_reconnects_after_injected_errors() {
+    let config = get_memory_config("pool_errors", MemoryConfig::new(0, 0, 1.0));
+    let pool = get_pool(DatabaseType::Memory, config).expect("Failed to open pool");
+    
+    assert!(pool.ping().is_some());
+    let events = pool.take_reconnect_events();
+    assert_eq!(events.len(), 1);
+    assert!(events[0].is_reconnected());
+}
+
+/// Pool of one connection to `store`, the first connection fails every request and
+/// the ones it reconnects with don't.
+fn get_flaky_pool(store: &'static str) -> pool::Pool {
+    let connections = Cell::new(0);
+    return pool::Pool::new(PoolConfig::new(1, 0, 1, 0, 0), move || {
+        let error_rate = if connections.get() == 0 { 1.0 } else { 0.0 };
+        connections.set(connections.get()+1);
+        return Ok(Box::new(memory_facade::Database::new(Some(store), MemoryConfig::new(0, 0, error_rate))) as Box<dyn QueryBase>)
+    }).expect("Failed to open pool")
+}
+
+#[test]
+fn memory_pool_does_not_resend_writes() {
+    let database = get_memory_database("pool_writes");
+    insert_points(database.as_ref(), &["a"], &[1, 2]);
+    let all = || Request::from_condition(String::from(TABLE), HashMap::new());
+    
+    let pool = get_flaky_pool("pool_writes");
+    assert!(pool.insert_many(TABLE, vec![get_point("a", 3)]).is_some());
+    assert_eq!(pool.take_reconnect_events().len(), 1);
+    let mut data = HashMap::new();
+    data.insert(String::from("value"), DBValue::Int(0));
+    let update = Request::new(String::from(TABLE), data, get_condition("tag", Condition::Eq(DBValue::from("a"))));
+    assert!(get_flaky_pool("pool_writes").update(update).is_some());
+    assert!(get_flaky_pool("pool_writes").delete(all()).is_some());
+    
+    let documents = select(database.as_ref(), all().with_order("time", Order::Asc));
+    assert_eq!(get_times(&documents), vec![1, 2]);
+    assert_eq!(documents[0]["value"], 10);
+    // Reads are sent again on the new connection
+    assert_eq!(select(&get_flaky_pool("pool_writes"), all()).len(), 2);
+}
+
+#[test]
+fn memory_changes_report_new_documents() {
+    let database = get_memory_database("changes");
+    insert_points(database.as_ref(), &["a"], &[1]);
+    
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let subscriber = thread::spawn(move || {
+        let database = get_memory_database("changes");
+        let mut changes = Vec::new();
+        let request = Request::from_condition(String::from(TABLE), get_condition("tag", Condition::Eq(DBValue::from("b"))));
+        database.changes(request, &mut |change_res| {
+            let change = change_res.expect("Failed to receive change");
+            if change["state"] == "ready" {
+                ready_tx.send(()).expect("Test stopped");
+                return true
+            }
+            changes.push(change["new_val"]["time"].as_i64().expect("No time"));
+            return changes.len() < 2
+        });
+        return changes
+    });
+    
+    ready_rx.recv_timeout(Duration::from_secs(10)).expect("Subscription is not ready");
+    insert_points(database.as_ref(), &["a", "b"], &[2]);
+    insert_points(database.as_ref(), &["b"], &[3]);
+    assert_eq!(subscriber.join().expect("Subscriber panicked"), vec![2, 3]);
+}
+
+#[test]
+fn memory_executor_runs_operations() {
+    let mut executor = QueryExecutor::get_constructor(DatabaseType::Memory)(get_memory_config("executor", MemoryConfig::default())).expect("Failed to connect");
+    let points = (0..10)
+        .map(|time| Point::new(format!("series{}", time % 2), time as usize, time))
+        .collect();
+    executor.insert(points).expect("Failed to insert points");
+    
+    let mut hashes = executor.get_hashes(10).expect("Failed to get hashes");
+    hashes.sort();
+    assert_eq!(hashes, vec![String::from("series0"), String::from("series1")]);
+    assert_eq!(executor.get_last_timestamp("series1").expect("Failed to get last timestamp"), Some(9));
+    executor.select("series0", &Query::Range(2, 6)).expect("Failed to select");
+    
+    executor.delete("series1", 9).expect("Failed to delete");
+    executor.compact().expect("Failed to compact");
+    assert_eq!(executor.get_last_timestamp("series1").expect("Failed to get last timestamp"), Some(9));
+    executor.delete("series1", 10).expect("Failed to delete");
+    assert_eq!(executor.get_last_timestamp("series1").expect("Failed to get last timestamp"), None);
+    assert!(executor.take_reconnect_events().is_empty());
+}
+
+#[test]
+fn rethink_database_persists_and_selects() {
+    let stand_in = StandIn::start(&DatabaseType::Rethinkdb, &[TABLE]);
+    let database = get_database(&DatabaseType::Rethinkdb, stand_in.get_pool_config()).expect("Failed to connect to rethinkdb");
+    assert!(database.ensure_index(TABLE, "tag").is_none());
+    assert!(database.ensure_index(TABLE, "tag").is_none());
+    assert_eq!(database.get_indexes(TABLE).expect("Failed to list indexes"), json!(["tag"]));
+    insert_points(database.as_ref(), &["a", "b"], &[1, 2, 3, 4]);
+    
+    let mut conditions = get_condition("tag", Condition::Eq(DBValue::from("a")));
+    conditions.insert(String::from("time"), Condition::Range(DBValue::Timestamp(2), DBValue::Timestamp(4)));
+    let request = Request::from_condition(String::from(TABLE), conditions).with_order("time", Order::Asc);
+    let documents = select(database.as_ref(), request);
+    assert_eq!(get_times(&documents), vec![2, 3]);
+    assert!(documents.iter().all(|document| document["tag"] == "a"));
+    assert_eq!(documents[0]["value"], 20);
+    
+    let request = Request::from_condition(String::from(TABLE), get_condition("value", Condition::In(vec![DBValue::Int(10), DBValue::Int(40)])))
+        .with_order("time", Order::Desc)
+        .with_skip(1)
+        .with_limit(2)
+        .with_fields(vec![String::from("time")]);
+    let documents = select(database.as_ref(), request);
+    assert_eq!(get_times(&documents), vec![4, 1]);
+    assert!(documents.iter().all(|document| document["tag"].is_null()));
+    
+    let request = Request::from_condition(String::from(TABLE), HashMap::new()).with_order("time", Order::Asc).with_batch_size(3);
+    assert_eq!(select(database.as_ref(), request).len(), 8);
+    let values = database.distinct(TABLE, "tag", 10).expect("Failed to execute distinct");
+    assert_eq!(values, vec![Value::from("a"), Value::from("b")]);
+    
+    let mut data = HashMap::new();
+    data.insert(String::from("tag"), DBValue::from("c"));
+    let request_update = Request::new(String::from(TABLE), data, get_condition("time", Condition::Eq(DBValue::Timestamp(4))));
+    assert!(database.update(request_update).is_none());
+    let conditions = get_condition("tag", Condition::Eq(DBValue::from("c")));
+    assert_eq!(select(database.as_ref(), Request::from_condition(String::from(TABLE), conditions)).len(), 2);
+    
+    let mut conditions = get_condition("tag", Condition::Ne(DBValue::from("c")));
+    conditions.insert(String::from("time"), Condition::Lt(DBValue::Timestamp(3)));
+    assert!(database.delete(Request::from_condition(String::from(TABLE), conditions)).is_none());
+    let documents = select(database.as_ref(), Request::from_condition(String::from(TABLE), HashMap::new()).with_order("time", Order::Asc));
+    assert_eq!(get_times(&documents), vec![3, 3, 4, 4]);
+    assert!(database.ping().is_none());
+}
+
+#[test]
+fn rethink_failed_writes_are_errors() {
+    let stand_in = StandIn::start(&DatabaseType::Rethinkdb, &[TABLE]);
+    let database = get_database(&DatabaseType::Rethinkdb, stand_in.get_pool_config()).expect("Failed to connect to rethinkdb");
+    
+    // The server fails the query on a missing table
+    let err = database.insert_many("missing", vec![get_point("a", 1)]).expect("Write to a missing table succeeded");
+    assert!(matches!(err, DBError::Rethink(_)), "Unexpected error: {:?}", err);
+    assert!(!err.is_connection_error());
+    let request = Request::from_condition(String::from("missing"), get_condition("tag", Condition::Eq(DBValue::from("a"))));
+    assert!(matches!(database.delete(request), Some(DBError::Rethink(_))));
+    
+    // but only reports the documents it could not write
+    let mut data = get_point("a", 1);
+    data.insert(String::from("id"), DBValue::from("point1"));
+    assert!(database.insert(Request::new(String::from(TABLE), data.clone(), HashMap::new())).is_none());
+    let err = database.insert(Request::new(String::from(TABLE), data, HashMap::new())).expect("Duplicate write succeeded");
+    assert!(format!("{:?}", err).contains("Duplicate primary key"), "Unexpected error: {:?}", err);
+    assert!(!err.is_connection_error());
+    assert_eq!(select(database.as_ref(), Request::from_condition(String::from(TABLE), HashMap::new())).len(), 1);
+    assert!(database.ping().is_none());
+}
+
+/// Inserts points with `executor` and reads them back with a facade of the same
+/// backend to check that every field of a point is persisted.
+fn check_executor_persists_and_selects<E: Executor>(executor: &mut E, database_type: &DatabaseType, stand_in: &StandIn, table: &str) {
+    let points = (0..10)
+        .map(|time| Point::new(format!("series{}", time % 2), time as usize*100, time))
+        .collect();
+    executor.insert(points).expect("Failed to insert points");
+    
+    let database = get_database(database_type, stand_in.get_pool_config()).expect("Failed to connect to database");
+    let request = Request::from_condition(String::from(table), get_condition("address", Condition::Eq(DBValue::from("series1"))))
+        .with_order("time", Order::Asc);
+    let documents = select(database.as_ref(), request);
+    assert_eq!(get_times(&documents), vec![1, 3, 5, 7, 9]);
+    for document in documents.iter() {
+        let time = json_to_timestamp(&document["time"]).expect("No time");
+        assert_eq!(document["address"], "series1");
+        assert_eq!(document["from"], "series1from");
+        assert_eq!(document["to"], "series1to");
+        assert_eq!(document["balance"].as_i64(), Some(time*100));
+    }
+    
+    let mut hashes = executor.get_hashes(10).expect("Failed to get hashes");
+    hashes.sort();
+    assert_eq!(hashes, vec![String::from("series0"), String::from("series1")]);
+    assert_eq!(executor.get_last_timestamp("series0").expect("Failed to get last timestamp"), Some(8));
+    executor.select("series0", &Query::Range(2, 6)).expect("Failed to select");
+    executor.select("series0", &Query::Series).expect("Failed to select");
+    
+    executor.delete("series1", 9).expect("Failed to delete");
+    assert_eq!(executor.get_last_timestamp("series1").expect("Failed to get last timestamp"), Some(9));
+    let conditions = get_condition("address", Condition::Eq(DBValue::from("series1")));
+    assert_eq!(select(database.as_ref(), Request::from_condition(String::from(table), conditions)).len(), 1);
+    assert!(executor.take_reconnect_events().is_empty());
+}
+
+#[test]
+fn influx_executor_persists_and_selects() {
+    let stand_in = StandIn::start(&DatabaseType::Influxdb, &[]);
+    let mut executor = QueryExecutor::get_constructor(DatabaseType::Influxdb)(stand_in.get_pool_config()).expect("Failed to connect");
+    check_executor_persists_and_selects(&mut executor, &DatabaseType::Influxdb, &stand_in, "accounts");
+}
+
+#[test]
+fn mongo_executor_persists_and_selects() {
+    let stand_in = StandIn::start(&DatabaseType::Mongodb, &[]);
+    let mut executor = QueryExecutor::get_constructor(DatabaseType::Mongodb)(stand_in.get_pool_config()).expect("Failed to connect");
+    check_executor_persists_and_selects(&mut executor, &DatabaseType::Mongodb, &stand_in, "accounts");
+}
+
+#[test]
+fn rethink_executor_persists_and_selects() {
+    let stand_in = StandIn::start(&DatabaseType::Rethinkdb, &["test"]);
+    let mut executor = QueryExecutor::get_constructor(DatabaseType::Rethinkdb)(stand_in.get_pool_config()).expect("Failed to connect");
+    check_executor_persists_and_selects(&mut executor, &DatabaseType::Rethinkdb, &stand_in, "test");
+}
+
+#[test]
+fn memory_executor_persists_and_selects() {
+    let config = get_memory_config("executor_fields", MemoryConfig::default());
+    let mut executor = QueryExecutor::get_constructor(DatabaseType::Memory)(config).expect("Failed to connect");
+    let stand_in_database = get_database(&DatabaseType::Memory, config).expect("Failed to open memory database");
+    let points = vec![Point::new(String::from("series1"), 300, 3)];
+    executor.insert(points).expect("Failed to insert points");
+    
+    let documents = select(stand_in_database.as_ref(), Request::from_condition(String::from("accounts"), HashMap::new()));
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0]["address"], "series1");
+    assert_eq!(documents[0]["from"], "series1from");
+    assert_eq!(documents[0]["to"], "series1to");
+    assert_eq!(documents[0]["balance"], 300);
+}
+
+
+#[test]
+fn influx_mock_records_lines_and_answers_canned_series() {
+    let mock = InfluxMock::start(0).with_executor_responses(3);
+    let mut executor = QueryExecutor::get_constructor(DatabaseType::Influxdb)(PoolConfig::new(1, 0, 1, 0, 0).with_host(Some(mock.get_host()))).expect("Failed to connect");
+    executor.insert(vec![Point::new(String::from("series1"), 300, 3), Point::new(String::from("series2"), 400, 4)]).expect("Failed to insert points");
+    
+    let lines = mock.take_lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("accounts,address=series1 "));
+    assert!(lines[0].contains("balance=300i"));
+    assert!(lines[0].contains("from=\"series1from\""));
+    assert!(lines[0].contains("to=\"series1to\""));
+    assert!(lines[0].ends_with(" 3"));
+    assert!(lines[1].starts_with("accounts,address=series2 "));
+    assert!(mock.take_lines().is_empty());
+    
+    assert_eq!(executor.get_hashes(10).expect("Failed to get hashes"), vec![String::from("mock0"), String::from("mock1"), String::from("mock2")]);
+    assert_eq!(executor.get_last_timestamp("series1").expect("Failed to get last timestamp"), Some(0));
+    let queries = mock.take_queries();
+    assert!(queries.last().expect("No query").starts_with("select * from \"accounts\" where"));
+    assert_eq!(mock.get_counts(), InfluxMockCounts { writes: 1, lines: 2, queries: queries.len() as u64 });
+}
+
+#[test]
+fn influx_mock_answers_after_latency() {
+    let mock = InfluxMock::start(50)
+        .with_response("show databases", vec![get_series("databases", &["name"], vec![vec![json!("bench")]])])
+        .without_recording();
+    let database = get_database(&DatabaseType::Influxdb, PoolConfig::new(1, 0, 1, 0, 0).with_host(Some(mock.get_host())))
+        .expect("Failed to connect to database");
+    
+    let start_time = Instant::now();
+    assert!(database.ping().is_none());
+    assert!(start_time.elapsed() >= Duration::from_millis(50));
+    
+    // Queries without a canned answer get an empty result
+    assert!(select(database.as_ref(), Request::from_condition(String::from(TABLE), HashMap::new())).is_empty());
+    assert!(database.insert_many(TABLE, vec![get_point("a", 1)]).is_none());
+    assert!(mock.take_lines().is_empty());
+    assert!(mock.take_queries().is_empty());
+    assert_eq!(mock.get_counts(), InfluxMockCounts { writes: 1, lines: 1, queries: 2 });
+}
+
+#[test]
+fn influx_rejected_points_are_errors() {
+    let stand_in = StandIn::start(&DatabaseType::Influxdb, &[]);
+    let database = get_database(&DatabaseType::Influxdb, stand_in.get_pool_config()).expect("Failed to connect to influxdb");
+    insert_points(database.as_ref(), &["a"], &[1]);
+    
+    // A field keeps the type it was first written with
+    let mut data = get_point("a", 2);
+    data.insert(String::from("value"), DBValue::from("twenty"));
+    let err = database.insert_many(TABLE, vec![data, get_point("a", 3)]).expect("Write of a conflicting field succeeded");
+    assert!(matches!(err, DBError::Influx(ClientError::Syntax(_))), "Unexpected error: {:?}", err);
+    assert!(!err.is_connection_error());
+    
+    let documents = select(database.as_ref(), Request::from_condition(String::from(TABLE), HashMap::new()));
+    assert_eq!(get_times(&documents), vec![1, 3]);
+}
+
+#[test]
+fn influx_statement_errors_fail_the_query() {
+    let mock = InfluxMock::start(0)
+        .with_error("select", "retention policy not found: autogen")
+        .with_error("show tag values", "shard is unavailable");
+    let database = get_database(&DatabaseType::Influxdb, PoolConfig::new(1, 0, 1, 0, 0).with_host(Some(mock.get_host())))
+        .expect("Failed to connect to database");
+    
+    match database.select(Request::from_condition(String::from(TABLE), HashMap::new())) {
+        Err(DBError::Influx(ClientError::CouldNotComplete(message))) => assert_eq!(message, "retention policy not found: autogen"),
+        Err(err) => panic!("Unexpected error: {:?}", err),
+        Ok(_) => panic!("Failed query succeeded")
+    }
+    match database.distinct(TABLE, "tag", 10) {
+        Err(err) => assert!(!err.is_connection_error(), "Unexpected error: {:?}", err),
+        Ok(values) => panic!("Failed query returned {:?}", values)
+    }
+    assert!(database.ping().is_none());
+}
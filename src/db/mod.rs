@@ -1,3 +1,4 @@
+#[allow(dead_code)]
 pub mod db_raw;
 
 extern crate futures;
@@ -0,0 +1,384 @@
+use {compute_time_diff_ms, get_current_time, Executor, Point, Query};
+use bench_rng::BenchRng;
+use db::db_raw::query_db::{DBError, PoolConfig, ReconnectEvent};
+use rand::Rng;
+use serde_json::Value;
+use std::thread;
+use std::time::Duration;
+
+
+static DEFAULT_LATENCY_SPIKE_MS: u64 = 1000;
+static DEFAULT_DROPPED_HANG_MS: u64 = 60000;
+static DEFAULT_RETRY_BACKOFF_MS: u64 = 100;
+
+/// Chance of every kind of fault per operation, from 0 to 1.
+#[derive(Clone, Copy, Debug, Default)]
+struct FaultRates {
+    latency_spike: f64,
+    dropped: f64,
+    error: f64,
+    reset: f64
+}
+
+impl FaultRates {
+    /// Missing rates are taken from `defaults`.
+    fn from_json(json: &Value, defaults: FaultRates) -> FaultRates {
+        let rate = |key: &str, default: f64| {
+            let rate = json[key].as_f64().unwrap_or(default);
+            assert!((0.0..=1.0).contains(&rate), "Fault {} out of 0..1: {}", key, rate);
+            return rate
+        };
+        return FaultRates {
+            latency_spike: rate("latency_spike_rate", defaults.latency_spike),
+            dropped: rate("dropped_rate", defaults.dropped),
+            error: rate("error_rate", defaults.error),
+            reset: rate("reset_rate", defaults.reset)
+        }
+    }
+}
+
+/// Rates that replace the default ones from `from_ms` inclusive to `to_ms`
+/// exclusive after the start of the run.
+#[derive(Clone, Copy, Debug)]
+struct FaultWindow {
+    from_ms: u64,
+    to_ms: u64,
+    rates: FaultRates
+}
+
+/// Faults that `FaultExecutor` injects into the operations of a backend.
+#[derive(Clone, Debug)]
+pub struct FaultConfig {
+    rates: FaultRates,
+    windows: Vec<FaultWindow>,
+    latency_spike_ms: u64,
+    dropped_hang_ms: u64,
+    retries: u32,
+    retry_backoff_ms: u64,
+    /// Time the windows are relative to
+    start_time: u128
+}
+
+impl FaultConfig {
+    /// JSON keys: latency_spike_rate, dropped_rate, error_rate, reset_rate,
+    /// latency_spike, dropped_hang, retries, retry_backoff and windows of
+    /// {from, to} with their own rates. The rates are from 0 to 1.
+    pub fn from_json(json: &Value) -> FaultConfig {
+        let rates = FaultRates::from_json(json, FaultRates::default());
+        let windows = match json["windows"].as_array() {
+            Some(windows) => windows.iter()
+                .map(|window_json| {
+                    let from_ms = window_json["from"].as_u64().expect("Invalid fault window from param");
+                    let to_ms = window_json["to"].as_u64().expect("Invalid fault window to param");
+                    assert!(from_ms <= to_ms, "Fault window ends before it starts: {} > {}", from_ms, to_ms);
+                    return FaultWindow {
+                        from_ms: from_ms,
+                        to_ms: to_ms,
+                        rates: FaultRates::from_json(window_json, rates)
+                    }
+                })
+                .collect(),
+            None => Vec::new()
+        };
+        
+        return FaultConfig {
+            rates: rates,
+            windows: windows,
+            latency_spike_ms: json["latency_spike"].as_u64().unwrap_or(DEFAULT_LATENCY_SPIKE_MS),
+            dropped_hang_ms: json["dropped_hang"].as_u64().unwrap_or(DEFAULT_DROPPED_HANG_MS),
+            retries: json["retries"].as_u64().unwrap_or(0) as u32,
+            retry_backoff_ms: json["retry_backoff"].as_u64().unwrap_or(DEFAULT_RETRY_BACKOFF_MS),
+            start_time: get_current_time()
+        }
+    }
+    
+    /// Config with the windows starting at `start_time`.
+    pub fn starting_at(&self, start_time: u128) -> FaultConfig {
+        let mut config = self.clone();
+        config.start_time = start_time;
+        return config
+    }
+    
+    fn get_rates(&self) -> FaultRates {
+        let elapsed_ms = get_current_time().saturating_sub(self.start_time) as u64;
+        return self.windows.iter()
+            .find(|window| window.from_ms <= elapsed_ms && elapsed_ms < window.to_ms)
+            .map_or(self.rates, |window| window.rates)
+    }
+}
+
+/// Number of injected faults and of the retries they caused.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FaultCounts {
+    pub latency_spikes: u64,
+    pub dropped: u64,
+    pub errors: u64,
+    pub resets: u64,
+    pub retries: u64
+}
+
+impl FaultCounts {
+    pub fn add(&mut self, other: &FaultCounts) {
+        self.latency_spikes += other.latency_spikes;
+        self.dropped += other.dropped;
+        self.errors += other.errors;
+        self.resets += other.resets;
+        self.retries += other.retries;
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        return *self == FaultCounts::default()
+    }
+    
+    pub fn to_json(self) -> Value {
+        return json!({
+            "latency_spikes": self.latency_spikes,
+            "dropped": self.dropped,
+            "errors": self.errors,
+            "resets": self.resets,
+            "retries": self.retries
+        });
+    }
+    
+    /// Results written without fault injection have no counts.
+    pub fn from_json(json: &Value) -> FaultCounts {
+        let number = |key: &str| json[key].as_u64().unwrap_or(0);
+        return FaultCounts {
+            latency_spikes: number("latency_spikes"),
+            dropped: number("dropped"),
+            errors: number("errors"),
+            resets: number("resets"),
+            retries: number("retries")
+        }
+    }
+}
+
+/// Wraps the executor of any backend and injects faults into its inserts, selects,
+/// deletes and compactions. The time of an operation covers its faults and retries:
+/// - a latency spike waits before the operation,
+/// - a dropped response withholds the result, it times out with an operation timeout,
+/// - an error fails the operation before it reaches the database,
+/// - a reset replaces the executor with new connections before the operation.
+///
+/// Injected errors and connection errors of the database are retried after a
+/// backoff, the operation fails with the error once it is out of retries. The
+/// faults are drawn from the seed of `set_seed`, so a run with the same seed
+/// injects the same faults.
+pub struct FaultExecutor<E: Executor> {
+    new_executor: fn(PoolConfig) -> Result<E, DBError>,
+    pool_config: PoolConfig,
+    executor: E,
+    config: FaultConfig,
+    rng: BenchRng,
+    counts: FaultCounts,
+    reconnect_events: Vec<ReconnectEvent>
+}
+
+impl<E: Executor> FaultExecutor<E> {
+    pub fn new(new_executor: fn(PoolConfig) -> Result<E, DBError>, pool_config: PoolConfig, config: FaultConfig) -> Result<FaultExecutor<E>, DBError> {
+        return Ok(FaultExecutor {
+            new_executor: new_executor,
+            pool_config: pool_config,
+            executor: new_executor(pool_config)?,
+            config: config,
+            rng: BenchRng::new(0),
+            counts: FaultCounts::default(),
+            reconnect_events: Vec::new()
+        })
+    }
+    
+    /// Keeps the executor when the new one can't connect.
+    fn reset(&mut self) -> Option<DBError> {
+        let start_time = get_current_time();
+        self.reconnect_events.extend(self.executor.take_reconnect_events());
+        let executor_res = (self.new_executor)(self.pool_config);
+        let is_reconnected = executor_res.is_ok();
+        self.reconnect_events.push(ReconnectEvent::new(start_time,
+            1,
+            compute_time_diff_ms(start_time, get_current_time()),
+            is_reconnected,
+            String::from("Injected connection reset")));
+        return match executor_res {
+            Ok(executor) => {
+                self.executor = executor;
+                None
+            },
+            Err(err) => Some(err)
+        }
+    }
+    
+    /// Runs `operation` once per attempt.
+    fn run<F>(&mut self, mut operation: F) -> Result<i32, DBError>
+        where F: FnMut(&mut E) -> Result<i32, DBError> {
+        
+        let rates = self.config.get_rates();
+        let start_time = get_current_time();
+        if self.rng.gen::<f64>() < rates.latency_spike {
+            self.counts.latency_spikes += 1;
+            thread::sleep(Duration::from_millis(self.config.latency_spike_ms));
+        }
+        
+        let mut attempts = 0;
+        loop {
+            let mut reset_error = None;
+            if self.rng.gen::<f64>() < rates.reset {
+                self.counts.resets += 1;
+                reset_error = self.reset();
+            }
+            let result = match reset_error {
+                Some(err) => Err(err),
+                None if self.rng.gen::<f64>() < rates.error => {
+                    self.counts.errors += 1;
+                    Err(DBError::Injected(String::from("Injected error")))
+                },
+                None => operation(&mut self.executor)
+            };
+            match result {
+                Err(ref err) if err.is_connection_error() && attempts < self.config.retries => {
+                    attempts += 1;
+                    self.counts.retries += 1;
+                    thread::sleep(Duration::from_millis(self.config.retry_backoff_ms));
+                },
+                Err(err) => return Err(err),
+                Ok(_) => break
+            }
+        }
+        
+        if self.rng.gen::<f64>() < rates.dropped {
+            self.counts.dropped += 1;
+            thread::sleep(Duration::from_millis(self.config.dropped_hang_ms));
+        }
+        return Ok(compute_time_diff_ms(start_time, get_current_time()));
+    }
+}
+
+impl<E: Executor> Executor for FaultExecutor<E> {
+    fn insert(&mut self, points: Vec<Point>) -> Result<i32, DBError> {
+        return self.run(|executor| executor.insert(points.clone()));
+    }
+    
+    fn select(&mut self, hash: &str, query: &Query) -> Result<i32, DBError> {
+        return self.run(|executor| executor.select(hash, query));
+    }
+    
+    fn delete(&mut self, hash: &str, before: i64) -> Result<i32, DBError> {
+        return self.run(|executor| executor.delete(hash, before));
+    }
+    
+    fn compact(&mut self) -> Result<i32, DBError> {
+        return self.run(|executor| executor.compact());
+    }
+    
+    fn get_last_timestamp(&mut self, hash: &str) -> Result<Option<i64>, DBError> {
+        return self.executor.get_last_timestamp(hash)
+    }
+    
+    fn get_hashes(&mut self, sample_size: usize) -> Result<Vec<String>, DBError> {
+        return self.executor.get_hashes(sample_size)
+    }
+    
+    fn take_reconnect_events(&mut self) -> Vec<ReconnectEvent> {
+        let mut events: Vec<ReconnectEvent> = self.reconnect_events.drain(..).collect();
+        events.extend(self.executor.take_reconnect_events());
+        return events
+    }
+    
+    fn set_seed(&mut self, seed: u64) {
+        self.rng = BenchRng::new(seed);
+    }
+    
+    fn take_fault_counts(&mut self) -> FaultCounts {
+        let counts = self.counts;
+        self.counts = FaultCounts::default();
+        return counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use influent::client::ClientError;
+    
+    /// Fails its first `failures` inserts with a connection error.
+    struct FlakyExecutor {
+        failures: usize
+    }
+    
+    impl Executor for FlakyExecutor {
+        fn insert(&mut self, _points: Vec<Point>) -> Result<i32, DBError> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(DBError::from(ClientError::Communication(String::from("Connection refused"))))
+            }
+            return Ok(0)
+        }
+        
+        fn select(&mut self, _hash: &str, _query: &Query) -> Result<i32, DBError> {
+            return Ok(0)
+        }
+        
+        fn delete(&mut self, _hash: &str, _before: i64) -> Result<i32, DBError> {
+            return Ok(0)
+        }
+        
+        fn compact(&mut self) -> Result<i32, DBError> {
+            return Ok(0)
+        }
+        
+        fn get_last_timestamp(&mut self, _hash: &str) -> Result<Option<i64>, DBError> {
+            return Ok(None)
+        }
+        
+        fn get_hashes(&mut self, _sample_size: usize) -> Result<Vec<String>, DBError> {
+            return Ok(Vec::new())
+        }
+        
+        fn take_reconnect_events(&mut self) -> Vec<ReconnectEvent> {
+            return Vec::new()
+        }
+    }
+    
+    fn get_pool_config() -> PoolConfig {
+        return PoolConfig::new(1, 0, 1, 0, 0)
+    }
+    
+    #[test]
+    fn connection_errors_are_retried() {
+        let config = FaultConfig::from_json(&json!({"retries": 2, "retry_backoff": 0}));
+        let mut executor = FaultExecutor::new(|_| Ok(FlakyExecutor { failures: 2 }), get_pool_config(), config.clone())
+            .expect("Failed to connect");
+        assert!(executor.insert(vec![Point::new(String::from("series0"), 0, 0)]).is_ok());
+        assert_eq!(executor.take_fault_counts().retries, 2);
+        
+        let mut executor = FaultExecutor::new(|_| Ok(FlakyExecutor { failures: 3 }), get_pool_config(), config)
+            .expect("Failed to connect");
+        let err = executor.insert(vec![Point::new(String::from("series0"), 0, 0)]).expect_err("Insert out of retries");
+        assert!(err.is_connection_error());
+    }
+    
+    #[test]
+    fn faults_of_the_same_seed_repeat() {
+        let config = FaultConfig::from_json(&json!({"error_rate": 0.5}));
+        let run = |seed: u64| {
+            let mut executor = FaultExecutor::new(|_| Ok(FlakyExecutor { failures: 0 }), get_pool_config(), config.clone())
+                .expect("Failed to connect");
+            executor.set_seed(seed);
+            return (0..32).map(|_| executor.compact().is_ok()).collect::<Vec<bool>>()
+        };
+        let faults = run(7);
+        assert_eq!(run(7), faults);
+        assert!(faults.contains(&true) && faults.contains(&false));
+    }
+    
+    #[test]
+    #[should_panic(expected = "Fault error_rate out of 0..1")]
+    fn rates_above_one_are_rejected() {
+        FaultConfig::from_json(&json!({"error_rate": 1.5}));
+    }
+    
+    #[test]
+    #[should_panic(expected = "Fault window ends before it starts")]
+    fn windows_ending_before_their_start_are_rejected() {
+        FaultConfig::from_json(&json!({"windows": [{"from": 2000, "to": 1000}]}));
+    }
+}
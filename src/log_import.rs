@@ -0,0 +1,239 @@
+use results::{LatencyHistogram, PhaseResult, Results};
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+
+/// Phase of a text log that is still being read.
+struct LogPhase {
+    phase: &'static str,
+    histogram: LatencyHistogram,
+    total: Option<u64>,
+    /// Completed queries
+    completed: Option<u64>,
+    /// Points of the completed queries of an insert phase, one per query in old logs
+    records: Option<u64>,
+    timed_out: u64,
+    queries_time_ms: i64
+}
+
+impl LogPhase {
+    fn new(phase: &'static str) -> LogPhase {
+        return LogPhase {
+            phase: phase,
+            histogram: LatencyHistogram::new(),
+            total: None,
+            completed: None,
+            records: None,
+            timed_out: 0,
+            queries_time_ms: 0
+        }
+    }
+    
+    /// A phase without its summary line was interrupted and has no result.
+    fn into_result(self) -> Option<PhaseResult> {
+        let operations = self.completed?;
+        let completed = self.records.unwrap_or(operations);
+        let total = self.total.unwrap_or(completed+self.timed_out);
+        return Some(PhaseResult::from_totals(self.phase.to_string(),
+            total,
+            completed,
+            operations,
+            self.timed_out,
+            self.queries_time_ms,
+            self.histogram))
+    }
+}
+
+/// Converts text logs written by `write_log` into a results file. Every log is a
+/// test case of the database in its name, `InfluxDB_log1.txt` is test case 1 of
+/// InfluxDB and the only benchmark logs like `influx_only_bench.txt` are test case 0.
+///
+/// Insert phases only log a sample of the query times, so their percentiles are
+/// estimates and `null` when no time was logged, and the logs have no wall time,
+/// so throughput is computed from the sum of the query times.
+pub fn import_logs(results_file_path: &str, log_file_paths: &[String]) {
+    let mut results = Results::new();
+    for log_file_path in log_file_paths {
+        let (database, test_case_id) = parse_file_name(log_file_path);
+        let phases = parse_log(log_file_path);
+        println!("Import {}: {} test case {}, {} phases", log_file_path, database, test_case_id, phases.len());
+        
+        let run = results.get_run_mut(&database, get_modified_time(log_file_path));
+        for phase_result in phases {
+            run.get_test_case_mut(test_case_id).add_phase(0, phase_result);
+        }
+    }
+    results.save(results_file_path);
+}
+
+fn parse_file_name(log_file_path: &str) -> (String, usize) {
+    let file_name = Path::new(log_file_path)
+        .file_stem()
+        .and_then(|file_name| file_name.to_str())
+        .expect("Invalid log file name");
+    
+    if let Some(pos) = file_name.find("_only_bench") {
+        return (get_database_name(&file_name[..pos]), 0)
+    }
+    return match file_name.rfind("_log") {
+        Some(pos) => {
+            let test_case_id = file_name[pos+4..].parse().expect("Invalid test case id in log file name");
+            (get_database_name(&file_name[..pos]), test_case_id)
+        },
+        None => panic!("Invalid log file name: {}", file_name)
+    }
+}
+
+/// Old only benchmark logs are named after the database in lower case.
+fn get_database_name(prefix: &str) -> String {
+    let lowercase = prefix.to_lowercase();
+    if lowercase.starts_with("influx") {
+        return String::from("InfluxDB")
+    } else if lowercase.starts_with("mongo") {
+        return String::from("MongoDB")
+    } else if lowercase.starts_with("rethink") {
+        return String::from("RethinkDB")
+    }
+    return prefix.to_string()
+}
+
+fn get_modified_time(file_path: &str) -> u128 {
+    return fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |modified| modified.as_millis())
+}
+
+fn parse_log(log_file_path: &str) -> Vec<PhaseResult> {
+    let file = File::open(log_file_path).expect("Can't open log file");
+    let mut phases = Vec::new();
+    let mut current: Option<LogPhase> = None;
+    
+    for line_res in BufReader::new(file).lines() {
+        let line = line_res.expect("Can't read log file");
+        let next_phase = if line.starts_with("# Begin insert series") {
+            Some("series")
+        } else if line.starts_with("# Begin insert points") {
+            Some("points")
+        } else if line.starts_with("# Begin select queries") {
+            Some("select")
+        } else {
+            None
+        };
+        if let Some(next_phase) = next_phase {
+            phases.extend(current.take().and_then(LogPhase::into_result));
+            current = Some(LogPhase::new(next_phase));
+            continue
+        }
+        
+        if let Some(ref mut phase) = current {
+            parse_line(phase, &line);
+        }
+    }
+    phases.extend(current.take().and_then(LogPhase::into_result));
+    return phases;
+}
+
+fn parse_line(phase: &mut LogPhase, line: &str) {
+    let numbers = parse_numbers(line);
+    if line.starts_with("Insert query;") || (line.starts_with("Select query") && line.contains("query time")) {
+        if let Some(query_time) = numbers.last() {
+            phase.histogram.record(*query_time as i32);
+        }
+    } else if line.starts_with("# Insert ") || line.starts_with("# Select ") {
+        if line.contains("timed out") {
+            phase.timed_out = numbers.last().cloned().unwrap_or(0);
+        } else if line.contains("stopped after") && numbers.len() >= 2 {
+            phase.total = Some(numbers[1]);
+        } else if numbers.len() >= 2 {
+            phase.completed = Some(numbers[0]);
+            phase.queries_time_ms = numbers[1] as i64;
+            if line.starts_with("# Insert ") {
+                phase.records = numbers.get(2).cloned();
+            }
+        }
+    }
+}
+
+/// Returns the whole numbers of a line in order.
+fn parse_numbers(line: &str) -> Vec<u64> {
+    return line.split(|symbol: char| !symbol.is_ascii_digit())
+        .filter(|number| !number.is_empty())
+        .filter_map(|number| number.parse().ok())
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+    
+    static LOG: &str = "\
+# Begin insert series
+# Insert 10 queries for 30 ms, 10 points
+# Begin insert points
+Insert query; average time: 3 ms, query time: 3 ms
+Insert query; average time: 4 ms, query time: 5 ms
+# Insert queries timed out: 10
+# Insert stopped after 1000 of 1200 queries, phase timeout
+# Insert 20 queries for 80 ms, 990 points
+# Begin select queries
+Select query №0 average time: 2 ms, query time: 2 ms
+Select query №1 timed out
+# Select 1 queries for 2 ms, 100 entities per query
+# Begin insert points
+Insert query; average time: 1 ms, query time: 1 ms
+";
+    
+    #[test]
+    fn log_phases_are_imported_without_the_unfinished_one() {
+        let log_file_path = env::temp_dir().join(format!("InfluxDB_log{}.txt", process::id()));
+        File::create(&log_file_path)
+            .and_then(|mut file| file.write_all(LOG.as_bytes()))
+            .expect("Can't write test log");
+        let phases = parse_log(log_file_path.to_str().expect("Invalid test log path"));
+        fs::remove_file(&log_file_path).expect("Can't remove test log");
+        assert_eq!(phases.len(), 3);
+        
+        let series = &phases[0];
+        assert_eq!(series.get_phase(), "series");
+        assert_eq!(series.get_completed(), 10);
+        assert_eq!(series.get_latency().get_p50(), None);
+        
+        let points = &phases[1];
+        assert_eq!(points.get_phase(), "points");
+        assert_eq!(points.get_total(), 1200);
+        assert_eq!(points.get_completed(), 990);
+        assert_eq!(points.get_operations(), 20);
+        assert_eq!(points.get_timed_out(), 10);
+        assert_eq!(points.get_queries_time_ms(), 80);
+        assert_eq!(points.get_latency().get_average(), 4.0);
+        assert_eq!(points.get_latency().get_p50(), Some(5));
+        
+        let select = &phases[2];
+        assert_eq!(select.get_phase(), "select");
+        assert_eq!(select.get_completed(), 1);
+        assert_eq!(select.get_operations(), 1);
+        assert_eq!(select.get_latency().get_max(), Some(2));
+    }
+    
+    #[test]
+    fn log_file_name_holds_the_database_and_test_case() {
+        assert_eq!(parse_file_name("logs/InfluxDB_log7.txt"), (String::from("InfluxDB"), 7));
+        assert_eq!(parse_file_name("MongoDB_log12.txt"), (String::from("MongoDB"), 12));
+        assert_eq!(parse_file_name("influx_only_bench.txt"), (String::from("InfluxDB"), 0));
+        assert_eq!(parse_file_name("rethink_only_bench.txt"), (String::from("RethinkDB"), 0));
+    }
+    
+    #[test]
+    #[should_panic(expected = "Invalid log file name")]
+    fn log_file_name_without_test_case_is_rejected() {
+        parse_file_name("results.txt");
+    }
+}
@@ -1,61 +1,352 @@
-mod influxdb_bench;
-mod mongodb_bench;
-mod rethinkdb_bench;
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+mod bench_rng;
+mod changes_bench;
+mod checkpoint;
+mod compare;
+mod fault_executor;
+mod log_import;
+mod progress;
+mod query_executor;
+mod report;
+mod results;
+mod scenario;
+mod scheduler;
+mod statistics;
+mod test_matrix;
+mod timed_executor;
 mod db;
 
 
+extern crate ctrlc;
 extern crate influent;
 #[macro_use(bson, doc)]
 extern crate mongodb;
 extern crate rand;
+#[macro_use]
 extern crate serde_json;
 extern crate sha2;
 extern crate tokio_core; 
 
 
-use influxdb_bench::ExecutorInflux;
-use mongodb_bench::ExecutorMongo;
-use rethinkdb_bench::ExecutorRethink;
+use changes_bench::start_changes_benchmark;
+use bench_rng::BenchRng;
+use checkpoint::{Checkpoint, Checkpointer, Phase, PhaseStats};
+use compare::compare;
+use fault_executor::{FaultConfig, FaultCounts, FaultExecutor};
+use db::db_raw::query_db::{Condition, DBError, DBValue, MemoryConfig, Order, PoolConfig, ReconnectEvent, Request};
+use db::db_raw::query_db::influx_mock::InfluxMock;
+use log_import::import_logs;
+use progress::Progress;
+use query_executor::QueryExecutor;
+use report::write_report;
+use results::{ReportIntervals, Results, ResultsWriter, RunResult, TestCaseResult};
+use scenario::ScenarioPhase;
+use scheduler::{PointScheduler, ScheduleOrder};
 use sha2::{Sha256, Digest};
 use serde_json::Value;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fmt::Arguments;
-use std::fs::File;
+use std::fs;
+use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use rand::Rng;
+use timed_executor::{OperationResult, TimedExecutor};
 
 
 static CONFIG_FILE_PATH: &str = "config.json";
+static USAGE: &str = "Usage: influx_bench [resume]
+       influx_bench dry-run
+       influx_bench mock-influx
+       influx_bench import-logs <results file> <log files>...
+       influx_bench compare [--threshold <percent>] <results files>...
+       influx_bench report <html file> <results files>...";
+static DEFAULT_REGRESSION_THRESHOLD: f64 = 10.0;
 static INFLUX_TEST_CASES_FILE_PATH: &str = "influx_testcases.json";
 static MONGO_TEST_CASES_FILE_PATH: &str = "mongo_testcases.json";
 static RETHINK_TEST_CASES_FILE_PATH: &str = "rethink_testcases.json";
+static MEMORY_TEST_CASES_FILE_PATH: &str = "memory_testcases.json";
+static DRY_RUN_SUFFIX: &str = "_dry_run";
+static MOCK_SUFFIX: &str = "_mock";
+static COMBINED_RESULTS_FILE_PATH: &str = "combined_results.json";
+static DEFAULT_SAMPLE_SIZE: usize = 1000;
+static DEFAULT_QUERIES: usize = 1000;
+static DEFAULT_SUBSCRIBERS: usize = 10;
+static DEFAULT_TOPICS: usize = 10;
+static DEFAULT_EVENTS: usize = 1000;
+static DEFAULT_CHANGES_TIMEOUT_MS: u64 = 10000;
+static DEFAULT_POOL_SIZE: usize = 1;
+static DEFAULT_HEALTH_CHECK_INTERVAL_MS: u64 = 10000;
+static DEFAULT_RECONNECT_ATTEMPTS: u32 = 10;
+static DEFAULT_RECONNECT_BACKOFF_MS: u64 = 100;
+static DEFAULT_RECONNECT_MAX_BACKOFF_MS: u64 = 30000;
+static DEFAULT_CHECKPOINT_INTERVAL_MS: u64 = 60000;
+static DEFAULT_POINT_INTERVAL_MS: u64 = 60000;
+static DEFAULT_PROGRESS_INTERVAL_MS: u64 = 1000;
+static INSERT_LOG_INTERVAL_MS: u64 = 1000;
+static DEFAULT_REPORT_INTERVAL_MS: u64 = 10000;
+static DEFAULT_VARIATION_THRESHOLD: f64 = 10.0;
+static DEFAULT_INFLUX_MOCK_ROWS: usize = 10;
+/// A range query reads this fraction of the points of a series
+static RANGE_FRACTION: usize = 10;
+static CANCELLED: AtomicBool = AtomicBool::new(false);
 
+/// The timed operations return their time in milliseconds.
 pub trait Executor {
-    fn insert(&mut self, hash: String, random_number: usize) -> i32;
-    fn select(&mut self, hash: &String) -> i32;
+    /// Writes the points with one request.
+    fn insert(&mut self, points: Vec<Point>) -> Result<i32, DBError>;
+    fn select(&mut self, hash: &str, query: &Query) -> Result<i32, DBError>;
+    /// Deletes the points of the series `hash` older than `before` in milliseconds.
+    fn delete(&mut self, hash: &str, before: i64) -> Result<i32, DBError>;
+    /// Compacts the storage of the points.
+    fn compact(&mut self) -> Result<i32, DBError>;
+    /// Time of the newest point of the series `hash` the database returns.
+    fn get_last_timestamp(&mut self, hash: &str) -> Result<Option<i64>, DBError>;
+    /// Discovers up to `sample_size` distinct tags that are already stored in the database.
+    fn get_hashes(&mut self, sample_size: usize) -> Result<Vec<String>, DBError>;
+    /// Returns the reconnects to the database since the last call.
+    fn take_reconnect_events(&mut self) -> Vec<ReconnectEvent>;
+    /// Seeds the faults that a `FaultExecutor` injects.
+    fn set_seed(&mut self, _seed: u64) {}
+    /// Returns the injected faults since the last call, only a `FaultExecutor` injects them.
+    fn take_fault_counts(&mut self) -> FaultCounts {
+        return FaultCounts::default()
+    }
+}
+
+/// Point of the series `hash`, stamped with `timestamp` in milliseconds.
+#[derive(Clone, Debug)]
+pub struct Point {
+    hash: String,
+    random_number: usize,
+    timestamp: i64
+}
+
+impl Point {
+    pub fn new(hash: String, random_number: usize, timestamp: i64) -> Point {
+        return Point {
+            hash: hash,
+            random_number: random_number,
+            timestamp: timestamp
+        }
+    }
+    
+    pub fn get_hash(&self) -> &str {
+        return &self.hash
+    }
+    
+    pub fn get_random_number(&self) -> usize {
+        return self.random_number
+    }
+    
+    pub fn get_timestamp(&self) -> i64 {
+        return self.timestamp
+    }
+}
+
+/// Points of a series that a select reads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Query {
+    /// All points
+    Series,
+    /// The newest point
+    Last,
+    /// The points from the first timestamp inclusive to the second one exclusive
+    Range(i64, i64)
+}
+
+impl Query {
+    /// Request of the points of `table` whose `tag` is `hash`, the time of a point
+    /// is in `time_field`.
+    pub fn to_request(&self, table: &str, tag: &str, time_field: &str, hash: &str) -> Request {
+        let mut condition = HashMap::new();
+        condition.insert(String::from(tag), Condition::Eq(DBValue::from(hash)));
+        if let Query::Range(from, to) = *self {
+            condition.insert(String::from(time_field), Condition::Range(DBValue::Timestamp(from), DBValue::Timestamp(to)));
+        }
+        let request = Request::from_condition(String::from(table), condition);
+        
+        return match *self {
+            Query::Last => request.with_order(time_field, Order::Desc).with_limit(1),
+            _ => request
+        }
+    }
+}
+
+/// Query of the select phase of a test case.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryType {
+    /// All points of a series
+    Series,
+    /// The newest point of a series
+    Last,
+    /// The points of a random tenth of the time of a series
+    Range
+}
+
+impl QueryType {
+    pub fn as_str(&self) -> &'static str {
+        return match *self {
+            QueryType::Series => "series",
+            QueryType::Last => "last",
+            QueryType::Range => "range"
+        }
+    }
+    
+    pub fn parse(query_type: &str) -> Option<QueryType> {
+        return match query_type {
+            "series" => Some(QueryType::Series),
+            "last" => Some(QueryType::Last),
+            "range" => Some(QueryType::Range),
+            _ => None
+        }
+    }
+}
+
+/// Timeouts in milliseconds, `None` waits forever.
+#[derive(Clone, Copy)]
+pub struct Timeouts {
+    operation: Option<u64>,
+    insert_phase: Option<u64>,
+    select_phase: Option<u64>
+}
+
+impl Timeouts {
+    pub fn new(operation: Option<u64>, insert_phase: Option<u64>, select_phase: Option<u64>) -> Timeouts {
+        return Timeouts {
+            operation: operation,
+            insert_phase: insert_phase,
+            select_phase: select_phase
+        }
+    }
+    
+    pub fn get_operation(&self) -> Option<Duration> {
+        return self.operation.map(Duration::from_millis)
+    }
+    
+    pub fn get_insert_phase(&self) -> Option<Duration> {
+        return self.insert_phase.map(Duration::from_millis)
+    }
+    
+    pub fn get_select_phase(&self) -> Option<Duration> {
+        return self.select_phase.map(Duration::from_millis)
+    }
+}
+
+/// Order in which the points of the series are written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IngestOrder {
+    /// All points of a series, then the next series, like an import of history
+    Backfill,
+    /// The point at the same time of every series, then the next time, like live collection
+    Live,
+    Random
+}
+
+impl IngestOrder {
+    fn get_schedule_order(&self) -> ScheduleOrder {
+        return match *self {
+            IngestOrder::Backfill => ScheduleOrder::SeriesBySeries,
+            IngestOrder::Live => ScheduleOrder::RoundRobin,
+            IngestOrder::Random => ScheduleOrder::Random
+        }
+    }
 }
 
 pub struct TestCase {
     id: usize,
+    name: String,
     series: usize,
     points_per_series: usize,
-    queries: usize
+    queries: usize,
+    timeouts: Timeouts,
+    ingest_order: IngestOrder,
+    point_interval: u64,
+    repeat: usize,
+    batch_size: usize,
+    concurrency: usize,
+    query_type: QueryType,
+    /// Phases that replace the insert and select phases
+    scenario: Option<Vec<ScenarioPhase>>
 }
 
 impl TestCase {
-    pub fn new(id: usize,series: usize, points_per_series: usize, queries: usize) -> TestCase {
+    pub fn new(id: usize, series: usize, points_per_series: usize, queries: usize, timeouts: Timeouts) -> TestCase {
         return TestCase {
             id: id,
+            name: format!("test case {}", id),
             series: series,
             points_per_series: points_per_series,
-            queries: queries
+            queries: queries,
+            timeouts: timeouts,
+            ingest_order: IngestOrder::Random,
+            point_interval: DEFAULT_POINT_INTERVAL_MS,
+            repeat: 1,
+            batch_size: 1,
+            concurrency: 1,
+            query_type: QueryType::Series,
+            scenario: None
         }
     }
     
+    pub fn with_name(mut self, name: String) -> TestCase {
+        self.name = name;
+        return self
+    }
+    
+    pub fn with_ingest_order(mut self, ingest_order: IngestOrder) -> TestCase {
+        self.ingest_order = ingest_order;
+        return self
+    }
+    
+    /// Points of a series are `point_interval` milliseconds apart.
+    pub fn with_point_interval(mut self, point_interval: u64) -> TestCase {
+        self.point_interval = point_interval;
+        return self
+    }
+    
+    /// Runs the workload `repeat` times.
+    pub fn with_repeat(mut self, repeat: usize) -> TestCase {
+        self.repeat = cmp::max(repeat, 1);
+        return self
+    }
+    
+    /// Writes `batch_size` points with every insert.
+    pub fn with_batch_size(mut self, batch_size: usize) -> TestCase {
+        self.batch_size = cmp::max(batch_size, 1);
+        return self
+    }
+    
+    /// Runs `concurrency` inserts or selects at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> TestCase {
+        self.concurrency = cmp::max(concurrency, 1);
+        return self
+    }
+    
+    pub fn with_query_type(mut self, query_type: QueryType) -> TestCase {
+        self.query_type = query_type;
+        return self
+    }
+    
+    pub fn with_scenario(mut self, scenario: Vec<ScenarioPhase>) -> TestCase {
+        self.scenario = Some(scenario);
+        return self
+    }
+    
     pub fn get_id(&self) -> usize {
         return self.id
     }
     
+    pub fn get_name(&self) -> &str {
+        return &self.name
+    }
+    
     pub fn get_series(&self) -> usize {
         return self.series
     }
@@ -67,167 +358,853 @@ impl TestCase {
     pub fn get_queries(&self) -> usize {
         return self.queries
     }
+    
+    pub fn get_timeouts(&self) -> &Timeouts {
+        return &self.timeouts
+    }
+    
+    pub fn get_ingest_order(&self) -> IngestOrder {
+        return self.ingest_order
+    }
+    
+    pub fn get_point_interval(&self) -> u64 {
+        return self.point_interval
+    }
+    
+    pub fn get_repeat(&self) -> usize {
+        return self.repeat
+    }
+    
+    pub fn get_batch_size(&self) -> usize {
+        return self.batch_size
+    }
+    
+    pub fn get_concurrency(&self) -> usize {
+        return self.concurrency
+    }
+    
+    pub fn get_query_type(&self) -> QueryType {
+        return self.query_type
+    }
+    
+    pub fn get_scenario(&self) -> Option<&[ScenarioPhase]> {
+        return self.scenario.as_deref()
+    }
+    
+    /// Time of the first point of every series, the last points are written with
+    /// the start time of the run.
+    fn compute_start_timestamp(&self, run_start_time: i64) -> i64 {
+        let history_ms = self.points_per_series.saturating_sub(1) as u64*self.point_interval;
+        return run_start_time-history_ms as i64;
+    }
 }
 
 pub struct TestEnviroment<'a> {
-    executor: &'a mut Executor,
+    executor: &'a mut TimedExecutor,
     log_file: File,
+    checkpointer: Option<&'a mut Checkpointer>,
+    results: &'a mut ResultsWriter,
+    progress_interval: Option<Duration>,
+    report_intervals: ReportIntervals
+}
+
+impl<'a> TestEnviroment<'a> {
+    fn save_checkpoint(&mut self, state: &Checkpoint) {
+        if let Some(ref mut checkpointer) = self.checkpointer {
+            checkpointer.save(state);
+        }
+    }
+    
+    fn save_checkpoint_if_due(&mut self, state: &Checkpoint) {
+        if let Some(ref mut checkpointer) = self.checkpointer {
+            checkpointer.save_if_due(state);
+        }
+    }
+    
+    fn is_checkpoint_due(&self) -> bool {
+        return self.checkpointer.as_ref().is_some_and(|checkpointer| checkpointer.is_due())
+    }
 }
 
-enum DatabaseType {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DatabaseType {
     Influxdb = 1,
     Mongodb = 2,
     Rethinkdb = 3,
-    InfluxOnlyBench = 4,
+    /// In-memory database of the process, for runs without a server
+    Memory = 5
+}
+
+impl DatabaseType {
+    fn get_name(&self) -> &'static str {
+        return match *self {
+            DatabaseType::Influxdb => "InfluxDB",
+            DatabaseType::Mongodb => "MongoDB",
+            DatabaseType::Rethinkdb => "RethinkDB",
+            DatabaseType::Memory => "Memory"
+        }
+    }
+    
+    fn get_test_case_file_path(&self) -> &'static str {
+        return match *self {
+            DatabaseType::Influxdb => INFLUX_TEST_CASES_FILE_PATH,
+            DatabaseType::Mongodb => MONGO_TEST_CASES_FILE_PATH,
+            DatabaseType::Rethinkdb => RETHINK_TEST_CASES_FILE_PATH,
+            DatabaseType::Memory => MEMORY_TEST_CASES_FILE_PATH
+        }
+    }
+}
+
+/// A database the benchmark runs against, its name prefixes the log, checkpoint
+/// and results files.
+#[derive(Clone, Debug)]
+pub struct Backend {
+    name: String,
+    database_type: DatabaseType,
+    host: Option<&'static str>
+}
+
+impl Backend {
+    fn get_name(&self) -> &str {
+        return &self.name
+    }
+    
+    fn get_database_type(&self) -> DatabaseType {
+        return self.database_type
+    }
+    
+    fn get_host(&self) -> Option<&'static str> {
+        return self.host
+    }
+}
+
+/// Seed and start time the data of every test case is derived from, so backends
+/// that run the same workload store the same points.
+#[derive(Clone, Copy, Debug)]
+pub struct Workload {
+    seed: u64,
+    start_time: u128
+}
+
+impl Workload {
+    fn new(seed: u64, start_time: u128) -> Workload {
+        return Workload {
+            seed: seed,
+            start_time: start_time
+        }
+    }
+    
+    /// The series tags derive from the seed plus the series position, so the seeds
+    /// of the test cases are scrambled to keep their series apart.
+    fn get_test_case_seed(&self, test_case_id: usize) -> u64 {
+        return BenchRng::new(self.seed.wrapping_add(test_case_id as u64)).gen::<u64>()
+    }
+}
+
+#[derive(Clone)]
+pub struct Config {
+    backends: Vec<Backend>,
+    concurrent_backends: bool,
+    test_case_file: Option<String>,
+    only_bench: bool,
+    sample_size: usize,
+    queries: usize,
+    changes_bench: bool,
+    subscribers: usize,
+    topics: usize,
+    events: usize,
+    publish_interval: u64,
+    changes_timeout: u64,
+    pool_config: PoolConfig,
+    faults: Option<FaultConfig>,
+    timeouts: Timeouts,
+    checkpoint_interval: u64,
+    progress_interval: Option<u64>,
+    report_intervals: ReportIntervals,
+    variation_threshold: f64,
+    influx_mock_latency: u64,
+    influx_mock_rows: usize
+}
+
+impl Config {
+    fn get_backends(&self) -> &[Backend] {
+        return &self.backends
+    }
+    
+    /// Whether several backends run at once instead of one after another.
+    fn is_concurrent_backends(&self) -> bool {
+        return self.concurrent_backends
+    }
+    
+    /// Every backend of a run with several backends gets the test cases of the first one.
+    fn get_test_case_file_path(&self, database_type: DatabaseType) -> &str {
+        return match self.test_case_file {
+            Some(ref test_case_file) => test_case_file,
+            None if self.backends.len() > 1 => self.backends[0].get_database_type().get_test_case_file_path(),
+            None => database_type.get_test_case_file_path()
+        }
+    }
+    
+    /// Config with the connections to `backend`.
+    fn for_backend(&self, backend: &Backend) -> Config {
+        let mut config = self.clone();
+        config.pool_config = self.pool_config.with_host(backend.get_host());
+        return config
+    }
+    
+    /// Config that runs the test cases of every backend against its own in-memory
+    /// database, the backends get the `_dry_run` suffix to keep their files apart.
+    fn into_dry_run(mut self) -> Config {
+        if self.test_case_file.is_none() {
+            let test_case_file_path = self.get_test_case_file_path(self.backends[0].get_database_type());
+            self.test_case_file = Some(test_case_file_path.to_string());
+        }
+        for backend in self.backends.iter_mut() {
+            backend.name = format!("{}{}", backend.name, DRY_RUN_SUFFIX);
+            backend.database_type = DatabaseType::Memory;
+            backend.host = Some(Box::leak(backend.name.clone().into_boxed_str()));
+        }
+        return self
+    }
+    
+    /// Config that runs the test cases of the InfluxDB backends against the mock at
+    /// `host`, the backends get the `_mock` suffix to keep their files apart.
+    fn into_influx_mock(mut self, host: &'static str) -> Config {
+        if self.test_case_file.is_none() {
+            let test_case_file_path = self.get_test_case_file_path(self.backends[0].get_database_type());
+            self.test_case_file = Some(test_case_file_path.to_string());
+        }
+        self.backends.retain(|backend| backend.get_database_type() == DatabaseType::Influxdb);
+        if self.backends.is_empty() {
+            panic!("The influx mock needs an InfluxDB backend");
+        }
+        for backend in self.backends.iter_mut() {
+            backend.name = format!("{}{}", backend.name, MOCK_SUFFIX);
+            backend.host = Some(host);
+        }
+        return self
+    }
+    
+    fn without_progress(mut self) -> Config {
+        self.progress_interval = None;
+        return self
+    }
+    
+    pub fn is_only_bench(&self) -> bool {
+        return self.only_bench
+    }
+    
+    pub fn get_sample_size(&self) -> usize {
+        return self.sample_size
+    }
+    
+    pub fn get_queries(&self) -> usize {
+        return self.queries
+    }
+    
+    pub fn is_changes_bench(&self) -> bool {
+        return self.changes_bench
+    }
+    
+    pub fn get_subscribers(&self) -> usize {
+        return self.subscribers
+    }
+    
+    pub fn get_topics(&self) -> usize {
+        return self.topics
+    }
+    
+    pub fn get_events(&self) -> usize {
+        return self.events
+    }
+    
+    pub fn get_publish_interval(&self) -> u64 {
+        return self.publish_interval
+    }
+    
+    pub fn get_changes_timeout(&self) -> u64 {
+        return self.changes_timeout
+    }
+    
+    pub fn get_pool_config(&self) -> PoolConfig {
+        return self.pool_config
+    }
+    
+    /// Faults injected into the operations of every backend, `None` without injection.
+    /// The changes benchmark runs without them.
+    pub fn get_faults(&self) -> Option<&FaultConfig> {
+        return self.faults.as_ref()
+    }
+    
+    /// Timeouts of the only benchmark, test cases have their own.
+    pub fn get_timeouts(&self) -> &Timeouts {
+        return &self.timeouts
+    }
+    
+    pub fn get_checkpoint_interval(&self) -> u64 {
+        return self.checkpoint_interval
+    }
+    
+    /// `None` when the progress display is off.
+    pub fn get_progress_interval(&self) -> Option<Duration> {
+        return self.progress_interval.map(Duration::from_millis)
+    }
+    
+    pub fn get_report_intervals(&self) -> ReportIntervals {
+        return self.report_intervals
+    }
+    
+    /// Coefficient of variation between repetitions in percent above which a warning is shown.
+    pub fn get_variation_threshold(&self) -> f64 {
+        return self.variation_threshold
+    }
 }
 
-/*
 fn main() {
-    match get_database_type() {
-        DatabaseType::Influxdb => {
-            let executor = &mut ExecutorInflux::new();
-            let test_cases = get_test_cases(INFLUX_TEST_CASES_FILE_PATH);
-            println!("# Start InfluxDB benchmark");
-            start_benchmark(String::from("InfluxDB"), executor, test_cases);
-        },
-        DatabaseType::Mongodb => {
-            let executor = &mut ExecutorMongo::new();
-            let test_cases = get_test_cases(MONGO_TEST_CASES_FILE_PATH);
-            println!("# Start MongoDB benchmark");
-            start_benchmark(String::from("MongoDB"), executor, test_cases);
-        },
-        DatabaseType::Rethinkdb => {
-            let executor = &mut ExecutorRethink::new();
-            let test_cases = get_test_cases(RETHINK_TEST_CASES_FILE_PATH);
-            println!("# Start RethinkDB benchmark");
-            start_benchmark(String::from("RethinkDB"), executor, test_cases);
-        },
-        DatabaseType::InfluxOnlyBench => {
-            println!("# Start InfluxBD, only benchmark");
-            let mut executor = ExecutorInflux::new();
-            let tags = executor.get_hashes();    
-            let mut log_file = File::create(format!("influx_only_bench.txt"))
-                .expect("Can't create log file");
-            let mut test_env = TestEnviroment { executor: &mut executor, log_file: log_file };
-            
-            
-            let queries = 1000;
-            write_log(&mut test_env.log_file, format_args!("# Begin select queries\n"));  
-                  
-            if tags.len() == 0 {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => start(false, false),
+        Some("resume") => start(true, false),
+        Some("dry-run") => start(false, true),
+        Some("mock-influx") => start_influx_mock(),
+        Some("import-logs") if args.len() >= 3 => import_logs(&args[1], &args[2..]),
+        Some("compare") if args.len() >= 2 => start_compare(&args[1..]),
+        Some("report") if args.len() >= 3 => write_report(&args[1], &args[2..]),
+        Some(_) => {
+            println!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+/// Exits with status 1 when a run regressed past the threshold.
+/// The arguments are checked before any results file is loaded.
+fn start_compare(args: &[String]) {
+    let (threshold, result_file_paths) = if args[0] == "--threshold" {
+        (args.get(1).and_then(|threshold| threshold.parse::<f64>().ok()), args.get(2..).unwrap_or_default())
+    } else {
+        (Some(DEFAULT_REGRESSION_THRESHOLD), args)
+    };
+    let threshold = match threshold {
+        Some(threshold) if threshold.is_finite() && threshold >= 0.0 => threshold,
+        _ => {
+            println!("The threshold is a percent of 0 or more\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if result_file_paths.is_empty() {
+        println!("{}", USAGE);
+        process::exit(2);
+    }
+    if compare(threshold, result_file_paths) {
+        process::exit(1);
+    }
+}
+
+/// `is_resume` continues the test cases from the last checkpoint of every backend.
+/// `is_dry_run` runs them against in-memory databases instead, which keep nothing
+/// to resume from.
+fn start(is_resume: bool, is_dry_run: bool) {
+    let config = get_config();
+    let config = if is_dry_run { config.into_dry_run() } else { config };
+    run(config, is_resume);
+}
+
+/// Runs the InfluxDB backends against an embedded mock of the HTTP API that
+/// answers with canned points, so with no mock latency the times are the overhead
+/// of `QueryExecutor` and the InfluxDB client alone.
+fn start_influx_mock() {
+    let config = get_config();
+    let mock = InfluxMock::start(config.influx_mock_latency)
+        .without_recording()
+        .with_executor_responses(config.influx_mock_rows);
+    run(config.into_influx_mock(mock.get_host()), false);
+    
+    let counts = mock.get_counts();
+    println!("# InfluxDB mock answered {} writes of {} points and {} queries", counts.writes, counts.lines, counts.queries);
+}
+
+fn run(config: Config, is_resume: bool) {
+    if !config.is_changes_bench() {
+        install_cancel_handler();
+    }
+    let backends = config.get_backends();
+    let resumed_workload = if is_resume { find_resumed_workload(backends) } else { None };
+    let workload = resumed_workload.unwrap_or_else(|| Workload::new(get_current_time() as u64, get_current_time()));
+    if backends.len() == 1 {
+        run_backend(&backends[0], &config.for_backend(&backends[0]), workload, is_resume);
+        return
+    }
+    
+    let names: Vec<&str> = backends.iter().map(Backend::get_name).collect();
+    println!("# Start benchmark of {}, seed: {}", names.join(", "), workload.seed);
+    if config.is_concurrent_backends() {
+        // The progress lines of the backends would overwrite each other
+        thread::scope(|scope| {
+            for backend in backends {
+                let backend_config = config.for_backend(backend).without_progress();
+                scope.spawn(move || run_backend(backend, &backend_config, workload, is_resume));
+            }
+        });
+    } else {
+        for backend in backends {
+            if is_cancelled() {
+                break
+            }
+            run_backend(backend, &config.for_backend(backend), workload, is_resume);
+        }
+    }
+    
+    if !config.is_changes_bench() && !config.is_only_bench() {
+        combine_results(backends, workload);
+    }
+}
+
+/// Runs the benchmark of the config against one backend.
+fn run_backend(backend: &Backend, config: &Config, workload: Workload, is_resume: bool) {
+    let db_name = backend.get_name().to_string();
+    let test_case_file_path = config.get_test_case_file_path(backend.get_database_type());
+    let new_executor = QueryExecutor::get_constructor(backend.get_database_type());
+    if config.is_changes_bench() {
+        println!("# Start {} changes benchmark", db_name);
+        start_changes_benchmark(db_name, new_executor, config);
+    } else {
+        let executor = &mut match new_timed_executor(new_executor, config) {
+            Ok(executor) => executor,
+            Err(err) => {
+                println!("# Failed to connect to {}: {:?}", db_name, err);
                 return
-            } 
-            
-            let mut rng = rand::thread_rng();
-            let max_tag_pos = tags.len()-1;
-            let mut queries_time_ms: i32 = 0;
-            
-            for i in 0..queries {
-                let random_number = if max_tag_pos > 0 { rng.gen_range(0, max_tag_pos) } else { 0 };
-                let hash = tags.get(random_number).unwrap();
-                
-                let query_time = test_env.executor.select(hash);
-                
-                queries_time_ms += query_time;
-                let average_time: i32 = queries_time_ms/(i+1) as i32;
-                write_log(&mut test_env.log_file, 
-                    format_args!("Select query №{} average time: {} ms, query time: {} ms\n", 
-                    i, 
-                    average_time, 
-                    query_time));
             }
-            write_log(&mut test_env.log_file,
-                format_args!("# Select {} queries for {} ms\n", 
-                queries, 
-                queries_time_ms));
-        },   
+        };
+        println!("# Start {} benchmark", db_name);
+        run_benchmark(db_name, executor, test_case_file_path, config, workload, is_resume);
     }
 }
-*/
 
-fn main() {
-    ExecutorRethink::new().start_listeners();
+/// Executor of the backend, wrapped to inject the faults of the config. The fault
+/// windows start with the benchmark of the backend.
+fn new_timed_executor<E>(new_executor: fn(PoolConfig) -> Result<E, DBError>, config: &Config) -> Result<TimedExecutor, DBError>
+    where E: Executor + 'static {
+    
+    return match config.get_faults() {
+        Some(faults) => {
+            let faults = faults.starting_at(get_current_time());
+            TimedExecutor::new(move |pool_config| FaultExecutor::new(new_executor, pool_config, faults.clone()), config.get_pool_config())
+        },
+        None => TimedExecutor::new(new_executor, config.get_pool_config())
+    }
 }
 
-pub fn start_benchmark(db_name: String, executor: &mut Executor, test_cases: Vec<TestCase>) {
-    for test_case in test_cases {
-        println!("Begin test case: {}", test_case.get_id());
-        let mut log_file = File::create(format!("{}_log{}.txt", db_name, test_case.get_id()))
-            .expect("Can't create log file");
-        let mut test_env = TestEnviroment { executor, log_file };
-        
-        let tags = insert_points(&mut test_env, test_case.get_series(), test_case.get_points_per_series());
-        start_testcase(&mut test_env, tags.clone(), test_case.get_queries(), test_case.get_points_per_series());
+fn run_benchmark(db_name: String, executor: &mut TimedExecutor, test_case_file_path: &str, config: &Config, workload: Workload, is_resume: bool) {
+    if config.is_only_bench() {
+        if is_resume {
+            println!("# Only benchmark has no checkpoints, starting it again");
+        }
+        start_only_benchmark(db_name, executor, config);
+        return
     }
+    
+    let test_cases = get_test_cases(test_case_file_path);
+    let checkpointer = Checkpointer::new(get_checkpoint_file_path(&db_name), config.get_checkpoint_interval());
+    let checkpoint = if is_resume && checkpointer.exists() { Some(checkpointer.load()) } else { None };
+    if is_resume && checkpoint.is_none() {
+        if find_run(&db_name, workload).is_some() {
+            println!("# {} has finished the resumed run", db_name);
+            return
+        }
+        println!("# {} has no checkpoint, starting its test cases again", db_name);
+    }
+    start_benchmark(db_name, executor, test_cases, checkpointer, checkpoint, workload, config);
 }
 
-fn insert_points(test_env: &mut TestEnviroment,
-    series: usize, 
-    points_per_series: usize) -> Vec<String> {
-        
-    if series == 0 {
-        return Vec::new();
+fn get_checkpoint_file_path(db_name: &str) -> String {
+    return format!("{}_checkpoint.json", db_name)
+}
+
+fn get_results_file_path(db_name: &str) -> String {
+    return format!("{}_results.json", db_name)
+}
+
+/// Returns the run of `db_name` with the seed of `workload` from its results file.
+fn find_run(db_name: &str, workload: Workload) -> Option<RunResult> {
+    let results_file_path = get_results_file_path(db_name);
+    if fs::metadata(&results_file_path).is_err() {
+        return None
+    }
+    return Results::load(&results_file_path).get_runs().iter()
+        .rev()
+        .find(|run| run.get_database() == db_name && run.get_seed() == Some(workload.seed))
+        .cloned()
+}
+
+/// Workload of the interrupted run, taken from the results of a backend that left
+/// a checkpoint.
+fn find_resumed_workload(backends: &[Backend]) -> Option<Workload> {
+    for backend in backends {
+        let results_file_path = get_results_file_path(backend.get_name());
+        if fs::metadata(get_checkpoint_file_path(backend.get_name())).is_err() || fs::metadata(&results_file_path).is_err() {
+            continue
+        }
+        let results = Results::load(&results_file_path);
+        if let Some(run) = results.get_runs().iter().rev().find(|run| run.get_database() == backend.get_name()) {
+            if let Some(seed) = run.get_seed() {
+                return Some(Workload::new(seed, run.get_start_time()))
+            }
+        }
+    }
+    return None
+}
+
+/// Writes the runs of the backends with the same workload to one results file.
+fn combine_results(backends: &[Backend], workload: Workload) {
+    let mut combined = Results::new();
+    for backend in backends {
+        match find_run(backend.get_name(), workload) {
+            Some(run) => combined.add_run(run),
+            None => println!("# {} has no results of the run", backend.get_name())
+        }
     }
-    write_log(&mut test_env.log_file, format_args!("# Begin insert series\n"));   
+    combined.save(COMBINED_RESULTS_FILE_PATH);
+    println!("# Results of {} backends are written to {}", combined.get_runs().len(), COMBINED_RESULTS_FILE_PATH);
+}
+
+/// Runs the select workload against data that is already stored in the database.
+pub fn start_only_benchmark(db_name: String, executor: &mut TimedExecutor, config: &Config) {
+    let sample_size = config.get_sample_size();
+    let timeouts = config.get_timeouts();
+    println!("Begin only benchmark, sample size: {}", sample_size);
+    let log_file = File::create(format!("{}_only_bench.txt", db_name))
+        .expect("Can't create log file");
+    let results = &mut ResultsWriter::new(format!("{}_only_bench_results.json", db_name), db_name, get_current_time(), false);
+    let mut test_env = TestEnviroment {
+        executor: executor,
+        log_file: log_file,
+        checkpointer: None,
+        results: results,
+        progress_interval: config.get_progress_interval(),
+        report_intervals: config.get_report_intervals()
+    };
+    test_env.executor.set_timeout(timeouts.get_operation());
     
-    let mut rng = rand::thread_rng();
-    let data_getter = || {
-        let random_number = rng.gen::<usize>();
-        return (generate_hash_from_number(&random_number), random_number);
+    let mut state = Checkpoint::new(0, 0, get_current_time() as u64, get_current_time() as i64);
+    state.start_phase(Phase::Select);
+    test_env.executor.set_seed(state.seed);
+    let tags = match test_env.executor.get_hashes(sample_size) {
+        Ok(tags) => tags,
+        Err(err) => {
+            fail_only_benchmark(&mut test_env, &mut state, config.get_queries(), &format!("failed to get the tags: {:?}", err));
+            return
+        }
     };
+    if tags.is_empty() {
+        fail_only_benchmark(&mut test_env, &mut state, config.get_queries(), "the database has no tags to select");
+        return
+    }
+    write_log(&mut test_env.log_file, format_args!("# Begin select queries\n"));
     
-    let tags = insert_records(test_env, true, series, data_getter);
+    select_records(&mut test_env, &mut state, config.get_queries(), timeouts.get_select_phase(), |state| {
+        return (tags[state.rng.gen_range(0, tags.len())].clone(), Query::Series);
+    });
+    write_log(&mut test_env.log_file,
+        format_args!("# Select {} queries for {} ms\n", 
+        state.stats.completed, 
+        state.stats.queries_time_ms));
+}
+
+/// Writes a select phase whose `queries` all failed, with the reason in the log.
+fn fail_only_benchmark(test_env: &mut TestEnviroment, state: &mut Checkpoint, queries: usize, reason: &str) {
+    println!("# Only benchmark failed, {}", reason);
+    write_log(&mut test_env.log_file, format_args!("# Only benchmark failed, {}\n", reason));
+    state.recorder.start(test_env.report_intervals);
+    state.recorder.record_failed(queries);
+    state.stats.failed = queries;
+    finish_phase(test_env, state, queries, None);
+}
+
+/// Continues from `checkpoint` when it is given, the test cases before it are skipped.
+pub fn start_benchmark(db_name: String,
+    executor: &mut TimedExecutor,
+    test_cases: Vec<TestCase>,
+    mut checkpointer: Checkpointer,
+    mut checkpoint: Option<Checkpoint>,
+    workload: Workload,
+    config: &Config) {
     
-    if points_per_series == 0 {
-        return tags
-    } 
-    write_log(&mut test_env.log_file, format_args!("# Begin insert points\n"));   
-    
-    let points_per_series = points_per_series-1;
-    let points_count = series*points_per_series;
-    let mut max_tag_pos = series;
-    let mut rng = rand::thread_rng();
-    let mut tags_series = tags.clone();
-    let mut points: Vec<usize> = vec![0; series];
-    let data_getter = || {
-        let random_number = if max_tag_pos > 0 { rng.gen_range(0, max_tag_pos) } else { 0 };
-        let hash = tags_series.get(random_number).unwrap().to_string();
-        points[random_number] += 1;
-        if points[random_number] == points_per_series {
-            points.remove(random_number);
-            tags_series.remove(random_number);
-            max_tag_pos -= 1;
-        }
-        return (hash, random_number);
+    let mut results = ResultsWriter::new(get_results_file_path(&db_name), db_name.clone(), workload.start_time, checkpoint.is_some());
+    results.init_seed(workload.seed);
+    // A resumed run keeps the workload it started with
+    let workload = Workload::new(results.get_run().get_seed().unwrap_or(workload.seed), results.get_run().get_start_time());
+    let start_timestamp = workload.start_time as i64;
+    for test_case in test_cases {
+        if is_cancelled() {
+            break
+        }
+        let mut state = match checkpoint {
+            Some(ref resumed) if resumed.test_case_id != test_case.get_id() => continue,
+            Some(_) => checkpoint.take().unwrap(),
+            None => Checkpoint::new(test_case.get_id(), 0, workload.get_test_case_seed(test_case.get_id()), test_case.compute_start_timestamp(start_timestamp))
+        };
+        
+        // Every repetition writes new series with the next seed
+        loop {
+            if state.phase != Phase::Done {
+                run_repetition(&db_name, executor, &mut checkpointer, &mut results, &mut state, &test_case, config);
+            }
+            if is_cancelled() || state.repetition+1 >= test_case.get_repeat() {
+                break
+            }
+            let seed = state.rng.gen::<u64>();
+            state = Checkpoint::new(test_case.get_id(), state.repetition+1, seed, test_case.compute_start_timestamp(start_timestamp));
+        }
+        
+        if !is_cancelled() && test_case.get_repeat() > 1 {
+            if let Some(test_case_result) = results.get_test_case(test_case.get_id()) {
+                let log_file = &mut open_log_file(format!("{}_log{}.txt", db_name, test_case.get_id()), true);
+                write_statistics(log_file, test_case_result, config.get_variation_threshold());
+            }
+        }
+    }
+    
+    if let Some(resumed) = checkpoint {
+        println!("# Test case {} of the checkpoint is not found", resumed.test_case_id);
+    } else if !is_cancelled() {
+        checkpointer.remove();
+    }
+}
+
+fn run_repetition(db_name: &str,
+    executor: &mut TimedExecutor,
+    checkpointer: &mut Checkpointer,
+    results: &mut ResultsWriter,
+    state: &mut Checkpoint,
+    test_case: &TestCase,
+    config: &Config) {
+    
+    println!("Begin test case: {} ({}), ingest order: {:?}, batch size: {}, concurrency: {}, query type: {}",
+        test_case.get_id(),
+        test_case.get_name(),
+        test_case.get_ingest_order(),
+        test_case.get_batch_size(),
+        test_case.get_concurrency(),
+        test_case.get_query_type().as_str());
+    let is_append = state.is_started() || state.repetition > 0;
+    let log_file = open_log_file(format!("{}_log{}.txt", db_name, test_case.get_id()), is_append);
+    let mut test_env = TestEnviroment {
+        executor: executor,
+        log_file: log_file,
+        checkpointer: Some(checkpointer),
+        results: results,
+        progress_interval: config.get_progress_interval(),
+        report_intervals: config.get_report_intervals()
     };
-    insert_records(test_env, false, points_count, data_getter);
-    return tags;
+    test_env.executor.set_timeout(test_case.get_timeouts().get_operation());
+    test_env.executor.set_concurrency(test_case.get_concurrency());
+    test_env.executor.set_seed(state.seed);
+    if test_case.get_repeat() > 1 && !state.is_started() {
+        println!("Repetition {} of {}", state.repetition+1, test_case.get_repeat());
+        write_log(&mut test_env.log_file, format_args!("# Begin repetition {} of {}\n", state.repetition+1, test_case.get_repeat()));
+    }
+    if state.is_started() && test_case.get_scenario().is_some() {
+        write_log(&mut test_env.log_file, format_args!("# Resume scenario from phase {}\n", state.step+1));
+    } else if state.is_started() {
+        write_log(&mut test_env.log_file,
+            format_args!("# Resume {} phase after {} queries\n",
+            state.get_phase_name(),
+            state.stats.get_attempted()));
+    }
+    
+    match test_case.get_scenario() {
+        Some(phases) => scenario::run_scenario(&mut test_env, state, test_case, phases),
+        None => {
+            insert_points(&mut test_env, state, test_case);
+            if state.phase == Phase::Select {
+                start_testcase(&mut test_env, state, test_case);
+            }
+        }
+    }
+    
+    if !is_cancelled() {
+        state.start_phase(Phase::Done);
+    }
+    test_env.save_checkpoint(state);
 }
 
+/// Shows the mean and the 95% confidence interval of every phase over the
+/// repetitions, with a warning when a value varies more than `variation_threshold`
+/// percent between them.
+fn write_statistics(log_file: &mut File, test_case_result: &TestCaseResult, variation_threshold: f64) {
+    for statistics in test_case_result.get_statistics() {
+        let metrics: Vec<String> = statistics.get_metrics().iter()
+            .filter(|&&(_, _, estimate)| estimate.get_count() > 0)
+            .map(|&(name, unit, estimate)| format!("{}: {:.1} ± {:.1} {}", name, estimate.get_mean(), estimate.get_margin(), unit))
+            .collect();
+        let line = format!("# Test case {} {} phase over {} repetitions, {}\n",
+            test_case_result.get_id(),
+            statistics.get_phase(),
+            statistics.get_repetitions(),
+            metrics.join(", "));
+        print!("{}", line);
+        write_log(log_file, format_args!("{}", line));
+        
+        for &(name, unit, estimate) in statistics.get_metrics().iter() {
+            if estimate.get_variation() <= variation_threshold {
+                continue
+            }
+            if let Some((repetition, value)) = estimate.get_outlier() {
+                let line = format!("# Warning: {} of the {} phase varies by {:.1}% between repetitions, repetition {} is the farthest from the mean: {:.1} {}\n",
+                    name,
+                    statistics.get_phase(),
+                    estimate.get_variation(),
+                    repetition+1,
+                    value,
+                    unit);
+                print!("{}", line);
+                write_log(log_file, format_args!("{}", line));
+            }
+        }
+    }
+}
+
+/// Inserts the first point of every series and then the other points in the ingest
+/// order of the test case.
+fn insert_points(test_env: &mut TestEnviroment, state: &mut Checkpoint, test_case: &TestCase) {
+    let series = test_case.get_series();
+    let points_per_series = test_case.get_points_per_series();
+    let timeouts = test_case.get_timeouts();
+    let point_interval = test_case.get_point_interval() as i64;
+    let batch_size = test_case.get_batch_size();
+    let phase_start_time = Instant::now();
+    if state.phase == Phase::Series {
+        if series == 0 {
+            state.start_phase(Phase::Select);
+            return
+        }
+        write_log(&mut test_env.log_file, format_args!("# Begin insert series\n"));   
+        
+        insert_records(test_env, state, series, batch_size, phase_start_time, timeouts.get_insert_phase(), |state, pos| {
+            let (hash, random_number) = get_series_tag(state.seed, pos);
+            return Some(Point::new(hash, random_number, state.start_timestamp));
+        });
+        
+        if is_cancelled() {
+            return
+        }
+        state.series_count = state.stats.get_attempted();
+        if points_per_series == 0 || get_stop_reason(phase_start_time, timeouts.get_insert_phase()).is_some() {
+            state.start_phase(Phase::Select);
+            return
+        }
+        state.start_phase(Phase::Points);
+        let seed = state.rng.gen::<u64>();
+        let order = test_case.get_ingest_order().get_schedule_order();
+        state.scheduler = Some(PointScheduler::new(order, state.series_count, points_per_series-1, seed));
+        test_env.save_checkpoint(state);
+    }
+    
+    if state.phase == Phase::Points {
+        write_log(&mut test_env.log_file, format_args!("# Begin insert points\n"));   
+        
+        let points_count = state.scheduler.as_ref().map_or(0, PointScheduler::get_total);
+        insert_records(test_env, state, points_count, batch_size, phase_start_time, timeouts.get_insert_phase(), |state, _| {
+            let (series, point) = state.scheduler.as_mut().and_then(Iterator::next)?;
+            let (hash, _) = get_series_tag(state.seed, series);
+            // The first point of the series is written in the series phase
+            let timestamp = state.start_timestamp+(point as i64+1)*point_interval;
+            return Some(Point::new(hash, series, timestamp));
+        });
+        
+        if !is_cancelled() {
+            state.start_phase(Phase::Select);
+        }
+    }
+}
+
+/// Inserts records in batches of `batch_size` until `iteration_count` of them are
+/// attempted in this phase, counting the ones attempted before a resume.
+/// `data_getter` returns the record at a position of the phase. The batches run
+/// on every executor at once, and a checkpoint waits until none of them is running.
 fn insert_records<F>(test_env: &mut TestEnviroment, 
-    is_need_collect_tags: bool,
+    state: &mut Checkpoint,
     iteration_count: usize, 
-    mut data_getter: F) -> Vec<String> 
-    where F : FnMut() -> (String, usize)  {
-    
-    let mut tags = Vec::new();
-    let mut queries_time_ms: i32 = 0;
-    let log_frequency = iteration_count/500;
-    let mut iteration_between_log = 0;
-    for i in 0..iteration_count {
-        iteration_between_log += 1;
-        let (hash, random_number) = data_getter();
-        
-        if is_need_collect_tags {
-            tags.push(hash.clone());
+    batch_size: usize,
+    phase_start_time: Instant,
+    phase_timeout: Option<Duration>,
+    mut data_getter: F)
+    where F : FnMut(&mut Checkpoint, usize) -> Option<Point>  {
+    
+    let mut stop_reason = None;
+    let mut progress = Progress::new(state.get_phase_name(), Some(iteration_count), state.stats.get_attempted(), test_env.progress_interval);
+    state.recorder.start(test_env.report_intervals);
+    let log_interval = Duration::from_millis(INSERT_LOG_INTERVAL_MS);
+    let mut last_log_time = Instant::now();
+    // Position and size of the running batches
+    let mut batches: Vec<(usize, usize)> = Vec::new();
+    let mut position = state.stats.get_attempted();
+    let mut is_exhausted = false;
+    loop {
+        if batches.is_empty() {
+            test_env.save_checkpoint_if_due(state);
+        }
+        while stop_reason.is_none() && !is_exhausted && position < iteration_count
+            && test_env.executor.has_idle_worker() && !test_env.is_checkpoint_due() {
+            
+            stop_reason = get_stop_reason(phase_start_time, phase_timeout);
+            if stop_reason.is_some() {
+                break
+            }
+            let size = cmp::min(batch_size, iteration_count-position);
+            let mut points = Vec::with_capacity(size);
+            while points.len() < size {
+                match data_getter(state, position+points.len()) {
+                    Some(point) => points.push(point),
+                    None => {
+                        is_exhausted = true;
+                        break
+                    }
+                }
+            }
+            if points.is_empty() {
+                break
+            }
+            batches.push((position, points.len()));
+            position += points.len();
+            test_env.executor.start_insert(points, batches[batches.len()-1].0);
+        }
+        if batches.is_empty() {
+            break
         }
         
-        let query_time = test_env.executor.insert(hash, random_number);
-        queries_time_ms += query_time;
+        let (batch_position, result) = test_env.executor.wait_next();
+        let batch_pos = batches.iter().position(|batch| batch.0 == batch_position).expect("Unknown insert batch");
+        let (_, size) = batches.swap_remove(batch_pos);
+        state.recorder.record_faults(&test_env.executor.take_fault_counts());
+        let query_time = match result {
+            OperationResult::Done(query_time) => query_time,
+            OperationResult::Failed(err) => {
+                state.stats.failed += size;
+                progress.record(None, size);
+                state.recorder.record_failed(size);
+                write_reconnect_events(&mut test_env.log_file, test_env.executor.take_reconnect_events());
+                write_log(&mut test_env.log_file, format_args!("Insert query failed: {:?}\n", err));
+                continue
+            },
+            OperationResult::TimedOut => {
+                state.stats.timed_out += size;
+                progress.record(None, size);
+                state.recorder.record(None, size);
+                continue
+            }
+        };
+        state.stats.completed += size;
+        state.stats.operations += 1;
+        state.stats.queries_time_ms += query_time as i64;
+        progress.record(Some(query_time), size);
+        state.recorder.record(Some(query_time), size);
+        write_reconnect_events(&mut test_env.log_file, test_env.executor.take_reconnect_events());
         
-        if iteration_between_log == log_frequency {
-            iteration_between_log = 0;
-            let average_time: i32 = queries_time_ms/(i+1) as i32;
+        if last_log_time.elapsed() >= log_interval {
+            last_log_time = Instant::now();
+            let average_time = state.stats.queries_time_ms/state.stats.operations as i64;
             
             write_log(&mut test_env.log_file, 
                 format_args!("Insert query; average time: {} ms, query time: {} ms\n", 
@@ -235,50 +1212,190 @@ fn insert_records<F>(test_env: &mut TestEnviroment,
                 query_time));
         }
     }
+    progress.finish();
     
     write_log(&mut test_env.log_file,
-        format_args!("# Insert {} queries for {} ms\n", 
-        iteration_count, 
-        queries_time_ms));
-    
-    return tags
+        format_args!("# Insert {} queries for {} ms, {} points\n", 
+        state.stats.operations, 
+        state.stats.queries_time_ms,
+        state.stats.completed));
+    write_interruptions(&mut test_env.log_file, "Insert", state.stats, iteration_count, stop_reason);
+    finish_phase(test_env, state, iteration_count, stop_reason);
 }
 
-fn start_testcase(test_env: &mut TestEnviroment, 
-    tags: Vec<String>, 
-    queries: usize, 
-    points_per_series: usize) {
-        
+fn start_testcase(test_env: &mut TestEnviroment, state: &mut Checkpoint, test_case: &TestCase) {
     write_log(&mut test_env.log_file, format_args!("# Begin select queries\n"));  
-          
-    if tags.len() == 0 {
+    
+    if state.series_count == 0 {
         return
     } 
     
-    let mut rng = rand::thread_rng();
-    let max_tag_pos = tags.len()-1;
-    let mut queries_time_ms: i32 = 0;
+    let seed = state.seed;
+    let series_count = state.series_count;
+    let query_type = test_case.get_query_type();
+    let points_per_series = cmp::max(test_case.get_points_per_series(), 1);
+    let point_interval = test_case.get_point_interval() as i64;
+    let range_points = cmp::max(points_per_series/RANGE_FRACTION, 1);
+    select_records(test_env, state, test_case.get_queries(), test_case.get_timeouts().get_select_phase(), |state| {
+        let (hash, _) = get_series_tag(seed, state.rng.gen_range(0, series_count));
+        let query = match query_type {
+            QueryType::Series => Query::Series,
+            QueryType::Last => Query::Last,
+            QueryType::Range => {
+                let first_point = state.rng.gen_range(0, points_per_series-range_points+1);
+                let from = state.start_timestamp+first_point as i64*point_interval;
+                Query::Range(from, from+range_points as i64*point_interval)
+            }
+        };
+        return (hash, query);
+    });
+    
+    let entities = match query_type {
+        QueryType::Series => points_per_series,
+        QueryType::Last => 1,
+        QueryType::Range => range_points
+    };
+    write_log(&mut test_env.log_file,
+        format_args!("# Select {} queries for {} ms, {} entities per query\n", 
+        state.stats.completed, 
+        state.stats.queries_time_ms, 
+        entities));
+}
+
+/// Selects until `queries` queries are attempted in this phase, counting the ones
+/// attempted before a resume. `get_select` returns the tag and the query of the
+/// next select. The selects run on every executor at once, and a checkpoint waits
+/// until none of them is running.
+fn select_records<F>(test_env: &mut TestEnviroment,
+    state: &mut Checkpoint,
+    queries: usize,
+    phase_timeout: Option<Duration>,
+    mut get_select: F)
+    where F : FnMut(&mut Checkpoint) -> (String, Query) {
     
-    for i in 0..queries {
-        let random_number = if max_tag_pos > 0 { rng.gen_range(0, max_tag_pos) } else { 0 };
-        let hash = tags.get(random_number).unwrap();
+    let mut stop_reason = None;
+    let phase_start_time = Instant::now();
+    let mut progress = Progress::new(state.get_phase_name(), Some(queries), state.stats.get_attempted(), test_env.progress_interval);
+    state.recorder.start(test_env.report_intervals);
+    let mut running = 0;
+    let mut position = state.stats.get_attempted();
+    loop {
+        if running == 0 {
+            test_env.save_checkpoint_if_due(state);
+        }
+        while stop_reason.is_none() && position < queries
+            && test_env.executor.has_idle_worker() && !test_env.is_checkpoint_due() {
+            
+            stop_reason = get_stop_reason(phase_start_time, phase_timeout);
+            if stop_reason.is_some() {
+                break
+            }
+            let (hash, query) = get_select(state);
+            test_env.executor.start_select(hash, query, position);
+            position += 1;
+            running += 1;
+        }
+        if running == 0 {
+            break
+        }
         
-        let query_time = test_env.executor.select(hash);
+        let (i, result) = test_env.executor.wait_next();
+        running -= 1;
+        state.recorder.record_faults(&test_env.executor.take_fault_counts());
+        let query_time = match result {
+            OperationResult::Done(query_time) => query_time,
+            OperationResult::Failed(err) => {
+                state.stats.failed += 1;
+                progress.record(None, 1);
+                state.recorder.record_failed(1);
+                write_reconnect_events(&mut test_env.log_file, test_env.executor.take_reconnect_events());
+                write_log(&mut test_env.log_file, format_args!("Select query №{} failed: {:?}\n", i, err));
+                continue
+            },
+            OperationResult::TimedOut => {
+                state.stats.timed_out += 1;
+                progress.record(None, 1);
+                state.recorder.record(None, 1);
+                write_log(&mut test_env.log_file, format_args!("Select query №{} timed out\n", i));
+                continue
+            }
+        };
+        write_reconnect_events(&mut test_env.log_file, test_env.executor.take_reconnect_events());
         
-        queries_time_ms += query_time;
-        let average_time: i32 = queries_time_ms/(i+1) as i32;
+        state.stats.completed += 1;
+        state.stats.operations += 1;
+        state.stats.queries_time_ms += query_time as i64;
+        progress.record(Some(query_time), 1);
+        state.recorder.record(Some(query_time), 1);
+        let average_time = state.stats.queries_time_ms/state.stats.completed as i64;
         write_log(&mut test_env.log_file, 
             format_args!("Select query №{} average time: {} ms, query time: {} ms\n", 
             i, 
             average_time, 
             query_time));
     }
-    let points_per_series = if points_per_series > 0 { points_per_series } else { 1 };
-    write_log(&mut test_env.log_file,
-        format_args!("# Select {} queries for {} ms, {} entities per query\n", 
-        queries, 
-        queries_time_ms, 
-        points_per_series));
+    progress.finish();
+    
+    write_interruptions(&mut test_env.log_file, "Select", state.stats, queries, stop_reason);
+    finish_phase(test_env, state, queries, stop_reason);
+}
+
+/// Writes the result of a phase unless it was cancelled, a cancelled phase is
+/// continued from the checkpoint.
+fn finish_phase(test_env: &mut TestEnviroment, state: &mut Checkpoint, total: usize, stop_reason: Option<&str>) {
+    if stop_reason != Some("cancelled") {
+        let phase_result = state.recorder.finish(state.get_phase_name(), total as u64);
+        write_faults(&mut test_env.log_file, phase_result.get_phase(), phase_result.get_faults());
+        test_env.results.add_phase(state.test_case_id, state.repetition, phase_result);
+    }
+    test_env.save_checkpoint(state);
+}
+
+/// Ctrl-C finishes the current operation and writes the partial results, a second
+/// Ctrl-C exits immediately.
+fn install_cancel_handler() {
+    ctrlc::set_handler(|| {
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+        println!("# Cancelling benchmark, press Ctrl-C again to exit immediately");
+    }).expect("Failed to set Ctrl-C handler");
+}
+
+pub fn is_cancelled() -> bool {
+    return CANCELLED.load(Ordering::SeqCst)
+}
+
+fn get_stop_reason(phase_start_time: Instant, phase_timeout: Option<Duration>) -> Option<&'static str> {
+    if is_cancelled() {
+        return Some("cancelled")
+    }
+    if phase_timeout.is_some_and(|phase_timeout| phase_start_time.elapsed() >= phase_timeout) {
+        return Some("phase timeout")
+    }
+    return None
+}
+
+fn write_interruptions(log_file: &mut File,
+    phase: &str,
+    stats: PhaseStats,
+    total: usize,
+    stop_reason: Option<&str>) {
+    
+    if stats.timed_out > 0 {
+        write_log(log_file, format_args!("# {} queries timed out: {}\n", phase, stats.timed_out));
+    }
+    if stats.failed > 0 {
+        write_log(log_file, format_args!("# {} queries failed: {}\n", phase, stats.failed));
+    }
+    if let Some(stop_reason) = stop_reason {
+        write_log(log_file,
+            format_args!("# {} stopped after {} of {} queries, {}\n",
+            phase,
+            stats.get_attempted(),
+            total,
+            stop_reason));
+    }
 }
 
 pub fn generate_hash_from_number(id: &usize) -> String {
@@ -287,6 +1404,13 @@ pub fn generate_hash_from_number(id: &usize) -> String {
     return format!("{:x}", hasher.result())
 }
 
+/// Tags are derived from the seed and the series position, so they don't have to
+/// be kept in memory. Returns the tag and the number it is hashed from.
+pub fn get_series_tag(seed: u64, series: usize) -> (String, usize) {
+    let random_number = BenchRng::new(seed.wrapping_add(series as u64)).gen::<usize>();
+    return (generate_hash_from_number(&random_number), random_number);
+}
+
 pub fn get_current_time() -> u128 {
     let system_time = SystemTime::now().duration_since(UNIX_EPOCH)
         .expect("Time went backwards"); 
@@ -297,27 +1421,113 @@ pub fn compute_time_diff_ms(start_time: u128, end_time: u128) -> i32 {
     return (end_time-start_time) as i32;
 }
 
-pub fn parse_query(value: String) -> String {
-    let json: serde_json::Value = serde_json::from_str(&value.to_string()).expect("Invalid query json format");
-    let result = json["results"].as_array().expect("Invalid query results param");
-    let series = result[0]["series"].as_array().expect("Invalid query series param");
-    let values = series[0]["values"].as_array().expect("Invalid query values param");
-    let value  = values[0][1].as_str().expect("Invalid query value param");
-    return value[..64].to_string();
+pub fn compute_percentile(sorted_values: &[i32], percentile: f64) -> i32 {
+    if sorted_values.is_empty() {
+        return 0
+    }
+    let pos = (percentile/100.0*(sorted_values.len()-1) as f64).round() as usize;
+    return sorted_values[pos];
 }
 
-fn get_database_type() -> DatabaseType {
+fn get_config() -> Config {
     let mut file = File::open(CONFIG_FILE_PATH).expect("Can't open configuration file");
     let mut contents = String::new();
     file.read_to_string(&mut contents).expect("Can't read configuration file");
     
     let json: Value = serde_json::from_str(&contents).expect("Invalid json format");
-    return match json["database_type"].as_u64().expect("Invalid database_type params") {
+    let mut only_bench = json["only_bench"].as_bool().unwrap_or(false);
+    let backends: Vec<Backend> = match json["backends"].as_array() {
+        Some(backends) => backends.iter().map(|backend_json| get_backend(backend_json, &mut only_bench)).collect(),
+        None => vec![get_backend(&json, &mut only_bench)]
+    };
+    let mut names = HashSet::new();
+    for backend in backends.iter() {
+        if !names.insert(backend.get_name()) {
+            panic!("Duplicate backend name: {}", backend.get_name());
+        }
+    }
+    let concurrent_backends = json["concurrent_backends"].as_bool().unwrap_or(false);
+    let test_case_file = json["test_case_file"].as_str().map(String::from);
+    let sample_size = json["sample_size"].as_u64().map_or(DEFAULT_SAMPLE_SIZE, |v| v as usize);
+    let queries = json["queries"].as_u64().map_or(DEFAULT_QUERIES, |v| v as usize);
+    let changes_bench = json["changes_bench"].as_bool().unwrap_or(false);
+    let subscribers = json["subscribers"].as_u64().map_or(DEFAULT_SUBSCRIBERS, |v| v as usize);
+    let topics = json["topics"].as_u64().map_or(DEFAULT_TOPICS, |v| v as usize);
+    let events = json["events"].as_u64().map_or(DEFAULT_EVENTS, |v| v as usize);
+    let publish_interval = json["publish_interval"].as_u64().unwrap_or(0);
+    let changes_timeout = json["changes_timeout"].as_u64().unwrap_or(DEFAULT_CHANGES_TIMEOUT_MS);
+    let faults = if json["faults"].is_object() { Some(FaultConfig::from_json(&json["faults"])) } else { None };
+    let timeouts = get_timeouts(&json);
+    let checkpoint_interval = json["checkpoint_interval"].as_u64().unwrap_or(DEFAULT_CHECKPOINT_INTERVAL_MS);
+    let report_intervals = ReportIntervals::new(
+        Some(json["report_interval"].as_u64().unwrap_or(DEFAULT_REPORT_INTERVAL_MS)).filter(|interval| *interval > 0),
+        json["report_operations"].as_u64().filter(|operations| *operations > 0));
+    let variation_threshold = json["variation_threshold"].as_f64().unwrap_or(DEFAULT_VARIATION_THRESHOLD);
+    let influx_mock_latency = json["influx_mock_latency"].as_u64().unwrap_or(0);
+    let influx_mock_rows = json["influx_mock_rows"].as_u64().map_or(DEFAULT_INFLUX_MOCK_ROWS, |v| v as usize);
+    let progress_interval = if json["progress"].as_bool().unwrap_or(true) {
+        Some(json["progress_interval"].as_u64().unwrap_or(DEFAULT_PROGRESS_INTERVAL_MS))
+    } else {
+        None
+    };
+    let pool_config = PoolConfig::new(
+        json["pool_size"].as_u64().map_or(DEFAULT_POOL_SIZE, |v| v as usize),
+        json["health_check_interval"].as_u64().unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL_MS),
+        json["reconnect_attempts"].as_u64().map_or(DEFAULT_RECONNECT_ATTEMPTS, |v| v as u32),
+        json["reconnect_backoff"].as_u64().unwrap_or(DEFAULT_RECONNECT_BACKOFF_MS),
+        json["reconnect_max_backoff"].as_u64().unwrap_or(DEFAULT_RECONNECT_MAX_BACKOFF_MS))
+        .with_memory_config(MemoryConfig::new(
+            json["memory_latency"].as_u64().unwrap_or(0),
+            json["memory_jitter"].as_u64().unwrap_or(0),
+            json["memory_error_rate"].as_f64().unwrap_or(0.0)));
+    
+    return Config {
+        backends: backends,
+        concurrent_backends: concurrent_backends,
+        test_case_file: test_case_file,
+        only_bench: only_bench,
+        sample_size: sample_size,
+        queries: queries,
+        changes_bench: changes_bench,
+        subscribers: subscribers,
+        topics: topics,
+        events: events,
+        publish_interval: publish_interval,
+        changes_timeout: changes_timeout,
+        pool_config: pool_config,
+        faults: faults,
+        timeouts: timeouts,
+        checkpoint_interval: checkpoint_interval,
+        progress_interval: progress_interval,
+        report_intervals: report_intervals,
+        variation_threshold: variation_threshold,
+        influx_mock_latency: influx_mock_latency,
+        influx_mock_rows: influx_mock_rows
+    }
+}
+
+/// Legacy `database_type` 4 is InfluxDB with the only benchmark.
+fn get_backend(json: &Value, only_bench: &mut bool) -> Backend {
+    let database_type = match json["database_type"].as_u64().expect("Invalid database_type params") {
         1 => DatabaseType::Influxdb,
         2 => DatabaseType::Mongodb,
         3 => DatabaseType::Rethinkdb,
-        4 => DatabaseType::InfluxOnlyBench,
+        4 => {
+            // Legacy InfluxOnlyBench variant
+            *only_bench = true;
+            DatabaseType::Influxdb
+        },
+        5 => DatabaseType::Memory,
         _ => panic!("Invalid database_type variant")
+    };
+    let name = json["name"].as_str().unwrap_or(database_type.get_name()).to_string();
+    // The connections are reopened until the process exits, so the address lives as long
+    let host = json["host"].as_str().map(|host| &*Box::leak(host.to_string().into_boxed_str()));
+    
+    return Backend {
+        name: name,
+        database_type: database_type,
+        host: host
     }
 }
 
@@ -329,19 +1539,106 @@ fn get_test_cases(test_case_file_path: &str) -> Vec<TestCase> {
     let json: Value = serde_json::from_str(&contents).expect("Invalid tests file json format");
     let mut test_cases = Vec::new();
     
-    for test_case_json in json["test_cases"].as_array().expect("Invalid test_cases array param") {
+    let mut ids = HashSet::new();
+    let entries = json["test_cases"].as_array().expect("Invalid test_cases array param");
+    for test_case_json in entries.iter().flat_map(test_matrix::expand) {
         let id = test_case_json["id"].as_u64().expect("Invalid test_case id param") as usize;
-        let series = test_case_json["series"].as_u64().expect("Invalid test_case id param") as usize;
-        let points_per_series = test_case_json["points_per_series"].as_u64().expect("Invalid test_case id param") as usize;
-        let queries = test_case_json["queries"].as_u64().expect("Invalid test_case id param") as usize;
+        let series = test_case_json["series"].as_u64().expect("Invalid test_case series param") as usize;
+        if !ids.insert(id) {
+            panic!("Duplicate test_case id: {}", id);
+        }
+        
+        let timeouts = get_timeouts(&test_case_json);
+        let ingest_order = match test_case_json["ingest_order"].as_str() {
+            None | Some("random") => IngestOrder::Random,
+            Some("backfill") => IngestOrder::Backfill,
+            Some("live") => IngestOrder::Live,
+            Some(ingest_order) => panic!("Invalid test_case ingest_order variant: {}", ingest_order)
+        };
+        let query_type = match test_case_json["query_type"].as_str() {
+            None => QueryType::Series,
+            Some(query_type) => QueryType::parse(query_type)
+                .unwrap_or_else(|| panic!("Invalid test_case query_type variant: {}", query_type))
+        };
+        let batch_size = cmp::max(test_case_json["batch_size"].as_u64().unwrap_or(1) as usize, 1);
+        let concurrency = cmp::max(test_case_json["concurrency"].as_u64().unwrap_or(1) as usize, 1);
+        let scenario = if test_case_json["phases"].is_null() {
+            None
+        } else {
+            Some(scenario::parse_phases(&test_case_json["phases"], batch_size, concurrency, query_type))
+        };
+        // The phases of a scenario have their own points and queries
+        let (points_per_series, queries) = match scenario {
+            Some(ref phases) => (scenario::get_points_per_series(phases), 0),
+            None => (
+                test_case_json["points_per_series"].as_u64().expect("Invalid test_case points_per_series param") as usize,
+                test_case_json["queries"].as_u64().expect("Invalid test_case queries param") as usize)
+        };
         
-        let test_case = TestCase::new(id, series, points_per_series, queries);
+        let mut test_case = TestCase::new(id, series, points_per_series, queries, timeouts)
+            .with_ingest_order(ingest_order)
+            .with_point_interval(test_case_json["point_interval"].as_u64().unwrap_or(DEFAULT_POINT_INTERVAL_MS))
+            .with_repeat(test_case_json["repeat"].as_u64().unwrap_or(1) as usize)
+            .with_batch_size(batch_size)
+            .with_concurrency(concurrency)
+            .with_query_type(query_type);
+        if let Some(name) = test_case_json["name"].as_str() {
+            test_case = test_case.with_name(name.to_string());
+        }
+        if let Some(phases) = scenario {
+            test_case = test_case.with_scenario(phases);
+        }
         test_cases.push(test_case);
     }
     
     return test_cases;
 }
 
+fn get_timeouts(json: &Value) -> Timeouts {
+    return Timeouts::new(
+        json["operation_timeout"].as_u64(),
+        json["insert_timeout"].as_u64(),
+        json["select_timeout"].as_u64());
+}
+
+/// A resumed test case appends to the log of the interrupted run.
+fn open_log_file(file_path: String, is_append: bool) -> File {
+    if is_append {
+        return OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(file_path)
+            .expect("Can't open log file");
+    }
+    return File::create(file_path).expect("Can't create log file");
+}
+
 fn write_log(log_file: &mut File, args: Arguments) {
-    log_file.write_fmt(args);
+    log_file.write_fmt(args).expect("Can't write log file");
+}
+
+pub fn write_faults(log_file: &mut File, phase: &str, faults: &FaultCounts) {
+    if faults.is_empty() {
+        return
+    }
+    write_log(log_file,
+        format_args!("# {} faults: {} latency spikes, {} dropped responses, {} errors, {} resets, {} retries\n",
+        phase,
+        faults.latency_spikes,
+        faults.dropped,
+        faults.errors,
+        faults.resets,
+        faults.retries));
+}
+
+pub fn write_reconnect_events(log_file: &mut File, events: Vec<ReconnectEvent>) {
+    for event in events {
+        let result = if event.is_reconnected() { "Reconnected" } else { "Failed to reconnect" };
+        write_log(log_file,
+            format_args!("# {} after {} attempts, {} ms without connection: {}\n",
+            result,
+            event.get_attempts(),
+            event.get_downtime_ms(),
+            event.get_error()));
+    }
 }
@@ -0,0 +1,143 @@
+use compute_percentile;
+use std::cmp;
+use std::collections::VecDeque;
+use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
+
+
+static LATENCY_WINDOW: usize = 1000;
+
+/// Shows the progress of a phase on one terminal line, rewritten once per interval.
+/// Latency percentiles are computed over the last `LATENCY_WINDOW` operations.
+pub struct Progress {
+    phase: &'static str,
+    /// Records of the phase, `None` when it runs until a time or an event
+    total: Option<usize>,
+    attempted: usize,
+    interval: Option<Duration>,
+    start_time: Instant,
+    /// Time the phase ends at the latest
+    end_time: Option<Instant>,
+    start_attempted: usize,
+    last_report_time: Instant,
+    last_report_attempted: usize,
+    latencies: VecDeque<i32>,
+    is_reported: bool
+}
+
+impl Progress {
+    /// `attempted` operations were done before a resume, `interval` `None` shows nothing.
+    pub fn new(phase: &'static str, total: Option<usize>, attempted: usize, interval: Option<Duration>) -> Progress {
+        return Progress {
+            phase: phase,
+            total: total,
+            attempted: attempted,
+            interval: interval,
+            start_time: Instant::now(),
+            end_time: None,
+            start_attempted: attempted,
+            last_report_time: Instant::now(),
+            last_report_attempted: attempted,
+            latencies: VecDeque::with_capacity(LATENCY_WINDOW),
+            is_reported: false
+        }
+    }
+    
+    /// The ETA is at most the time left until `end_time`.
+    pub fn with_end_time(mut self, end_time: Option<Instant>) -> Progress {
+        self.end_time = end_time;
+        return self
+    }
+    
+    /// Counts an operation of `count` records, `latency` is `None` when it timed out
+    /// or failed.
+    pub fn record(&mut self, latency: Option<i32>, count: usize) {
+        self.attempted += count;
+        if let Some(latency) = latency {
+            if self.latencies.len() == LATENCY_WINDOW {
+                self.latencies.pop_front();
+            }
+            self.latencies.push_back(latency);
+        }
+        
+        match self.interval {
+            Some(interval) if self.last_report_time.elapsed() >= interval => self.report(),
+            _ => {}
+        }
+    }
+    
+    fn report(&mut self) {
+        let elapsed_ms = self.last_report_time.elapsed().as_millis().max(1);
+        let records_per_sec = (self.attempted-self.last_report_attempted) as u128*1000/elapsed_ms;
+        let mut latencies: Vec<i32> = self.latencies.iter().cloned().collect();
+        latencies.sort();
+        let attempted = match self.total {
+            Some(total) => format!("{}/{} ({}%)", self.attempted, total, (self.attempted*100).checked_div(total).unwrap_or(100)),
+            None => self.attempted.to_string()
+        };
+        
+        print!("\r{}: {}, {} records/s, p50: {} ms, p95: {} ms, p99: {} ms{}    ",
+            self.phase,
+            attempted,
+            records_per_sec,
+            compute_percentile(&latencies, 50.0),
+            compute_percentile(&latencies, 95.0),
+            compute_percentile(&latencies, 99.0),
+            self.format_eta());
+        let _ = stdout().flush();
+        
+        self.last_report_time = Instant::now();
+        self.last_report_attempted = self.attempted;
+        self.is_reported = true;
+    }
+    
+    /// The remaining records at the average rate of this run, at most until the end
+    /// time. Without a total or an end time there is no ETA.
+    fn format_eta(&self) -> String {
+        let done = self.attempted-self.start_attempted;
+        let records_eta_secs = match self.total {
+            Some(total) if done > 0 => {
+                let remaining = total.saturating_sub(self.attempted) as u128;
+                Some(self.start_time.elapsed().as_millis()*remaining/done as u128/1000)
+            },
+            _ => None
+        };
+        let time_eta_secs = self.end_time.map(|end_time| end_time.saturating_duration_since(Instant::now()).as_secs() as u128);
+        let eta_secs = match (records_eta_secs, time_eta_secs) {
+            (Some(records_eta_secs), Some(time_eta_secs)) => Some(cmp::min(records_eta_secs, time_eta_secs)),
+            (records_eta_secs, time_eta_secs) => records_eta_secs.or(time_eta_secs)
+        };
+        return match eta_secs {
+            Some(eta_secs) => format!(", ETA: {}:{:02}:{:02}", eta_secs/3600, eta_secs/60 % 60, eta_secs % 60),
+            None if self.total.is_some() => String::from(", ETA: unknown"),
+            None => String::new()
+        }
+    }
+    
+    /// Ends the progress line.
+    pub fn finish(&mut self) {
+        if self.is_reported {
+            self.report();
+            println!();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn eta_comes_from_the_total_or_the_end_time() {
+        let mut progress = Progress::new("load", Some(100), 0, None);
+        assert_eq!(progress.format_eta(), ", ETA: unknown");
+        progress.record(Some(1), 100);
+        assert_eq!(progress.format_eta(), ", ETA: 0:00:00");
+        
+        let progress = Progress::new("query", None, 0, None);
+        assert_eq!(progress.format_eta(), "");
+        let progress = Progress::new("query", None, 0, None)
+            .with_end_time(Some(Instant::now()+Duration::from_secs(90)));
+        assert!(progress.format_eta().starts_with(", ETA: 0:01:"));
+    }
+}
@@ -0,0 +1,167 @@
+use {compute_time_diff_ms, DatabaseType, Executor, get_current_time, Point, Query};
+use changes_bench::{subscribe_changes, ChangeEvent, ChangesExecutor};
+use db::db_raw::query_db;
+use db::db_raw::query_db::{Condition, DBError, DBValue, PoolConfig, ReconnectEvent, Request, QueryBase};
+use std::collections::HashMap;
+
+
+pub static INFLUX_TABLE: &str = "accounts";
+static INFLUX_EVENTS_TABLE: &str = "events";
+static MONGO_TABLE: &str = "accounts";
+static MONGO_EVENTS_TABLE: &str = "accounts_events";
+/// The events of rethinkdb share the table of the points
+static RETHINK_TABLE: &str = "test";
+static MEMORY_TABLE: &str = "accounts";
+static MEMORY_EVENTS_TABLE: &str = "events";
+pub static TAG: &str = "address";
+pub static FIELD_ONE: &str = "from";
+pub static FIELD_TWO: &str = "to";
+pub static FIELD_THREE: &str = "balance";
+static FIELD_CREATED: &str = "created";
+pub static FIELD_TIME: &str = "time";
+
+/// Runs the benchmark through the data-access layer of any backend, the points
+/// go to `table` and the events of the changes benchmark to `events_table`.
+pub struct QueryExecutor {
+    database: Box<dyn QueryBase>,
+    table: &'static str,
+    events_table: &'static str
+}
+
+impl QueryExecutor {
+    pub fn new(database_type: DatabaseType, table: &'static str, events_table: &'static str, pool_config: PoolConfig) -> Result<QueryExecutor, DBError> {
+        let database = query_db::get_pool(database_type, pool_config)?;
+        if let Some(err) = database.ensure_index(table, TAG) {
+            return Err(err)
+        }
+        
+        return Ok(QueryExecutor {
+            database: database,
+            table: table,
+            events_table: events_table
+        })
+    }
+    
+    /// Constructor of the executor of `database_type` with the tables of the backend.
+    pub fn get_constructor(database_type: DatabaseType) -> fn(PoolConfig) -> Result<QueryExecutor, DBError> {
+        return match database_type {
+            DatabaseType::Influxdb => |pool_config| QueryExecutor::new(DatabaseType::Influxdb, INFLUX_TABLE, INFLUX_EVENTS_TABLE, pool_config),
+            DatabaseType::Mongodb => |pool_config| QueryExecutor::new(DatabaseType::Mongodb, MONGO_TABLE, MONGO_EVENTS_TABLE, pool_config),
+            DatabaseType::Rethinkdb => |pool_config| QueryExecutor::new(DatabaseType::Rethinkdb, RETHINK_TABLE, RETHINK_TABLE, pool_config),
+            DatabaseType::Memory => |pool_config| QueryExecutor::new(DatabaseType::Memory, MEMORY_TABLE, MEMORY_EVENTS_TABLE, pool_config)
+        }
+    }
+}
+
+impl Executor for QueryExecutor {
+    fn insert(&mut self, points: Vec<Point>) -> Result<i32, DBError> {
+        let data = points.iter()
+            .map(|point| {
+                let hash = point.get_hash();
+                let mut data = HashMap::new();
+                data.insert(String::from(TAG), DBValue::from(hash));
+                data.insert(String::from(FIELD_ONE), DBValue::Str(format!("{}from", hash)));
+                data.insert(String::from(FIELD_TWO), DBValue::Str(format!("{}to", hash)));
+                data.insert(String::from(FIELD_THREE), DBValue::Int(point.get_random_number() as i64));
+                data.insert(String::from(FIELD_TIME), DBValue::Timestamp(point.get_timestamp()));
+                return data;
+            })
+            .collect();
+        
+        let start_time = get_current_time();
+        if let Some(err) = self.database.insert_many(self.table, data) {
+            return Err(err)
+        }
+        let end_time = get_current_time();
+        
+        return Ok(compute_time_diff_ms(start_time, end_time));
+    }
+    
+    fn select(&mut self, hash: &str, query: &Query) -> Result<i32, DBError> {
+        let request_select = query.to_request(self.table, TAG, FIELD_TIME, hash);
+        
+        let start_time = get_current_time();
+        let documents = self.database.select(request_select)?;
+        // The query is only done once the last document is read
+        for document_res in documents {
+            document_res?;
+        }
+        let end_time = get_current_time();
+        
+        return Ok(compute_time_diff_ms(start_time, end_time));
+    }
+    
+    fn delete(&mut self, hash: &str, before: i64) -> Result<i32, DBError> {
+        let mut condition = HashMap::new();
+        condition.insert(String::from(TAG), Condition::Eq(DBValue::from(hash)));
+        condition.insert(String::from(FIELD_TIME), Condition::Lt(DBValue::Timestamp(before)));
+        let request_delete = Request::from_condition(String::from(self.table), condition);
+        
+        let start_time = get_current_time();
+        if let Some(err) = self.database.delete(request_delete) {
+            return Err(err)
+        }
+        let end_time = get_current_time();
+        
+        return Ok(compute_time_diff_ms(start_time, end_time));
+    }
+    
+    fn compact(&mut self) -> Result<i32, DBError> {
+        let start_time = get_current_time();
+        if let Some(err) = self.database.compact(self.table) {
+            return Err(err)
+        }
+        let end_time = get_current_time();
+        
+        return Ok(compute_time_diff_ms(start_time, end_time));
+    }
+    
+    fn get_last_timestamp(&mut self, hash: &str) -> Result<Option<i64>, DBError> {
+        let request_select = Query::Last.to_request(self.table, TAG, FIELD_TIME, hash);
+        let mut documents = self.database.select(request_select)?;
+        let document = match documents.next() {
+            Some(document_res) => document_res?,
+            None => return Ok(None)
+        };
+        
+        return Ok(query_db::json_to_timestamp(&document[FIELD_TIME]));
+    }
+    
+    fn get_hashes(&mut self, sample_size: usize) -> Result<Vec<String>, DBError> {
+        let values = self.database.distinct(self.table, TAG, sample_size)?;
+        
+        return Ok(values.iter()
+            .filter_map(|value| value.as_str())
+            .map(|value| value.to_string())
+            .collect());
+    }
+    
+    fn take_reconnect_events(&mut self) -> Vec<ReconnectEvent> {
+        return self.database.take_reconnect_events()
+    }
+}
+
+impl ChangesExecutor for QueryExecutor {
+    fn subscribe(&mut self, topic: &str, on_event: &mut dyn FnMut(ChangeEvent) -> bool) -> Option<DBError> {
+        let mut condition = HashMap::new();
+        condition.insert(String::from(TAG), Condition::Eq(DBValue::from(topic)));
+        let request_changes = Request::from_condition(String::from(self.events_table), condition);
+        
+        return subscribe_changes(self.database.as_ref(), request_changes, FIELD_CREATED, on_event);
+    }
+    
+    fn publish(&mut self, topic: &str, stamp: u128) -> Result<i32, DBError> {
+        let mut data = HashMap::new();
+        data.insert(String::from(TAG), DBValue::from(topic));
+        data.insert(String::from(FIELD_CREATED), DBValue::Timestamp(stamp as i64));
+        let request_insert = Request::from_data(String::from(self.events_table), data);
+        
+        let start_time = get_current_time();
+        if let Some(err) = self.database.insert(request_insert) {
+            return Err(err)
+        }
+        let end_time = get_current_time();
+        
+        return Ok(compute_time_diff_ms(start_time, end_time));
+    }
+}
@@ -0,0 +1,341 @@
+use results::{PhaseResult, PhaseStatistics, Results, RunResult};
+use std::fs::File;
+use std::io::Write;
+
+
+static CHART_WIDTH: f64 = 640.0;
+static CHART_HEIGHT: f64 = 320.0;
+static MARGIN_LEFT: f64 = 70.0;
+static MARGIN_RIGHT: f64 = 20.0;
+static MARGIN_TOP: f64 = 30.0;
+static MARGIN_BOTTOM: f64 = 45.0;
+static HISTOGRAM_BINS: usize = 40;
+static COLORS: [&str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b"];
+/// Percentiles of the latency plots, evenly spaced on the x axis so the tail is readable
+static PLOT_PERCENTILES: [f64; 11] = [0.0, 25.0, 50.0, 75.0, 90.0, 95.0, 99.0, 99.5, 99.9, 99.99, 100.0];
+static STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: right; }
+th:first-child, td:first-child { text-align: left; }
+svg { margin: 0.5em 1em 0.5em 0; }
+svg text { font-size: 11px; }";
+
+/// Phase of a test case in one run, drawn with the color of the run.
+struct PhaseRow<'a> {
+    color: &'static str,
+    label: &'a str,
+    result: &'a PhaseResult,
+    /// Spread over the repetitions of the test case, when it is repeated
+    statistics: Option<&'a PhaseStatistics>
+}
+
+/// Line of a line chart.
+struct ChartLine<'a> {
+    color: &'static str,
+    label: &'a str,
+    points: Vec<(f64, f64)>
+}
+
+/// Writes one HTML file with a section per test case and phase of all runs of the
+/// results files: a summary table, the throughput per report window, the latency
+/// percentiles and the latency histogram of every run. The charts are inline SVG,
+/// so the file opens without a network connection.
+pub fn write_report(report_file_path: &str, result_file_paths: &[String]) {
+    let results: Vec<Results> = result_file_paths.iter().map(|path| Results::load(path)).collect();
+    let mut runs: Vec<(String, &RunResult)> = Vec::new();
+    for (result_file_path, file_results) in result_file_paths.iter().zip(results.iter()) {
+        for run in file_results.get_runs() {
+            runs.push((format!("{} ({})", run.get_database(), result_file_path), run));
+        }
+    }
+    
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>influx_bench report</title>\n");
+    html.push_str(&format!("<style>\n{}\n</style>\n</head>\n<body>\n<h1>influx_bench report</h1>\n<ul>\n", STYLE));
+    for (run_pos, (label, _)) in runs.iter().enumerate() {
+        html.push_str(&format!("<li style=\"color: {}\">{}</li>\n", get_color(run_pos), escape_html(label)));
+    }
+    html.push_str("</ul>\n");
+    
+    let mut test_case_ids: Vec<usize> = runs.iter()
+        .flat_map(|&(_, run)| run.get_test_cases().iter().map(|test_case| test_case.get_id()))
+        .collect();
+    test_case_ids.sort();
+    test_case_ids.dedup();
+    
+    for test_case_id in test_case_ids {
+        html.push_str(&format!("<h2>Test case {}</h2>\n", test_case_id));
+        let mut phases: Vec<&str> = Vec::new();
+        for &(_, run) in runs.iter() {
+            for phase_result in run.get_test_case(test_case_id).map_or(&[][..], |test_case| test_case.get_phases()) {
+                if !phases.contains(&phase_result.get_phase()) {
+                    phases.push(phase_result.get_phase());
+                }
+            }
+        }
+        
+        for phase in phases {
+            let rows: Vec<PhaseRow> = runs.iter().enumerate()
+                .filter_map(|(run_pos, &(ref label, run))| {
+                    let test_case = run.get_test_case(test_case_id)?;
+                    return test_case.get_phase(phase).map(|result| PhaseRow {
+                        color: get_color(run_pos),
+                        label: label,
+                        result: result,
+                        statistics: test_case.get_phase_statistics(phase)
+                    });
+                })
+                .collect();
+            
+            html.push_str(&format!("<h3>{} phase</h3>\n", escape_html(phase)));
+            html.push_str(&render_summary_table(&rows));
+            html.push_str(&render_statistics_table(&rows));
+            html.push_str("<div>\n");
+            html.push_str(&render_throughput_chart(&rows));
+            html.push_str(&render_percentile_chart(&rows));
+            html.push_str("</div>\n<div>\n");
+            html.push_str(&render_histograms(&rows));
+            html.push_str("</div>\n");
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    
+    let mut file = File::create(report_file_path).expect("Can't create report file");
+    file.write_all(html.as_bytes()).expect("Can't write report file");
+    println!("# Report of {} runs written to {}", runs.len(), report_file_path);
+}
+
+fn get_color(run_pos: usize) -> &'static str {
+    return COLORS[run_pos % COLORS.len()]
+}
+
+fn render_summary_table(rows: &[PhaseRow]) -> String {
+    let mut html = String::from("<table>\n<tr><th>Run</th><th>Total</th><th>Completed</th><th>Operations</th><th>Timed out</th>\
+        <th>Failed</th><th>Retries</th><th>Duration, s</th><th>Throughput, records/s</th><th>Operations/s</th><th>Average, ms</th><th>p50, ms</th><th>p95, ms</th>\
+        <th>p99, ms</th><th>Max, ms</th></tr>\n");
+    for row in rows {
+        let latency = row.result.get_latency();
+        html.push_str(&format!("<tr><td style=\"color: {}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+            <td>{:.1}</td><td>{:.1}</td><td>{:.1}</td><td>{:.1}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            row.color,
+            escape_html(row.label),
+            row.result.get_total(),
+            row.result.get_completed(),
+            row.result.get_operations(),
+            row.result.get_timed_out(),
+            row.result.get_failed(),
+            row.result.get_faults().retries,
+            row.result.get_duration_ms() as f64/1000.0,
+            row.result.get_throughput(),
+            row.result.get_operation_throughput(),
+            latency.get_average(),
+            format_latency(latency.get_p50()),
+            format_latency(latency.get_p95()),
+            format_latency(latency.get_p99()),
+            format_latency(latency.get_max())));
+    }
+    html.push_str("</table>\n");
+    return html;
+}
+
+/// Percentile of a phase, a phase without latency samples has none.
+fn format_latency(latency: Option<i32>) -> String {
+    return latency.map_or(String::from("-"), |latency| latency.to_string())
+}
+
+/// Mean and 95% confidence interval over the repetitions, the summary table shows
+/// all repetitions merged.
+fn render_statistics_table(rows: &[PhaseRow]) -> String {
+    if rows.iter().all(|row| row.statistics.is_none()) {
+        return String::new()
+    }
+    let mut html = String::from("<table>\n<tr><th>Run</th><th>Repetitions</th><th>Throughput, records/s</th>\
+        <th>p50, ms</th><th>p95, ms</th><th>p99, ms</th></tr>\n");
+    for row in rows {
+        let statistics = match row.statistics {
+            Some(statistics) => statistics,
+            None => continue
+        };
+        html.push_str(&format!("<tr><td style=\"color: {}\">{}</td><td>{}</td>", row.color, escape_html(row.label), statistics.get_repetitions()));
+        for &(_, _, estimate) in statistics.get_metrics().iter() {
+            if estimate.get_count() == 0 {
+                html.push_str("<td>-</td>");
+            } else {
+                html.push_str(&format!("<td>{:.1} &plusmn; {:.1}</td>", estimate.get_mean(), estimate.get_margin()));
+            }
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</table>\n");
+    return html;
+}
+
+/// Throughput of every report window at the end of the window.
+fn render_throughput_chart(rows: &[PhaseRow]) -> String {
+    let lines: Vec<ChartLine> = rows.iter()
+        .filter(|row| !row.result.get_intervals().is_empty())
+        .map(|row| {
+            let points = row.result.get_intervals().iter()
+                .map(|interval| ((interval.get_start_ms()+interval.get_duration_ms()) as f64/1000.0, interval.get_throughput()))
+                .collect();
+            return ChartLine { color: row.color, label: row.label, points: points };
+        })
+        .collect();
+    if lines.is_empty() {
+        return String::from("<p>No report windows recorded, throughput over time is not available.</p>\n")
+    }
+    
+    let max_x = lines.iter()
+        .flat_map(|line| line.points.iter().map(|point| point.0))
+        .fold(0.0, f64::max);
+    let (max_x, x_ticks) = compute_ticks(max_x);
+    let x_ticks: Vec<(f64, String)> = x_ticks.into_iter().map(|tick| (tick, format_number(tick))).collect();
+    return render_line_chart("Throughput over time", "Time, s", "records/s", max_x, &x_ticks, &lines);
+}
+
+fn render_percentile_chart(rows: &[PhaseRow]) -> String {
+    let lines: Vec<ChartLine> = rows.iter()
+        .filter(|row| row.result.get_histogram().get_count() > 0)
+        .map(|row| {
+            let histogram = row.result.get_histogram();
+            let points = PLOT_PERCENTILES.iter().enumerate()
+                .map(|(pos, percentile)| (pos as f64, histogram.get_percentile(*percentile) as f64))
+                .collect();
+            return ChartLine { color: row.color, label: row.label, points: points };
+        })
+        .collect();
+    if lines.is_empty() {
+        return String::from("<p>No latency samples recorded, latency percentiles are not available.</p>\n")
+    }
+    let x_ticks: Vec<(f64, String)> = PLOT_PERCENTILES.iter().enumerate()
+        .map(|(pos, percentile)| (pos as f64, format!("p{}", percentile)))
+        .collect();
+    return render_line_chart("Latency percentiles", "Percentile", "ms", (PLOT_PERCENTILES.len()-1) as f64, &x_ticks, &lines);
+}
+
+/// One histogram per run, with the same bins for all runs of the phase.
+fn render_histograms(rows: &[PhaseRow]) -> String {
+    let max_latency = rows.iter().map(|row| row.result.get_histogram().get_max()).max().unwrap_or(0).max(0) as usize;
+    let bin_width = max_latency/HISTOGRAM_BINS+1;
+    let mut html = String::new();
+    for row in rows {
+        let mut bins = vec![0u64; max_latency/bin_width+1];
+        for (latency, count) in row.result.get_histogram().get_counts() {
+            bins[(*latency).max(0) as usize/bin_width] += count;
+        }
+        html.push_str(&render_histogram(row, &bins, bin_width));
+    }
+    return html;
+}
+
+fn render_histogram(row: &PhaseRow, bins: &[u64], bin_width: usize) -> String {
+    let plot_width = CHART_WIDTH-MARGIN_LEFT-MARGIN_RIGHT;
+    let plot_height = CHART_HEIGHT-MARGIN_TOP-MARGIN_BOTTOM;
+    let (max_y, y_ticks) = compute_ticks(bins.iter().cloned().max().unwrap_or(0) as f64);
+    let bar_width = plot_width/bins.len() as f64;
+    
+    let mut svg = render_chart_frame(&format!("Latency histogram, {}", row.label), "Latency, ms", "Operations", max_y, &y_ticks);
+    for (pos, count) in bins.iter().enumerate() {
+        let bar_height = *count as f64/max_y*plot_height;
+        svg.push_str(&format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{}-{} ms: {}</title></rect>\n",
+            MARGIN_LEFT+pos as f64*bar_width,
+            MARGIN_TOP+plot_height-bar_height,
+            (bar_width-1.0).max(1.0),
+            bar_height,
+            row.color,
+            pos*bin_width,
+            (pos+1)*bin_width-1,
+            count));
+    }
+    let tick_step = (bins.len()/5).max(1);
+    for pos in (0..=bins.len()).step_by(tick_step) {
+        let x = MARGIN_LEFT+pos as f64*bar_width;
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+            x, MARGIN_TOP+plot_height+15.0, pos*bin_width));
+    }
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+fn render_line_chart(title: &str,
+    x_label: &str,
+    y_label: &str,
+    max_x: f64,
+    x_ticks: &[(f64, String)],
+    lines: &[ChartLine]) -> String {
+    
+    let plot_width = CHART_WIDTH-MARGIN_LEFT-MARGIN_RIGHT;
+    let plot_height = CHART_HEIGHT-MARGIN_TOP-MARGIN_BOTTOM;
+    let max_y = lines.iter()
+        .flat_map(|line| line.points.iter().map(|point| point.1))
+        .fold(0.0, f64::max);
+    let (max_y, y_ticks) = compute_ticks(max_y);
+    let max_x = if max_x > 0.0 { max_x } else { 1.0 };
+    let to_x = |x: f64| MARGIN_LEFT+x/max_x*plot_width;
+    let to_y = |y: f64| MARGIN_TOP+plot_height-y/max_y*plot_height;
+    
+    let mut svg = render_chart_frame(title, x_label, y_label, max_y, &y_ticks);
+    for &(tick, ref tick_label) in x_ticks {
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+            to_x(tick), MARGIN_TOP+plot_height+15.0, escape_html(tick_label)));
+    }
+    for (line_pos, line) in lines.iter().enumerate() {
+        let points: Vec<String> = line.points.iter()
+            .map(|&(x, y)| format!("{:.1},{:.1}", to_x(x), to_y(y)))
+            .collect();
+        svg.push_str(&format!("<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\"/>\n", points.join(" "), line.color));
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" fill=\"{}\">{}</text>\n",
+            CHART_WIDTH-MARGIN_RIGHT-5.0, MARGIN_TOP+12.0+line_pos as f64*14.0, line.color, escape_html(line.label)));
+    }
+    svg.push_str("</svg>\n");
+    return svg;
+}
+
+/// Opens an SVG element with the title, the axes and the horizontal grid lines.
+fn render_chart_frame(title: &str, x_label: &str, y_label: &str, max_y: f64, y_ticks: &[f64]) -> String {
+    let plot_height = CHART_HEIGHT-MARGIN_TOP-MARGIN_BOTTOM;
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", CHART_WIDTH, CHART_HEIGHT);
+    svg.push_str(&format!("<text x=\"{}\" y=\"18\" text-anchor=\"middle\" font-weight=\"bold\">{}</text>\n", CHART_WIDTH/2.0, escape_html(title)));
+    for tick in y_ticks {
+        let y = MARGIN_TOP+plot_height-tick/max_y*plot_height;
+        svg.push_str(&format!("<line x1=\"{}\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\" stroke=\"#eee\"/>\n",
+            MARGIN_LEFT, y, CHART_WIDTH-MARGIN_RIGHT, y));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\n", MARGIN_LEFT-5.0, y+4.0, format_number(*tick)));
+    }
+    svg.push_str(&format!("<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"#333\"/>\n", MARGIN_LEFT, MARGIN_TOP, MARGIN_TOP+plot_height));
+    svg.push_str(&format!("<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"#333\"/>\n", MARGIN_LEFT, MARGIN_TOP+plot_height, CHART_WIDTH-MARGIN_RIGHT));
+    svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+        MARGIN_LEFT+(CHART_WIDTH-MARGIN_LEFT-MARGIN_RIGHT)/2.0, CHART_HEIGHT-8.0, escape_html(x_label)));
+    svg.push_str(&format!("<text x=\"14\" y=\"{0}\" text-anchor=\"middle\" transform=\"rotate(-90 14 {0})\">{1}</text>\n",
+        MARGIN_TOP+plot_height/2.0, escape_html(y_label)));
+    return svg;
+}
+
+/// Rounds `max` up to a multiple of a 1, 2 or 5 step and returns it with the
+/// ticks from 0 to it, about 5 of them.
+fn compute_ticks(max: f64) -> (f64, Vec<f64>) {
+    let max = if max > 0.0 { max } else { 1.0 };
+    let raw_step = max/5.0;
+    let magnitude = 10f64.powf(raw_step.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter()
+        .map(|factor| factor*magnitude)
+        .find(|step| *step >= raw_step)
+        .unwrap_or(10.0*magnitude);
+    let tick_count = (max/step).ceil() as usize;
+    let ticks = (0..=tick_count).map(|pos| pos as f64*step).collect();
+    return (tick_count as f64*step, ticks);
+}
+
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 {
+        return format!("{:.0}", number)
+    }
+    return format!("{:.2}", number);
+}
+
+fn escape_html(text: &str) -> String {
+    return text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
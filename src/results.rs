@@ -0,0 +1,1093 @@
+use fault_executor::FaultCounts;
+use serde_json::Value;
+use statistics::Estimate;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+
+/// Latency counts per millisecond. Latencies are whole milliseconds, so the
+/// histogram is exact and its size is bounded by the slowest operation.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyHistogram {
+    counts: BTreeMap<i32, u64>
+}
+
+impl LatencyHistogram {
+    pub fn new() -> LatencyHistogram {
+        return LatencyHistogram {
+            counts: BTreeMap::new()
+        }
+    }
+    
+    pub fn record(&mut self, latency: i32) {
+        self.record_count(latency, 1);
+    }
+    
+    pub fn record_count(&mut self, latency: i32, count: u64) {
+        *self.counts.entry(latency).or_insert(0) += count;
+    }
+    
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        for (latency, count) in other.counts.iter() {
+            self.record_count(*latency, *count);
+        }
+    }
+    
+    pub fn get_count(&self) -> u64 {
+        return self.counts.values().sum()
+    }
+    
+    pub fn get_sum(&self) -> i64 {
+        return self.counts.iter().map(|(latency, count)| *latency as i64*(*count as i64)).sum()
+    }
+    
+    pub fn get_max(&self) -> i32 {
+        return self.counts.keys().next_back().cloned().unwrap_or(0)
+    }
+    
+    /// Nearest-rank percentile, the same rank as `compute_percentile` of sorted values.
+    pub fn get_percentile(&self, percentile: f64) -> i32 {
+        let count = self.get_count();
+        if count == 0 {
+            return 0
+        }
+        let pos = (percentile/100.0*(count-1) as f64).round() as u64;
+        let mut seen = 0;
+        for (latency, latency_count) in self.counts.iter() {
+            seen += latency_count;
+            if seen > pos {
+                return *latency
+            }
+        }
+        return self.get_max()
+    }
+    
+    pub fn get_counts(&self) -> &BTreeMap<i32, u64> {
+        return &self.counts
+    }
+    
+    pub fn to_json(&self) -> Value {
+        return Value::Array(self.counts.iter().map(|(latency, count)| json!([latency, count])).collect());
+    }
+    
+    pub fn from_json(json: &Value) -> LatencyHistogram {
+        let mut histogram = LatencyHistogram::new();
+        for bucket in json.as_array().expect("Invalid histogram param") {
+            let latency = bucket[0].as_i64().expect("Invalid histogram latency") as i32;
+            let count = bucket[1].as_u64().expect("Invalid histogram count");
+            histogram.record_count(latency, count);
+        }
+        return histogram;
+    }
+}
+
+/// The percentiles and the maximum are `None` without latency samples, they are
+/// written as `null`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatencySummary {
+    average: f64,
+    p50: Option<i32>,
+    p95: Option<i32>,
+    p99: Option<i32>,
+    max: Option<i32>
+}
+
+impl LatencySummary {
+    pub fn from_histogram(histogram: &LatencyHistogram) -> LatencySummary {
+        let count = histogram.get_count();
+        let percentile = |percentile: f64| if count > 0 { Some(histogram.get_percentile(percentile)) } else { None };
+        return LatencySummary {
+            average: if count > 0 { histogram.get_sum() as f64/count as f64 } else { 0.0 },
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: if count > 0 { Some(histogram.get_max()) } else { None }
+        }
+    }
+    
+    pub fn get_average(&self) -> f64 {
+        return self.average
+    }
+    
+    pub fn get_p50(&self) -> Option<i32> {
+        return self.p50
+    }
+    
+    pub fn get_p95(&self) -> Option<i32> {
+        return self.p95
+    }
+    
+    pub fn get_p99(&self) -> Option<i32> {
+        return self.p99
+    }
+    
+    pub fn get_max(&self) -> Option<i32> {
+        return self.max
+    }
+    
+    fn to_json(self) -> Value {
+        return json!({
+            "average": self.average,
+            "p50": self.p50,
+            "p95": self.p95,
+            "p99": self.p99,
+            "max": self.max
+        });
+    }
+    
+    fn from_json(json: &Value) -> LatencySummary {
+        let percentile = |key: &str| json[key].as_i64().map(|value| value as i32);
+        return LatencySummary {
+            average: json["average"].as_f64().expect("Invalid latency average param"),
+            p50: percentile("p50"),
+            p95: percentile("p95"),
+            p99: percentile("p99"),
+            max: percentile("max")
+        }
+    }
+}
+
+/// Throughput and latency of the operations of one window only.
+#[derive(Clone, Debug)]
+pub struct IntervalReport {
+    /// Milliseconds since the phase started
+    start_ms: u64,
+    duration_ms: u64,
+    /// Records of the completed operations
+    completed: u64,
+    timed_out: u64,
+    failed: u64,
+    /// Records attempted in the phase up to the end of the window
+    total_attempted: u64,
+    latency: LatencySummary
+}
+
+impl IntervalReport {
+    /// `histogram` has one latency per operation, the counts are in records.
+    pub fn new(start_ms: u64,
+        duration_ms: u64,
+        completed: u64,
+        timed_out: u64,
+        failed: u64,
+        total_attempted: u64,
+        histogram: &LatencyHistogram) -> IntervalReport {
+        
+        return IntervalReport {
+            start_ms: start_ms,
+            duration_ms: duration_ms,
+            completed: completed,
+            timed_out: timed_out,
+            failed: failed,
+            total_attempted: total_attempted,
+            latency: LatencySummary::from_histogram(histogram)
+        }
+    }
+    
+    pub fn get_start_ms(&self) -> u64 {
+        return self.start_ms
+    }
+    
+    pub fn get_duration_ms(&self) -> u64 {
+        return self.duration_ms
+    }
+    
+    pub fn get_completed(&self) -> u64 {
+        return self.completed
+    }
+    
+    pub fn get_timed_out(&self) -> u64 {
+        return self.timed_out
+    }
+    
+    pub fn get_failed(&self) -> u64 {
+        return self.failed
+    }
+    
+    pub fn get_total_attempted(&self) -> u64 {
+        return self.total_attempted
+    }
+    
+    /// Completed records per second.
+    pub fn get_throughput(&self) -> f64 {
+        return compute_throughput(self.completed, self.duration_ms)
+    }
+    
+    pub fn get_latency(&self) -> &LatencySummary {
+        return &self.latency
+    }
+    
+    /// The same window `offset_ms` later, for a phase merged after another one.
+    fn with_offset(&self, offset_ms: u64) -> IntervalReport {
+        let mut interval = self.clone();
+        interval.start_ms += offset_ms;
+        return interval;
+    }
+    
+    fn to_json(&self) -> Value {
+        return json!({
+            "start_ms": self.start_ms,
+            "duration_ms": self.duration_ms,
+            "completed": self.completed,
+            "timed_out": self.timed_out,
+            "failed": self.failed,
+            "total_attempted": self.total_attempted,
+            "throughput": self.get_throughput(),
+            "latency": self.latency.to_json()
+        });
+    }
+    
+    fn from_json(json: &Value) -> IntervalReport {
+        let number = |key: &str| json[key].as_u64().expect("Invalid interval report param");
+        return IntervalReport {
+            start_ms: number("start_ms"),
+            duration_ms: number("duration_ms"),
+            completed: number("completed"),
+            timed_out: number("timed_out"),
+            failed: json["failed"].as_u64().unwrap_or(0),
+            total_attempted: number("total_attempted"),
+            latency: LatencySummary::from_json(&json["latency"])
+        }
+    }
+}
+
+/// The counts of a phase are in records, an insert of a batch is one operation of
+/// many records. The latencies are one per operation.
+#[derive(Clone, Debug)]
+pub struct PhaseResult {
+    phase: String,
+    total: u64,
+    completed: u64,
+    /// Completed operations
+    operations: u64,
+    timed_out: u64,
+    failed: u64,
+    /// Faults injected into the operations
+    faults: FaultCounts,
+    /// Sum of the operation times, the duration also counts the time between operations
+    queries_time_ms: i64,
+    duration_ms: u64,
+    latency: LatencySummary,
+    histogram: LatencyHistogram,
+    intervals: Vec<IntervalReport>
+}
+
+impl PhaseResult {
+    pub fn new(phase: String,
+        total: u64,
+        completed: u64,
+        timed_out: u64,
+        duration_ms: u64,
+        histogram: LatencyHistogram,
+        intervals: Vec<IntervalReport>) -> PhaseResult {
+        
+        return PhaseResult {
+            phase: phase,
+            total: total,
+            completed: completed,
+            operations: histogram.get_count(),
+            timed_out: timed_out,
+            failed: 0,
+            faults: FaultCounts::default(),
+            queries_time_ms: histogram.get_sum(),
+            duration_ms: duration_ms,
+            latency: LatencySummary::from_histogram(&histogram),
+            histogram: histogram,
+            intervals: intervals
+        }
+    }
+    
+    /// Result that is only known from the totals of a phase and a sample of its
+    /// latencies, like a phase of the text logs. The operations ran one by one, so
+    /// the duration is the sum of their times.
+    pub fn from_totals(phase: String,
+        total: u64,
+        completed: u64,
+        operations: u64,
+        timed_out: u64,
+        queries_time_ms: i64,
+        histogram: LatencyHistogram) -> PhaseResult {
+        
+        let mut latency = LatencySummary::from_histogram(&histogram);
+        latency.average = if operations > 0 { queries_time_ms as f64/operations as f64 } else { 0.0 };
+        
+        return PhaseResult {
+            phase: phase,
+            total: total,
+            completed: completed,
+            operations: operations,
+            timed_out: timed_out,
+            failed: 0,
+            faults: FaultCounts::default(),
+            queries_time_ms: queries_time_ms,
+            duration_ms: queries_time_ms.max(0) as u64,
+            latency: latency,
+            histogram: histogram,
+            intervals: Vec::new()
+        }
+    }
+    
+    /// Sets the operations that failed with an error and the faults injected into them.
+    pub fn with_failed(mut self, failed: u64, faults: FaultCounts) -> PhaseResult {
+        self.failed = failed;
+        self.faults = faults;
+        return self
+    }
+    
+    pub fn get_phase(&self) -> &str {
+        return &self.phase
+    }
+    
+    pub fn get_total(&self) -> u64 {
+        return self.total
+    }
+    
+    pub fn get_completed(&self) -> u64 {
+        return self.completed
+    }
+    
+    pub fn get_operations(&self) -> u64 {
+        return self.operations
+    }
+    
+    pub fn get_timed_out(&self) -> u64 {
+        return self.timed_out
+    }
+    
+    /// Records of the operations that failed with an error.
+    pub fn get_failed(&self) -> u64 {
+        return self.failed
+    }
+    
+    pub fn get_faults(&self) -> &FaultCounts {
+        return &self.faults
+    }
+    
+    pub fn get_queries_time_ms(&self) -> i64 {
+        return self.queries_time_ms
+    }
+    
+    pub fn get_duration_ms(&self) -> u64 {
+        return self.duration_ms
+    }
+    
+    /// Completed records per second of wall time, points per second for inserts.
+    pub fn get_throughput(&self) -> f64 {
+        return compute_throughput(self.completed, self.duration_ms)
+    }
+    
+    /// Completed operations per second of wall time.
+    pub fn get_operation_throughput(&self) -> f64 {
+        return compute_throughput(self.operations, self.duration_ms)
+    }
+    
+    pub fn get_latency(&self) -> &LatencySummary {
+        return &self.latency
+    }
+    
+    pub fn get_histogram(&self) -> &LatencyHistogram {
+        return &self.histogram
+    }
+    
+    pub fn get_intervals(&self) -> &[IntervalReport] {
+        return &self.intervals
+    }
+    
+    /// Adds the operations of a repetition of the phase, its windows follow the
+    /// windows of this one.
+    fn merge(&mut self, other: &PhaseResult) {
+        let offset_ms = self.duration_ms;
+        self.total += other.total;
+        self.completed += other.completed;
+        self.operations += other.operations;
+        self.timed_out += other.timed_out;
+        self.failed += other.failed;
+        self.faults.add(&other.faults);
+        self.queries_time_ms += other.queries_time_ms;
+        self.duration_ms += other.duration_ms;
+        self.histogram.merge(&other.histogram);
+        self.latency = LatencySummary::from_histogram(&self.histogram);
+        self.latency.average = if self.operations > 0 { self.queries_time_ms as f64/self.operations as f64 } else { 0.0 };
+        self.intervals.extend(other.intervals.iter().map(|interval| interval.with_offset(offset_ms)));
+    }
+    
+    fn to_json(&self) -> Value {
+        return json!({
+            "phase": self.phase,
+            "total": self.total,
+            "completed": self.completed,
+            "operations": self.operations,
+            "timed_out": self.timed_out,
+            "failed": self.failed,
+            "faults": self.faults.to_json(),
+            "queries_time_ms": self.queries_time_ms,
+            "duration_ms": self.duration_ms,
+            "throughput": self.get_throughput(),
+            "operation_throughput": self.get_operation_throughput(),
+            "latency": self.latency.to_json(),
+            "histogram": self.histogram.to_json(),
+            "intervals": self.intervals.iter().map(IntervalReport::to_json).collect::<Vec<Value>>()
+        });
+    }
+    
+    fn from_json(json: &Value) -> PhaseResult {
+        let number = |key: &str| json[key].as_u64().expect("Invalid phase result param");
+        let histogram = LatencyHistogram::from_json(&json["histogram"]);
+        return PhaseResult {
+            phase: json["phase"].as_str().expect("Invalid phase result phase param").to_string(),
+            total: number("total"),
+            completed: number("completed"),
+            // Results of single record operations have no separate count
+            operations: json["operations"].as_u64().unwrap_or_else(|| histogram.get_count()),
+            timed_out: number("timed_out"),
+            failed: json["failed"].as_u64().unwrap_or(0),
+            faults: FaultCounts::from_json(&json["faults"]),
+            queries_time_ms: json["queries_time_ms"].as_i64().expect("Invalid phase result queries_time_ms param"),
+            duration_ms: number("duration_ms"),
+            latency: LatencySummary::from_json(&json["latency"]),
+            histogram: histogram,
+            intervals: json["intervals"].as_array().expect("Invalid phase result intervals param").iter()
+                .map(IntervalReport::from_json)
+                .collect()
+        }
+    }
+}
+
+/// Throughput and latency percentiles of a phase over the repetitions of a test case.
+#[derive(Clone, Debug)]
+pub struct PhaseStatistics {
+    phase: String,
+    throughput: Estimate,
+    p50: Estimate,
+    p95: Estimate,
+    p99: Estimate
+}
+
+impl PhaseStatistics {
+    fn from_phases(phase: &str, phases: &[&PhaseResult]) -> PhaseStatistics {
+        // Repetitions without latency samples have no percentiles to estimate
+        let estimate = |value: &dyn Fn(&PhaseResult) -> Option<f64>| -> Estimate {
+            return Estimate::from_samples(phases.iter().filter_map(|phase_result| value(phase_result)).collect());
+        };
+        return PhaseStatistics {
+            phase: phase.to_string(),
+            throughput: estimate(&|phase_result| Some(phase_result.get_throughput())),
+            p50: estimate(&|phase_result| phase_result.get_latency().get_p50().map(f64::from)),
+            p95: estimate(&|phase_result| phase_result.get_latency().get_p95().map(f64::from)),
+            p99: estimate(&|phase_result| phase_result.get_latency().get_p99().map(f64::from))
+        }
+    }
+    
+    pub fn get_phase(&self) -> &str {
+        return &self.phase
+    }
+    
+    pub fn get_repetitions(&self) -> usize {
+        return self.throughput.get_count()
+    }
+    
+    /// Name, unit and estimate of every measured value.
+    pub fn get_metrics(&self) -> [(&'static str, &'static str, &Estimate); 4] {
+        return [
+            ("throughput", "records/s", &self.throughput),
+            ("p50", "ms", &self.p50),
+            ("p95", "ms", &self.p95),
+            ("p99", "ms", &self.p99)
+        ]
+    }
+    
+    fn to_json(&self) -> Value {
+        return json!({
+            "phase": self.phase,
+            "throughput": self.throughput.to_json(),
+            "p50": self.p50.to_json(),
+            "p95": self.p95.to_json(),
+            "p99": self.p99.to_json()
+        });
+    }
+    
+    fn from_json(json: &Value) -> PhaseStatistics {
+        return PhaseStatistics {
+            phase: json["phase"].as_str().expect("Invalid phase statistics phase param").to_string(),
+            throughput: Estimate::from_json(&json["throughput"]),
+            p50: Estimate::from_json(&json["p50"]),
+            p95: Estimate::from_json(&json["p95"]),
+            p99: Estimate::from_json(&json["p99"])
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TestCaseResult {
+    id: usize,
+    /// Phases of all repetitions merged
+    phases: Vec<PhaseResult>,
+    /// Phases of every repetition
+    repetitions: Vec<Vec<PhaseResult>>,
+    /// Spread of the phases that ran more than once
+    statistics: Vec<PhaseStatistics>
+}
+
+impl TestCaseResult {
+    pub fn new(id: usize) -> TestCaseResult {
+        return TestCaseResult {
+            id: id,
+            phases: Vec::new(),
+            repetitions: Vec::new(),
+            statistics: Vec::new()
+        }
+    }
+    
+    pub fn get_id(&self) -> usize {
+        return self.id
+    }
+    
+    pub fn get_phases(&self) -> &[PhaseResult] {
+        return &self.phases
+    }
+    
+    pub fn get_phase(&self, phase: &str) -> Option<&PhaseResult> {
+        return self.phases.iter().find(|phase_result| phase_result.get_phase() == phase)
+    }
+    
+    pub fn get_statistics(&self) -> &[PhaseStatistics] {
+        return &self.statistics
+    }
+    
+    pub fn get_phase_statistics(&self, phase: &str) -> Option<&PhaseStatistics> {
+        return self.statistics.iter().find(|statistics| statistics.get_phase() == phase)
+    }
+    
+    /// Replaces the result of the same phase of the repetition, a resumed phase is
+    /// reported again. The merged phase and its statistics are computed again.
+    pub fn add_phase(&mut self, repetition: usize, phase_result: PhaseResult) {
+        let phase = phase_result.get_phase().to_string();
+        while self.repetitions.len() <= repetition {
+            self.repetitions.push(Vec::new());
+        }
+        self.repetitions[repetition].retain(|old| old.get_phase() != phase);
+        self.repetitions[repetition].push(phase_result);
+        
+        let repeated: Vec<&PhaseResult> = self.repetitions.iter()
+            .filter_map(|phases| phases.iter().find(|phase_result| phase_result.get_phase() == phase))
+            .collect();
+        let mut merged = repeated[0].clone();
+        for phase_result in &repeated[1..] {
+            merged.merge(phase_result);
+        }
+        self.phases.retain(|old| old.get_phase() != phase);
+        self.phases.push(merged);
+        
+        self.statistics.retain(|old| old.get_phase() != phase);
+        if repeated.len() > 1 {
+            self.statistics.push(PhaseStatistics::from_phases(&phase, &repeated));
+        }
+    }
+    
+    /// The repetitions are only written when there is more than one.
+    fn to_json(&self) -> Value {
+        let mut json = json!({
+            "id": self.id,
+            "phases": self.phases.iter().map(PhaseResult::to_json).collect::<Vec<Value>>()
+        });
+        if self.repetitions.len() > 1 {
+            json["repetitions"] = self.repetitions.iter()
+                .map(|phases| Value::Array(phases.iter().map(PhaseResult::to_json).collect()))
+                .collect();
+            json["statistics"] = self.statistics.iter().map(PhaseStatistics::to_json).collect();
+        }
+        return json;
+    }
+    
+    fn from_json(json: &Value) -> TestCaseResult {
+        let phases: Vec<PhaseResult> = json["phases"].as_array().expect("Invalid test case result phases param").iter()
+            .map(PhaseResult::from_json)
+            .collect();
+        let repetitions = match json["repetitions"].as_array() {
+            Some(repetitions) => repetitions.iter()
+                .map(|phases| phases.as_array().expect("Invalid test case result repetition param").iter()
+                    .map(PhaseResult::from_json)
+                    .collect())
+                .collect(),
+            None => vec![phases.clone()]
+        };
+        let statistics = json["statistics"].as_array().map_or(Vec::new(), |statistics| {
+            return statistics.iter().map(PhaseStatistics::from_json).collect();
+        });
+        
+        return TestCaseResult {
+            id: json["id"].as_u64().expect("Invalid test case result id param") as usize,
+            phases: phases,
+            repetitions: repetitions,
+            statistics: statistics
+        }
+    }
+}
+
+/// Results of one database.
+#[derive(Clone, Debug)]
+pub struct RunResult {
+    database: String,
+    start_time: u128,
+    /// Seed the test cases derive their data from, unknown for imported logs
+    seed: Option<u64>,
+    test_cases: Vec<TestCaseResult>
+}
+
+impl RunResult {
+    pub fn new(database: String, start_time: u128) -> RunResult {
+        return RunResult {
+            database: database,
+            start_time: start_time,
+            seed: None,
+            test_cases: Vec::new()
+        }
+    }
+    
+    pub fn get_database(&self) -> &str {
+        return &self.database
+    }
+    
+    pub fn get_start_time(&self) -> u128 {
+        return self.start_time
+    }
+    
+    pub fn get_seed(&self) -> Option<u64> {
+        return self.seed
+    }
+    
+    pub fn get_test_cases(&self) -> &[TestCaseResult] {
+        return &self.test_cases
+    }
+    
+    pub fn get_test_case(&self, id: usize) -> Option<&TestCaseResult> {
+        return self.test_cases.iter().find(|test_case| test_case.get_id() == id)
+    }
+    
+    pub fn get_test_case_mut(&mut self, id: usize) -> &mut TestCaseResult {
+        if let Some(pos) = self.test_cases.iter().position(|test_case| test_case.get_id() == id) {
+            return &mut self.test_cases[pos]
+        }
+        self.test_cases.push(TestCaseResult::new(id));
+        return self.test_cases.last_mut().unwrap()
+    }
+    
+    fn to_json(&self) -> Value {
+        let mut json = json!({
+            "database": self.database,
+            "start_time": self.start_time as u64,
+            "test_cases": self.test_cases.iter().map(TestCaseResult::to_json).collect::<Vec<Value>>()
+        });
+        if let Some(seed) = self.seed {
+            json["seed"] = json!(seed);
+        }
+        return json;
+    }
+    
+    fn from_json(json: &Value) -> RunResult {
+        return RunResult {
+            database: json["database"].as_str().expect("Invalid run result database param").to_string(),
+            start_time: json["start_time"].as_u64().expect("Invalid run result start_time param") as u128,
+            seed: json["seed"].as_u64(),
+            test_cases: json["test_cases"].as_array().expect("Invalid run result test_cases param").iter()
+                .map(TestCaseResult::from_json)
+                .collect()
+        }
+    }
+}
+
+/// Contents of a results file, the runs of one or more databases.
+#[derive(Clone, Debug, Default)]
+pub struct Results {
+    runs: Vec<RunResult>
+}
+
+impl Results {
+    pub fn new() -> Results {
+        return Results {
+            runs: Vec::new()
+        }
+    }
+    
+    pub fn get_runs(&self) -> &[RunResult] {
+        return &self.runs
+    }
+    
+    pub fn add_run(&mut self, run: RunResult) {
+        self.runs.push(run);
+    }
+    
+    /// Returns the last run of `database`, a new run started at `start_time` when there is none.
+    pub fn get_run_mut(&mut self, database: &str, start_time: u128) -> &mut RunResult {
+        if let Some(pos) = self.runs.iter().rposition(|run| run.get_database() == database) {
+            return &mut self.runs[pos]
+        }
+        self.runs.push(RunResult::new(database.to_string(), start_time));
+        return self.runs.last_mut().unwrap()
+    }
+    
+    pub fn load(file_path: &str) -> Results {
+        let mut file = File::open(file_path).expect("Can't open results file");
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).expect("Can't read results file");
+        
+        let json: Value = serde_json::from_str(&contents).expect("Invalid results json format");
+        return Results {
+            runs: json["runs"].as_array().expect("Invalid results runs param").iter()
+                .map(RunResult::from_json)
+                .collect()
+        }
+    }
+    
+    pub fn save(&self, file_path: &str) {
+        let json = json!({
+            "runs": self.runs.iter().map(RunResult::to_json).collect::<Vec<Value>>()
+        });
+        let temp_file_path = format!("{}.tmp", file_path);
+        let mut file = File::create(&temp_file_path).expect("Can't create results file");
+        let contents = serde_json::to_string_pretty(&json).expect("Can't serialize results");
+        file.write_all(contents.as_bytes()).expect("Can't write results file");
+        fs::rename(&temp_file_path, file_path).expect("Can't replace results file");
+    }
+}
+
+/// Keeps the results of a run in a file that is rewritten after every phase.
+pub struct ResultsWriter {
+    file_path: String,
+    results: Results,
+    run_pos: usize
+}
+
+impl ResultsWriter {
+    /// A resumed run continues the last run of `database` in the file.
+    pub fn new(file_path: String, database: String, start_time: u128, is_resume: bool) -> ResultsWriter {
+        let mut results = if is_resume && fs::metadata(&file_path).is_ok() {
+            Results::load(&file_path)
+        } else {
+            Results::new()
+        };
+        let run_pos = match results.runs.iter().rposition(|run| run.get_database() == database) {
+            Some(run_pos) if is_resume => run_pos,
+            _ => {
+                results.runs.push(RunResult::new(database, start_time));
+                results.runs.len()-1
+            }
+        };
+        
+        return ResultsWriter {
+            file_path: file_path,
+            results: results,
+            run_pos: run_pos
+        }
+    }
+    
+    pub fn get_run(&self) -> &RunResult {
+        return &self.results.runs[self.run_pos]
+    }
+    
+    /// Keeps the seed of a new run, a resumed run keeps the seed it started with.
+    pub fn init_seed(&mut self, seed: u64) {
+        let run = &mut self.results.runs[self.run_pos];
+        if run.seed.is_none() {
+            run.seed = Some(seed);
+        }
+    }
+    
+    pub fn get_test_case(&self, test_case_id: usize) -> Option<&TestCaseResult> {
+        return self.results.runs[self.run_pos].get_test_case(test_case_id)
+    }
+    
+    pub fn add_phase(&mut self, test_case_id: usize, repetition: usize, phase_result: PhaseResult) {
+        self.results.runs[self.run_pos].get_test_case_mut(test_case_id).add_phase(repetition, phase_result);
+        self.results.save(&self.file_path);
+    }
+}
+
+/// When the interval reports of a phase are closed, `None` disables a limit.
+#[derive(Clone, Copy, Debug)]
+pub struct ReportIntervals {
+    time: Option<u64>,
+    operations: Option<u64>
+}
+
+impl ReportIntervals {
+    /// `time` is in milliseconds.
+    pub fn new(time: Option<u64>, operations: Option<u64>) -> ReportIntervals {
+        return ReportIntervals {
+            time: time,
+            operations: operations
+        }
+    }
+    
+    pub fn get_time(&self) -> Option<Duration> {
+        return self.time.map(Duration::from_millis)
+    }
+    
+    pub fn get_operations(&self) -> Option<u64> {
+        return self.operations
+    }
+}
+
+/// Collects the latencies of a phase and closes an interval report whenever the
+/// time or the operations of the window reach their limit.
+#[derive(Clone, Debug)]
+pub struct PhaseRecorder {
+    /// One latency per operation, the counts are in records
+    histogram: LatencyHistogram,
+    completed: u64,
+    timed_out: u64,
+    failed: u64,
+    faults: FaultCounts,
+    intervals: Vec<IntervalReport>,
+    window: LatencyHistogram,
+    window_completed: u64,
+    window_timed_out: u64,
+    window_failed: u64,
+    window_start_ms: u64,
+    /// Phase time of the runs before a resume
+    resumed_elapsed_ms: u64,
+    start_time: Instant,
+    report_intervals: ReportIntervals
+}
+
+impl PhaseRecorder {
+    pub fn new() -> PhaseRecorder {
+        return PhaseRecorder {
+            histogram: LatencyHistogram::new(),
+            completed: 0,
+            timed_out: 0,
+            failed: 0,
+            faults: FaultCounts::default(),
+            intervals: Vec::new(),
+            window: LatencyHistogram::new(),
+            window_completed: 0,
+            window_timed_out: 0,
+            window_failed: 0,
+            window_start_ms: 0,
+            resumed_elapsed_ms: 0,
+            start_time: Instant::now(),
+            report_intervals: ReportIntervals::new(None, None)
+        }
+    }
+    
+    /// Starts the clock of the phase, again after a resume.
+    pub fn start(&mut self, report_intervals: ReportIntervals) {
+        self.start_time = Instant::now();
+        self.report_intervals = report_intervals;
+    }
+    
+    fn get_elapsed_ms(&self) -> u64 {
+        return self.resumed_elapsed_ms+self.start_time.elapsed().as_millis() as u64
+    }
+    
+    fn get_attempted(&self) -> u64 {
+        return self.completed+self.timed_out+self.failed
+    }
+    
+    fn get_window_operations(&self) -> u64 {
+        return self.window_completed+self.window_timed_out+self.window_failed
+    }
+    
+    /// Counts an operation of `count` records with one latency sample, `latency`
+    /// is `None` when it timed out.
+    pub fn record(&mut self, latency: Option<i32>, count: usize) {
+        let count = count as u64;
+        match latency {
+            Some(latency) => {
+                self.histogram.record(latency);
+                self.window.record(latency);
+                self.completed += count;
+                self.window_completed += count;
+            },
+            None => {
+                self.timed_out += count;
+                self.window_timed_out += count;
+            }
+        }
+        self.close_window_if_full();
+    }
+    
+    /// Counts an operation of `count` records that failed with an error.
+    pub fn record_failed(&mut self, count: usize) {
+        self.failed += count as u64;
+        self.window_failed += count as u64;
+        self.close_window_if_full();
+    }
+    
+    pub fn record_faults(&mut self, faults: &FaultCounts) {
+        self.faults.add(faults);
+    }
+    
+    fn close_window_if_full(&mut self) {
+        let window_operations = self.get_window_operations();
+        let elapsed_ms = self.get_elapsed_ms();
+        let is_time_full = self.report_intervals.get_time()
+            .is_some_and(|time| elapsed_ms-self.window_start_ms >= time.as_millis() as u64);
+        let is_operations_full = self.report_intervals.get_operations()
+            .is_some_and(|operations| window_operations >= operations);
+        if is_time_full || is_operations_full {
+            self.close_window(elapsed_ms);
+        }
+    }
+    
+    fn close_window(&mut self, elapsed_ms: u64) {
+        if self.get_window_operations() == 0 {
+            return
+        }
+        let report = IntervalReport::new(self.window_start_ms,
+            elapsed_ms-self.window_start_ms,
+            self.window_completed,
+            self.window_timed_out,
+            self.window_failed,
+            self.get_attempted(),
+            &self.window);
+        self.intervals.push(report);
+        self.window = LatencyHistogram::new();
+        self.window_completed = 0;
+        self.window_timed_out = 0;
+        self.window_failed = 0;
+        self.window_start_ms = elapsed_ms;
+    }
+    
+    /// Closes the last window and returns the result of the phase.
+    pub fn finish(&mut self, phase: &str, total: u64) -> PhaseResult {
+        let elapsed_ms = self.get_elapsed_ms();
+        self.close_window(elapsed_ms);
+        return PhaseResult::new(phase.to_string(),
+            total,
+            self.completed,
+            self.timed_out,
+            elapsed_ms,
+            self.histogram.clone(),
+            self.intervals.clone())
+            .with_failed(self.failed, self.faults)
+    }
+    
+    pub fn to_json(&self) -> Value {
+        return json!({
+            "histogram": self.histogram.to_json(),
+            "completed": self.completed,
+            "timed_out": self.timed_out,
+            "failed": self.failed,
+            "faults": self.faults.to_json(),
+            "intervals": self.intervals.iter().map(IntervalReport::to_json).collect::<Vec<Value>>(),
+            "window": self.window.to_json(),
+            "window_completed": self.window_completed,
+            "window_timed_out": self.window_timed_out,
+            "window_failed": self.window_failed,
+            "window_start_ms": self.window_start_ms,
+            "elapsed_ms": self.get_elapsed_ms()
+        });
+    }
+    
+    pub fn from_json(json: &Value) -> PhaseRecorder {
+        let number = |key: &str| json[key].as_u64().expect("Invalid phase recorder param");
+        let mut recorder = PhaseRecorder::new();
+        recorder.histogram = LatencyHistogram::from_json(&json["histogram"]);
+        recorder.completed = json["completed"].as_u64().unwrap_or_else(|| recorder.histogram.get_count());
+        recorder.timed_out = number("timed_out");
+        recorder.failed = json["failed"].as_u64().unwrap_or(0);
+        recorder.faults = FaultCounts::from_json(&json["faults"]);
+        recorder.intervals = json["intervals"].as_array().expect("Invalid phase recorder intervals param").iter()
+            .map(IntervalReport::from_json)
+            .collect();
+        recorder.window = LatencyHistogram::from_json(&json["window"]);
+        recorder.window_completed = json["window_completed"].as_u64().unwrap_or_else(|| recorder.window.get_count());
+        recorder.window_timed_out = number("window_timed_out");
+        recorder.window_failed = json["window_failed"].as_u64().unwrap_or(0);
+        recorder.window_start_ms = number("window_start_ms");
+        recorder.resumed_elapsed_ms = number("elapsed_ms");
+        return recorder;
+    }
+}
+
+pub fn compute_throughput(operations: u64, duration_ms: u64) -> f64 {
+    if duration_ms == 0 {
+        return 0.0
+    }
+    return operations as f64*1000.0/duration_ms as f64;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn recorder_closes_an_interval_when_its_operations_are_full() {
+        let mut recorder = PhaseRecorder::new();
+        recorder.start(ReportIntervals::new(None, Some(20)));
+        recorder.record(Some(5), 10);
+        recorder.record(Some(7), 10);
+        recorder.record(None, 5);
+        recorder.record_failed(5);
+        let result = recorder.finish("points", 30);
+        
+        let intervals = result.get_intervals();
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(intervals[0].get_completed(), 20);
+        assert_eq!(intervals[0].get_total_attempted(), 20);
+        assert_eq!(intervals[0].get_latency().get_max(), Some(7));
+        assert_eq!(intervals[1].get_completed(), 0);
+        assert_eq!(intervals[1].get_timed_out(), 5);
+        assert_eq!(intervals[1].get_failed(), 5);
+        assert_eq!(intervals[1].get_total_attempted(), 30);
+        assert_eq!(intervals[1].get_latency().get_p50(), None);
+        
+        assert_eq!(result.get_total(), 30);
+        assert_eq!(result.get_completed(), 20);
+        assert_eq!(result.get_operations(), 2);
+        assert_eq!(result.get_timed_out(), 5);
+        assert_eq!(result.get_failed(), 5);
+        assert_eq!(result.get_histogram().get_count(), 2);
+        assert_eq!(result.get_latency().get_p50(), Some(7));
+        assert_eq!(result.get_latency().get_average(), 6.0);
+    }
+    
+    #[test]
+    fn recorder_without_samples_has_no_percentiles() {
+        let mut recorder = PhaseRecorder::new();
+        recorder.record(None, 3);
+        let result = recorder.finish("select", 3);
+        assert_eq!(result.get_latency().get_p50(), None);
+        assert_eq!(result.get_latency().get_max(), None);
+        assert_eq!(result.get_intervals().len(), 1);
+        
+        let json = result.to_json();
+        assert!(json["latency"]["p99"].is_null());
+        assert_eq!(PhaseResult::from_json(&json).get_latency().get_p99(), None);
+    }
+    
+    #[test]
+    fn histogram_percentiles_use_the_nearest_rank() {
+        let mut histogram = LatencyHistogram::new();
+        assert_eq!(histogram.get_percentile(50.0), 0);
+        for latency in 1..101 {
+            histogram.record(latency);
+        }
+        histogram.record_count(1000, 1);
+        assert_eq!(histogram.get_percentile(50.0), 51);
+        assert_eq!(histogram.get_percentile(99.0), 100);
+        assert_eq!(histogram.get_percentile(100.0), 1000);
+        assert_eq!(histogram.get_max(), 1000);
+    }
+    
+    #[test]
+    fn recorder_continues_from_json() {
+        let mut recorder = PhaseRecorder::new();
+        recorder.start(ReportIntervals::new(None, Some(4)));
+        recorder.record(Some(3), 4);
+        recorder.record(Some(9), 2);
+        
+        let mut resumed = PhaseRecorder::from_json(&recorder.to_json());
+        resumed.start(ReportIntervals::new(None, Some(4)));
+        resumed.record(Some(1), 2);
+        let result = resumed.finish("points", 8);
+        assert_eq!(result.get_completed(), 8);
+        assert_eq!(result.get_operations(), 3);
+        assert_eq!(result.get_intervals().len(), 2);
+        assert_eq!(result.get_intervals()[1].get_completed(), 4);
+        assert_eq!(result.get_latency().get_max(), Some(9));
+    }
+}
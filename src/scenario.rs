@@ -0,0 +1,535 @@
+use {get_series_tag, is_cancelled, write_faults, write_log, write_reconnect_events, Point, Query, QueryType, TestCase, TestEnviroment, RANGE_FRACTION};
+use checkpoint::{Checkpoint, OperationStats};
+use progress::Progress;
+use rand::Rng;
+use scheduler::PointScheduler;
+use serde_json::Value;
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+use timed_executor::OperationResult;
+
+
+/// Series whose newest point a wait phase polls
+static WAIT_SAMPLE_SIZE: usize = 10;
+static WAIT_POLL_INTERVAL_MS: u64 = 100;
+static DEFAULT_WAIT_TIMEOUT_MS: u64 = 60000;
+static SLEEP_CHECK_INTERVAL_MS: u64 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhaseKind {
+    /// Writes the next points of every series
+    Load,
+    /// Waits until the database returns the newest points
+    Wait,
+    /// Runs selects
+    Query,
+    /// Writes the next points of every series while running selects
+    Mixed,
+    /// Deletes the oldest points of every series
+    Delete,
+    /// Compacts the storage of the points
+    Compact,
+    /// Pauses the workload
+    Sleep
+}
+
+impl PhaseKind {
+    pub fn as_str(&self) -> &'static str {
+        return match *self {
+            PhaseKind::Load => "load",
+            PhaseKind::Wait => "wait",
+            PhaseKind::Query => "query",
+            PhaseKind::Mixed => "mixed",
+            PhaseKind::Delete => "delete",
+            PhaseKind::Compact => "compact",
+            PhaseKind::Sleep => "sleep"
+        }
+    }
+    
+    fn parse(kind: &str) -> Option<PhaseKind> {
+        return match kind {
+            "load" => Some(PhaseKind::Load),
+            "wait" => Some(PhaseKind::Wait),
+            "query" => Some(PhaseKind::Query),
+            "mixed" => Some(PhaseKind::Mixed),
+            "delete" => Some(PhaseKind::Delete),
+            "compact" => Some(PhaseKind::Compact),
+            "sleep" => Some(PhaseKind::Sleep),
+            _ => None
+        }
+    }
+}
+
+/// Phase of a scenario with its own workload. A phase ends when its operations are
+/// done or its duration passes, whichever comes first.
+#[derive(Clone, Debug)]
+pub struct ScenarioPhase {
+    kind: PhaseKind,
+    /// Points of every series that a load or mixed phase writes or a delete phase deletes
+    points_per_series: usize,
+    /// Selects of a query phase, `None` runs them for the whole duration
+    queries: Option<usize>,
+    query_type: QueryType,
+    /// Share of the operations of a mixed phase that are inserts, the others are selects
+    insert_share: f64,
+    /// Operations per second, `None` is unlimited
+    rate: Option<f64>,
+    concurrency: usize,
+    batch_size: usize,
+    duration: Option<Duration>
+}
+
+/// Parses the `phases` of a test case, the params a phase leaves out are taken
+/// from the test case.
+pub fn parse_phases(json: &Value, batch_size: usize, concurrency: usize, query_type: QueryType) -> Vec<ScenarioPhase> {
+    return json.as_array().expect("Invalid test_case phases param").iter()
+        .map(|phase_json| parse_phase(phase_json, batch_size, concurrency, query_type))
+        .collect()
+}
+
+fn parse_phase(json: &Value, batch_size: usize, concurrency: usize, query_type: QueryType) -> ScenarioPhase {
+    let kind_name = json["type"].as_str().expect("Invalid phase type param");
+    let kind = PhaseKind::parse(kind_name).unwrap_or_else(|| panic!("Invalid phase type variant: {}", kind_name));
+    let query_type = match json["query_type"].as_str() {
+        None => query_type,
+        Some(query_type) => QueryType::parse(query_type)
+            .unwrap_or_else(|| panic!("Invalid phase query_type variant: {}", query_type))
+    };
+    let inserts = json["mix"]["insert"].as_f64().unwrap_or(1.0);
+    let selects = json["mix"]["select"].as_f64().unwrap_or(1.0);
+    if inserts < 0.0 || selects < 0.0 || inserts+selects <= 0.0 {
+        panic!("Invalid phase mix param");
+    }
+    
+    let phase = ScenarioPhase {
+        kind: kind,
+        points_per_series: json["points_per_series"].as_u64().unwrap_or(0) as usize,
+        queries: json["queries"].as_u64().map(|queries| queries as usize),
+        query_type: query_type,
+        insert_share: inserts/(inserts+selects),
+        rate: json["rate"].as_f64().filter(|rate| *rate > 0.0),
+        concurrency: cmp::max(json["concurrency"].as_u64().map_or(concurrency, |v| v as usize), 1),
+        batch_size: cmp::max(json["batch_size"].as_u64().map_or(batch_size, |v| v as usize), 1),
+        duration: json["duration"].as_u64().map(Duration::from_millis)
+    };
+    let is_bounded = match kind {
+        PhaseKind::Load | PhaseKind::Mixed | PhaseKind::Delete => phase.points_per_series > 0,
+        PhaseKind::Query => phase.queries.is_some() || phase.duration.is_some(),
+        PhaseKind::Sleep => phase.duration.is_some(),
+        PhaseKind::Wait | PhaseKind::Compact => true
+    };
+    if !is_bounded {
+        panic!("Invalid {} phase, its points_per_series, queries or duration param is missing", kind_name);
+    }
+    return phase;
+}
+
+/// Points of every series that the load and mixed phases write.
+pub fn get_points_per_series(phases: &[ScenarioPhase]) -> usize {
+    return phases.iter()
+        .filter(|phase| phase.kind == PhaseKind::Load || phase.kind == PhaseKind::Mixed)
+        .map(|phase| phase.points_per_series)
+        .sum()
+}
+
+enum ScenarioOperation {
+    Insert(Vec<Point>),
+    Select(String, Query),
+    Delete(String, i64),
+    Compact
+}
+
+impl ScenarioOperation {
+    fn get_kind(&self) -> &'static str {
+        return match *self {
+            ScenarioOperation::Insert(_) => "insert",
+            ScenarioOperation::Select(_, _) => "select",
+            ScenarioOperation::Delete(_, _) => "delete",
+            ScenarioOperation::Compact => "compact"
+        }
+    }
+    
+    /// Records of the operation, every point of an insert is one.
+    fn get_count(&self) -> usize {
+        return match *self {
+            ScenarioOperation::Insert(ref points) => points.len(),
+            _ => 1
+        }
+    }
+}
+
+/// Runs the phases of the scenario from the one of the checkpoint. A checkpoint is
+/// saved before every phase, so a resumed run starts the interrupted phase again,
+/// except for a load or mixed phase. Those save checkpoints while they run and a
+/// resumed one continues with the points it has not written yet.
+pub fn run_scenario(test_env: &mut TestEnviroment, state: &mut Checkpoint, test_case: &TestCase, phases: &[ScenarioPhase]) {
+    state.series_count = test_case.get_series();
+    let point_interval = test_case.get_point_interval() as i64;
+    while state.step < phases.len() && !is_cancelled() {
+        let phase = &phases[state.step];
+        let name = format!("{}-{}", state.step+1, phase.kind.as_str());
+        test_env.save_checkpoint(state);
+        test_env.executor.set_concurrency(phase.concurrency);
+        println!("Begin phase {}", name);
+        write_log(&mut test_env.log_file, format_args!("# Begin {} phase\n", name));
+        
+        match phase.kind {
+            PhaseKind::Load => run_load(test_env, state, test_case, phase, &name),
+            PhaseKind::Wait => run_wait(test_env, state, phase, &name, point_interval),
+            PhaseKind::Query => {
+                let mut issued = 0;
+                run_operations(test_env, state, phase, &name, phase.queries, |state| {
+                    if phase.queries.is_some_and(|queries| issued >= queries) || state.series_count == 0 {
+                        return None
+                    }
+                    issued += 1;
+                    return Some(next_select(state, phase.query_type, point_interval));
+                });
+            },
+            PhaseKind::Mixed => run_load(test_env, state, test_case, phase, &name),
+            PhaseKind::Delete => run_delete(test_env, state, phase, &name, point_interval),
+            PhaseKind::Compact => {
+                let mut is_started = false;
+                run_operations(test_env, state, phase, &name, Some(1), |_| {
+                    if is_started {
+                        return None
+                    }
+                    is_started = true;
+                    return Some(ScenarioOperation::Compact);
+                });
+            },
+            PhaseKind::Sleep => run_sleep(test_env, phase, &name)
+        }
+        
+        if !is_cancelled() {
+            state.step += 1;
+            state.scheduler = None;
+            state.operation_stats.clear();
+        }
+    }
+}
+
+/// Writes the next points of every series in the ingest order of the test case,
+/// a mixed phase runs selects of the points written before it in between. The
+/// schedule of the points is kept in the checkpoint, a resumed phase continues it.
+fn run_load(test_env: &mut TestEnviroment, state: &mut Checkpoint, test_case: &TestCase, phase: &ScenarioPhase, name: &str) {
+    let first_point = state.written_points;
+    let point_interval = test_case.get_point_interval() as i64;
+    if state.scheduler.is_none() {
+        let order = test_case.get_ingest_order().get_schedule_order();
+        let scheduler_seed = state.rng.gen::<u64>();
+        state.scheduler = Some(PointScheduler::new(order, state.series_count, phase.points_per_series, scheduler_seed));
+    }
+    // The selects of a mixed phase are counted too, so only a load phase has a total
+    let total = match phase.kind {
+        PhaseKind::Mixed => None,
+        _ => Some(state.series_count*phase.points_per_series)
+    };
+    
+    run_operations(test_env, state, phase, name, total, |state| {
+        if phase.kind == PhaseKind::Mixed && state.series_count > 0 && state.rng.gen::<f64>() >= phase.insert_share {
+            return Some(next_select(state, phase.query_type, point_interval));
+        }
+        let mut points = Vec::with_capacity(phase.batch_size);
+        while points.len() < phase.batch_size {
+            let (series, point) = match state.scheduler.as_mut().and_then(Iterator::next) {
+                Some(next) => next,
+                None => break
+            };
+            let (hash, _) = get_series_tag(state.seed, series);
+            let timestamp = state.start_timestamp+(first_point+point) as i64*point_interval;
+            points.push(Point::new(hash, series, timestamp));
+        }
+        if points.is_empty() {
+            return None
+        }
+        return Some(ScenarioOperation::Insert(points));
+    });
+    // The next phases continue after the planned points, even if the phase ended early
+    if !is_cancelled() {
+        state.written_points += phase.points_per_series;
+    }
+}
+
+/// Select of a random series, a range covers a part of the points that are
+/// written and not deleted.
+fn next_select(state: &mut Checkpoint, query_type: QueryType, point_interval: i64) -> ScenarioOperation {
+    let (hash, _) = get_series_tag(state.seed, state.rng.gen_range(0, state.series_count));
+    let points = cmp::max(state.written_points.saturating_sub(state.deleted_points), 1);
+    let query = match query_type {
+        QueryType::Series => Query::Series,
+        QueryType::Last => Query::Last,
+        QueryType::Range => {
+            let range_points = cmp::max(points/RANGE_FRACTION, 1);
+            let first_point = state.deleted_points+state.rng.gen_range(0, points-range_points+1);
+            let from = state.start_timestamp+first_point as i64*point_interval;
+            Query::Range(from, from+range_points as i64*point_interval)
+        }
+    };
+    return ScenarioOperation::Select(hash, query);
+}
+
+/// Deletes the oldest points of every series with one delete per series.
+fn run_delete(test_env: &mut TestEnviroment, state: &mut Checkpoint, phase: &ScenarioPhase, name: &str, point_interval: i64) {
+    let end_point = cmp::min(state.deleted_points+phase.points_per_series, state.written_points);
+    let before = state.start_timestamp+end_point as i64*point_interval;
+    let mut series = 0;
+    
+    run_operations(test_env, state, phase, name, Some(state.series_count), |state| {
+        if series >= state.series_count {
+            return None
+        }
+        let (hash, _) = get_series_tag(state.seed, series);
+        series += 1;
+        return Some(ScenarioOperation::Delete(hash, before));
+    });
+    if !is_cancelled() {
+        state.deleted_points = end_point;
+    }
+}
+
+/// Polls the newest point of sampled series until the database returns the last
+/// written one for all of them, at most for the duration of the phase.
+fn run_wait(test_env: &mut TestEnviroment, state: &mut Checkpoint, phase: &ScenarioPhase, name: &str, point_interval: i64) {
+    if state.series_count == 0 || state.written_points == 0 {
+        write_log(&mut test_env.log_file, format_args!("# {} phase has no points to wait for\n", name));
+        return
+    }
+    let last_timestamp = state.start_timestamp+(state.written_points-1) as i64*point_interval;
+    let timeout = phase.duration.unwrap_or(Duration::from_millis(DEFAULT_WAIT_TIMEOUT_MS));
+    let sample_size = cmp::min(WAIT_SAMPLE_SIZE, state.series_count);
+    let mut pending: Vec<String> = (0..sample_size)
+        .map(|_| get_series_tag(state.seed, state.rng.gen_range(0, state.series_count)).0)
+        .collect();
+    
+    let start_time = Instant::now();
+    while !pending.is_empty() && !is_cancelled() && start_time.elapsed() < timeout {
+        let hash = pending[pending.len()-1].clone();
+        if matches!(test_env.executor.get_last_timestamp(hash), Ok(Some(timestamp)) if timestamp >= last_timestamp) {
+            pending.pop();
+            continue
+        }
+        thread::sleep(Duration::from_millis(WAIT_POLL_INTERVAL_MS));
+    }
+    
+    let elapsed_ms = start_time.elapsed().as_millis();
+    if pending.is_empty() {
+        write_log(&mut test_env.log_file, format_args!("# {} phase, the last points are returned after {} ms\n", name, elapsed_ms));
+    } else {
+        write_log(&mut test_env.log_file,
+            format_args!("# {} phase stopped after {} ms, {} of {} sampled series miss their last point\n",
+            name,
+            elapsed_ms,
+            pending.len(),
+            sample_size));
+    }
+}
+
+fn run_sleep(test_env: &mut TestEnviroment, phase: &ScenarioPhase, name: &str) {
+    let duration = phase.duration.unwrap_or_default();
+    let start_time = Instant::now();
+    while !is_cancelled() && start_time.elapsed() < duration {
+        thread::sleep(cmp::min(duration.saturating_sub(start_time.elapsed()), Duration::from_millis(SLEEP_CHECK_INTERVAL_MS)));
+    }
+    write_log(&mut test_env.log_file, format_args!("# {} phase slept for {} ms\n", name, start_time.elapsed().as_millis()));
+}
+
+/// Starts the operations of `next_operation` on the idle executors until it returns
+/// `None` or the duration of the phase passes, at most `rate` operations per second.
+/// `total` is the number of records shown by the progress, `None` when the phase
+/// has no count of them. Every kind of operation
+/// gets its own results, named after the phase and in a mixed phase after the kind.
+/// A load or mixed phase saves checkpoints while no operation is running, a resumed
+/// one continues with the results of the checkpoint.
+fn run_operations<F>(test_env: &mut TestEnviroment,
+    state: &mut Checkpoint,
+    phase: &ScenarioPhase,
+    name: &str,
+    total: Option<usize>,
+    mut next_operation: F)
+    where F: FnMut(&mut Checkpoint) -> Option<ScenarioOperation> {
+    
+    let is_resumable = phase.kind == PhaseKind::Load || phase.kind == PhaseKind::Mixed;
+    let start_time = Instant::now();
+    let end_time = phase.duration.map(|duration| start_time+duration);
+    let resumed_attempted = state.operation_stats.iter().map(|operation_stats| operation_stats.stats.get_attempted()).sum();
+    let mut progress = Progress::new(phase.kind.as_str(), total, resumed_attempted, test_env.progress_interval)
+        .with_end_time(end_time);
+    for operation_stats in state.operation_stats.iter_mut() {
+        operation_stats.recorder.start(test_env.report_intervals);
+    }
+    // Tag, kind position and number of records of the running operations
+    let mut running: Vec<(usize, usize, usize)> = Vec::new();
+    let mut issued = 0;
+    let mut is_done = false;
+    loop {
+        if is_resumable && running.is_empty() {
+            test_env.save_checkpoint_if_due(state);
+        }
+        // The rate allows the next operation at this time
+        let mut next_time = None;
+        while !is_done && test_env.executor.has_idle_worker() && !(is_resumable && test_env.is_checkpoint_due()) {
+            let now = Instant::now();
+            if is_cancelled() || end_time.is_some_and(|end_time| now >= end_time) {
+                is_done = true;
+                break
+            }
+            if let Some(rate) = phase.rate {
+                let issue_time = start_time+Duration::from_secs_f64(issued as f64/rate);
+                if now < issue_time {
+                    next_time = Some(issue_time);
+                    break
+                }
+            }
+            let operation = match next_operation(state) {
+                Some(operation) => operation,
+                None => {
+                    is_done = true;
+                    break
+                }
+            };
+            
+            let kind = operation.get_kind();
+            let kind_pos = match state.operation_stats.iter().position(|operation_stats| operation_stats.kind == kind) {
+                Some(kind_pos) => kind_pos,
+                None => {
+                    let mut operation_stats = OperationStats::new(kind);
+                    operation_stats.recorder.start(test_env.report_intervals);
+                    state.operation_stats.push(operation_stats);
+                    state.operation_stats.len()-1
+                }
+            };
+            running.push((issued, kind_pos, operation.get_count()));
+            match operation {
+                ScenarioOperation::Insert(points) => test_env.executor.start_insert(points, issued),
+                ScenarioOperation::Select(hash, query) => test_env.executor.start_select(hash, query, issued),
+                ScenarioOperation::Delete(hash, before) => test_env.executor.start_delete(hash, before, issued),
+                ScenarioOperation::Compact => test_env.executor.start_compact(issued)
+            }
+            issued += 1;
+        }
+        if is_done && running.is_empty() {
+            break
+        }
+        
+        let (tag, result) = match test_env.executor.wait_next_until(next_time) {
+            Some(response) => response,
+            None => continue
+        };
+        let pos = running.iter().position(|operation| operation.0 == tag).expect("Unknown scenario operation");
+        let (_, kind_pos, count) = running.swap_remove(pos);
+        let operation_stats = &mut state.operation_stats[kind_pos];
+        operation_stats.recorder.record_faults(&test_env.executor.take_fault_counts());
+        match result {
+            OperationResult::Done(query_time) => {
+                operation_stats.stats.completed += count;
+                operation_stats.stats.operations += 1;
+                operation_stats.stats.queries_time_ms += query_time as i64;
+                operation_stats.recorder.record(Some(query_time), count);
+                progress.record(Some(query_time), count);
+            },
+            OperationResult::Failed(err) => {
+                operation_stats.stats.failed += count;
+                operation_stats.recorder.record_failed(count);
+                progress.record(None, count);
+                write_log(&mut test_env.log_file, format_args!("{} {} query failed: {:?}\n", name, operation_stats.kind, err));
+            },
+            OperationResult::TimedOut => {
+                operation_stats.stats.timed_out += count;
+                operation_stats.recorder.record(None, count);
+                progress.record(None, count);
+            }
+        }
+        write_reconnect_events(&mut test_env.log_file, test_env.executor.take_reconnect_events());
+    }
+    progress.finish();
+    
+    // The running operations are finished when cancelled, but the phase is not
+    let is_stopped = is_cancelled();
+    for operation_stats in state.operation_stats.iter_mut() {
+        let result_name = if phase.kind == PhaseKind::Mixed {
+            format!("{}-{}", name, operation_stats.kind)
+        } else {
+            name.to_string()
+        };
+        write_log(&mut test_env.log_file,
+            format_args!("# {} {} {} queries for {} ms, {} records, timed out: {}, failed: {}\n",
+            result_name,
+            operation_stats.kind,
+            operation_stats.stats.operations,
+            operation_stats.stats.queries_time_ms,
+            operation_stats.stats.completed,
+            operation_stats.stats.timed_out,
+            operation_stats.stats.failed));
+        if !is_stopped {
+            let phase_result = operation_stats.recorder.finish(&result_name, operation_stats.stats.get_attempted() as u64);
+            write_faults(&mut test_env.log_file, &result_name, phase_result.get_faults());
+            test_env.results.add_phase(state.test_case_id, state.repetition, phase_result);
+        }
+    }
+    // A phase that can't be resumed starts again without the results of this run
+    if is_stopped && !is_resumable {
+        state.operation_stats.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn parse(phases_json: Value) -> Vec<ScenarioPhase> {
+        return parse_phases(&phases_json, 50, 2, QueryType::Series)
+    }
+    
+    #[test]
+    fn phases_take_the_params_they_leave_out_from_the_test_case() {
+        let phases = parse(json!([
+            { "type": "load", "points_per_series": 10 },
+            { "type": "query", "queries": 5, "query_type": "last", "concurrency": 4 },
+            { "type": "sleep", "duration": 100 },
+            { "type": "mixed", "points_per_series": 5, "mix": { "insert": 1, "select": 3 }, "rate": 0, "batch_size": 20 },
+            { "type": "wait" }
+        ]));
+        assert_eq!(phases.len(), 5);
+        
+        assert_eq!(phases[0].kind, PhaseKind::Load);
+        assert_eq!(phases[0].batch_size, 50);
+        assert_eq!(phases[0].concurrency, 2);
+        assert_eq!(phases[0].query_type, QueryType::Series);
+        
+        assert_eq!(phases[1].kind, PhaseKind::Query);
+        assert_eq!(phases[1].queries, Some(5));
+        assert_eq!(phases[1].query_type, QueryType::Last);
+        assert_eq!(phases[1].concurrency, 4);
+        
+        assert_eq!(phases[2].kind, PhaseKind::Sleep);
+        assert_eq!(phases[2].duration, Some(Duration::from_millis(100)));
+        
+        assert_eq!(phases[3].kind, PhaseKind::Mixed);
+        assert_eq!(phases[3].insert_share, 0.25);
+        assert_eq!(phases[3].rate, None);
+        assert_eq!(phases[3].batch_size, 20);
+        
+        assert_eq!(phases[4].kind, PhaseKind::Wait);
+        assert_eq!(get_points_per_series(&phases), 15);
+    }
+    
+    #[test]
+    #[should_panic(expected = "Invalid load phase")]
+    fn load_phase_without_points_is_rejected() {
+        parse(json!([{ "type": "load" }]));
+    }
+    
+    #[test]
+    #[should_panic(expected = "Invalid phase type variant: warmup")]
+    fn unknown_phase_type_is_rejected() {
+        parse(json!([{ "type": "warmup" }]));
+    }
+    
+    #[test]
+    #[should_panic(expected = "Invalid phase mix param")]
+    fn negative_mix_is_rejected() {
+        parse(json!([{ "type": "mixed", "points_per_series": 5, "mix": { "insert": -1, "select": 3 } }]));
+    }
+}
@@ -0,0 +1,195 @@
+use bench_rng::BenchRng;
+use rand::Rng;
+use serde_json::Value;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScheduleOrder {
+    /// One point of every series, then the next point of every series
+    RoundRobin,
+    /// A random series that still has points
+    Random,
+    /// All points of a series, then the next series
+    SeriesBySeries
+}
+
+impl ScheduleOrder {
+    pub fn as_str(&self) -> &'static str {
+        return match *self {
+            ScheduleOrder::RoundRobin => "round_robin",
+            ScheduleOrder::Random => "random",
+            ScheduleOrder::SeriesBySeries => "series_by_series"
+        }
+    }
+    
+    pub fn from_str(order: &str) -> Option<ScheduleOrder> {
+        return match order {
+            "round_robin" => Some(ScheduleOrder::RoundRobin),
+            "random" => Some(ScheduleOrder::Random),
+            "series_by_series" => Some(ScheduleOrder::SeriesBySeries),
+            _ => None
+        }
+    }
+}
+
+/// Yields every (series, point index) pair once, the points of a series always in
+/// index order. Every pair takes O(1) time, the random order keeps two counters per
+/// series and the other orders only the position.
+pub struct PointScheduler {
+    order: ScheduleOrder,
+    series: usize,
+    points_per_series: usize,
+    position: usize,
+    rng: BenchRng,
+    /// Series that still have points, in random order only
+    active_series: Vec<u32>,
+    /// Next point index of every series, in random order only
+    next_points: Vec<u32>
+}
+
+impl PointScheduler {
+    pub fn new(order: ScheduleOrder, series: usize, points_per_series: usize, seed: u64) -> PointScheduler {
+        let (active_series, next_points) = if order == ScheduleOrder::Random && points_per_series > 0 {
+            ((0..series as u32).collect(), vec![0; series])
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        
+        return PointScheduler {
+            order: order,
+            series: series,
+            points_per_series: points_per_series,
+            position: 0,
+            rng: BenchRng::new(seed),
+            active_series: active_series,
+            next_points: next_points
+        }
+    }
+    
+    pub fn get_order(&self) -> ScheduleOrder {
+        return self.order
+    }
+    
+    pub fn get_total(&self) -> usize {
+        return self.series*self.points_per_series
+    }
+    
+    pub fn get_position(&self) -> usize {
+        return self.position
+    }
+    
+    fn next_random(&mut self) -> (usize, usize) {
+        let active_pos = self.rng.gen_range(0, self.active_series.len());
+        let series = self.active_series[active_pos] as usize;
+        let point = self.next_points[series] as usize;
+        self.next_points[series] += 1;
+        if point+1 == self.points_per_series {
+            self.active_series.swap_remove(active_pos);
+        }
+        return (series, point);
+    }
+    
+    pub fn to_json(&self) -> Value {
+        return json!({
+            "order": self.order.as_str(),
+            "series": self.series,
+            "points_per_series": self.points_per_series,
+            "position": self.position,
+            "rng_state": self.rng.get_state(),
+            "active_series": self.active_series,
+            "next_points": self.next_points
+        });
+    }
+    
+    pub fn from_json(json: &Value) -> PointScheduler {
+        let numbers = |key: &str| -> Vec<u32> {
+            return json[key].as_array().expect("Invalid scheduler counters param").iter()
+                .map(|number| number.as_u64().expect("Invalid scheduler counter") as u32)
+                .collect();
+        };
+        let order = json["order"].as_str().and_then(ScheduleOrder::from_str).expect("Invalid scheduler order param");
+        
+        return PointScheduler {
+            order: order,
+            series: json["series"].as_u64().expect("Invalid scheduler series param") as usize,
+            points_per_series: json["points_per_series"].as_u64().expect("Invalid scheduler points_per_series param") as usize,
+            position: json["position"].as_u64().expect("Invalid scheduler position param") as usize,
+            rng: BenchRng::new(json["rng_state"].as_u64().expect("Invalid scheduler rng_state param")),
+            active_series: numbers("active_series"),
+            next_points: numbers("next_points")
+        }
+    }
+}
+
+impl Iterator for PointScheduler {
+    type Item = (usize, usize);
+    
+    fn next(&mut self) -> Option<(usize, usize)> {
+        if self.position >= self.get_total() {
+            return None
+        }
+        
+        let pair = match self.order {
+            ScheduleOrder::RoundRobin => (self.position % self.series, self.position/self.series),
+            ScheduleOrder::SeriesBySeries => (self.position/self.points_per_series, self.position % self.points_per_series),
+            ScheduleOrder::Random => self.next_random()
+        };
+        self.position += 1;
+        return Some(pair);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn round_robin_takes_one_point_of_every_series_in_turn() {
+        let pairs: Vec<(usize, usize)> = PointScheduler::new(ScheduleOrder::RoundRobin, 2, 3, 1).collect();
+        assert_eq!(pairs, vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)]);
+    }
+    
+    #[test]
+    fn series_by_series_takes_every_point_of_a_series_first() {
+        let pairs: Vec<(usize, usize)> = PointScheduler::new(ScheduleOrder::SeriesBySeries, 2, 3, 1).collect();
+        assert_eq!(pairs, vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2)]);
+    }
+    
+    #[test]
+    fn random_order_yields_every_point_once_in_index_order() {
+        let scheduler = PointScheduler::new(ScheduleOrder::Random, 5, 4, 7);
+        assert_eq!(scheduler.get_total(), 20);
+        let pairs: Vec<(usize, usize)> = scheduler.collect();
+        assert_eq!(pairs.len(), 20);
+        
+        let mut next_points = vec![0; 5];
+        for (series, point) in pairs.iter().cloned() {
+            assert_eq!(point, next_points[series]);
+            next_points[series] += 1;
+        }
+        assert_eq!(next_points, vec![4; 5]);
+        assert_ne!(pairs, PointScheduler::new(ScheduleOrder::SeriesBySeries, 5, 4, 7).collect::<Vec<(usize, usize)>>());
+        assert_eq!(pairs, PointScheduler::new(ScheduleOrder::Random, 5, 4, 7).collect::<Vec<(usize, usize)>>());
+    }
+    
+    #[test]
+    fn scheduler_continues_from_json() {
+        for order in [ScheduleOrder::RoundRobin, ScheduleOrder::Random, ScheduleOrder::SeriesBySeries].iter() {
+            let mut scheduler = PointScheduler::new(*order, 3, 5, 11);
+            let first: Vec<(usize, usize)> = scheduler.by_ref().take(7).collect();
+            let resumed = PointScheduler::from_json(&scheduler.to_json());
+            assert_eq!(resumed.get_order(), *order);
+            assert_eq!(resumed.get_position(), 7);
+            
+            let rest: Vec<(usize, usize)> = resumed.collect();
+            assert_eq!(rest, scheduler.collect::<Vec<(usize, usize)>>());
+            assert_eq!(first.len()+rest.len(), 15);
+        }
+    }
+    
+    #[test]
+    fn scheduler_of_no_points_is_empty() {
+        assert_eq!(PointScheduler::new(ScheduleOrder::Random, 3, 0, 1).next(), None);
+        assert_eq!(PointScheduler::new(ScheduleOrder::RoundRobin, 0, 3, 1).next(), None);
+    }
+}
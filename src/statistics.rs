@@ -0,0 +1,141 @@
+use serde_json::Value;
+
+
+/// Two-sided 95% quantiles of Student's t distribution for 1 to 30 degrees of freedom
+static T_QUANTILES_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042];
+/// Two-sided 95% quantile of the normal distribution, used for more samples
+static NORMAL_QUANTILE_95: f64 = 1.960;
+
+/// Mean of a value measured once per repetition, with the 95% confidence interval
+/// of the mean. Repetitions are few, so the interval uses Student's t distribution.
+#[derive(Clone, Debug)]
+pub struct Estimate {
+    samples: Vec<f64>,
+    mean: f64,
+    std_dev: f64,
+    /// Half width of the confidence interval
+    margin: f64
+}
+
+impl Estimate {
+    pub fn from_samples(samples: Vec<f64>) -> Estimate {
+        let count = samples.len();
+        let mean = if count > 0 { samples.iter().sum::<f64>()/count as f64 } else { 0.0 };
+        let std_dev = if count > 1 {
+            (samples.iter().map(|sample| (sample-mean).powi(2)).sum::<f64>()/(count-1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        let margin = if count > 1 {
+            let quantile = T_QUANTILES_95.get(count-2).cloned().unwrap_or(NORMAL_QUANTILE_95);
+            quantile*std_dev/(count as f64).sqrt()
+        } else {
+            0.0
+        };
+        
+        return Estimate {
+            samples: samples,
+            mean: mean,
+            std_dev: std_dev,
+            margin: margin
+        }
+    }
+    
+    pub fn get_count(&self) -> usize {
+        return self.samples.len()
+    }
+    
+    pub fn get_mean(&self) -> f64 {
+        return self.mean
+    }
+    
+    pub fn get_margin(&self) -> f64 {
+        return self.margin
+    }
+    
+    /// Coefficient of variation in percent.
+    pub fn get_variation(&self) -> f64 {
+        if self.mean == 0.0 {
+            return 0.0
+        }
+        return self.std_dev/self.mean.abs()*100.0;
+    }
+    
+    /// Position and value of the sample farthest from the mean.
+    pub fn get_outlier(&self) -> Option<(usize, f64)> {
+        return self.samples.iter().cloned().enumerate()
+            .max_by(|a, b| (a.1-self.mean).abs().partial_cmp(&(b.1-self.mean).abs()).expect("Invalid sample"));
+    }
+    
+    pub fn to_json(&self) -> Value {
+        return json!({
+            "mean": self.mean,
+            "std_dev": self.std_dev,
+            "ci_low": self.mean-self.margin,
+            "ci_high": self.mean+self.margin,
+            "samples": self.samples
+        });
+    }
+    
+    pub fn from_json(json: &Value) -> Estimate {
+        let samples = json["samples"].as_array().expect("Invalid estimate samples param").iter()
+            .map(|sample| sample.as_f64().expect("Invalid estimate sample"))
+            .collect();
+        return Estimate::from_samples(samples);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn assert_near(value: f64, expected: f64) {
+        assert!((value-expected).abs() < 1e-3, "{} is not {}", value, expected);
+    }
+    
+    #[test]
+    fn estimate_uses_student_quantile_for_few_samples() {
+        let estimate = Estimate::from_samples(vec![10.0, 12.0, 14.0]);
+        assert_eq!(estimate.get_count(), 3);
+        assert_near(estimate.get_mean(), 12.0);
+        // t(0.975, 2) = 4.303, standard deviation 2
+        assert_near(estimate.get_margin(), 4.303*2.0/3f64.sqrt());
+        assert_near(estimate.get_variation(), 2.0/12.0*100.0);
+        assert_eq!(estimate.get_outlier(), Some((2, 14.0)));
+    }
+    
+    #[test]
+    fn estimate_uses_normal_quantile_for_many_samples() {
+        let samples: Vec<f64> = (0..40).map(|i| (i % 2) as f64*2.0).collect();
+        let estimate = Estimate::from_samples(samples);
+        assert_near(estimate.get_mean(), 1.0);
+        assert_near(estimate.get_margin(), 1.960*(40.0f64/39.0).sqrt()/40f64.sqrt());
+    }
+    
+    #[test]
+    fn estimate_of_one_sample_has_no_interval() {
+        let estimate = Estimate::from_samples(vec![5.0]);
+        assert_near(estimate.get_mean(), 5.0);
+        assert_near(estimate.get_margin(), 0.0);
+        assert_near(estimate.get_variation(), 0.0);
+        
+        let estimate = Estimate::from_samples(Vec::new());
+        assert_eq!(estimate.get_count(), 0);
+        assert_eq!(estimate.get_outlier(), None);
+    }
+    
+    #[test]
+    fn estimate_is_rebuilt_from_json() {
+        let estimate = Estimate::from_samples(vec![1.0, 2.0, 4.0]);
+        let json = estimate.to_json();
+        assert_near(json["ci_low"].as_f64().expect("No ci_low"), estimate.get_mean()-estimate.get_margin());
+        assert_near(json["ci_high"].as_f64().expect("No ci_high"), estimate.get_mean()+estimate.get_margin());
+        
+        let resumed = Estimate::from_json(&json);
+        assert_near(resumed.get_mean(), estimate.get_mean());
+        assert_near(resumed.get_margin(), estimate.get_margin());
+    }
+}
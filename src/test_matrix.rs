@@ -0,0 +1,89 @@
+use serde_json::Value;
+
+
+/// Params of a test case that may hold a list of values, the outermost first.
+static MATRIX_PARAMS: [&str; 5] = ["series", "points_per_series", "batch_size", "concurrency", "query_type"];
+
+/// Expands a test case whose matrix params hold lists of values into a test case
+/// for every combination of them. The generated test cases take the ids from the
+/// id of the entry upwards and the name of the entry followed by their values.
+/// An entry without lists is returned as is.
+pub fn expand(test_case_json: &Value) -> Vec<Value> {
+    let mut test_cases = vec![test_case_json.clone()];
+    let mut has_lists = false;
+    for param in MATRIX_PARAMS.iter() {
+        let values = match test_case_json[*param].as_array() {
+            Some(values) => values,
+            None => continue
+        };
+        if values.is_empty() {
+            panic!("Empty test_case {} list", param);
+        }
+        has_lists = true;
+        
+        let mut expanded = Vec::with_capacity(test_cases.len()*values.len());
+        for test_case in test_cases {
+            for value in values {
+                let mut combination = test_case.clone();
+                combination[*param] = value.clone();
+                expanded.push(combination);
+            }
+        }
+        test_cases = expanded;
+    }
+    if !has_lists {
+        return test_cases
+    }
+    
+    let first_id = test_case_json["id"].as_u64().expect("Invalid test_case id param");
+    let base_name = test_case_json["name"].as_str().map_or(format!("matrix {}", first_id), String::from);
+    for (pos, test_case) in test_cases.iter_mut().enumerate() {
+        let mut name = base_name.clone();
+        for param in MATRIX_PARAMS.iter() {
+            if test_case_json[*param].is_array() {
+                let value = &test_case[*param];
+                name.push_str(&format!(" {}={}", param, value.as_str().map_or(value.to_string(), String::from)));
+            }
+        }
+        test_case["id"] = json!(first_id+pos as u64);
+        test_case["name"] = json!(name);
+    }
+    return test_cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_case_without_lists_is_unchanged() {
+        let test_case = json!({ "id": 1, "series": 10, "batch_size": 50 });
+        assert_eq!(expand(&test_case), vec![test_case]);
+    }
+    
+    #[test]
+    fn lists_expand_into_every_combination() {
+        let test_cases = expand(&json!({ "id": 5, "series": [10, 100], "batch_size": [1, 50], "repeat": 3 }));
+        let combinations: Vec<(u64, u64, u64)> = test_cases.iter()
+            .map(|test_case| (test_case["id"].as_u64().unwrap(), test_case["series"].as_u64().unwrap(), test_case["batch_size"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(combinations, vec![(5, 10, 1), (6, 10, 50), (7, 100, 1), (8, 100, 50)]);
+        assert_eq!(test_cases[0]["name"], "matrix 5 series=10 batch_size=1");
+        assert!(test_cases.iter().all(|test_case| test_case["repeat"] == 3));
+    }
+    
+    #[test]
+    fn names_hold_the_values_without_quotes() {
+        let test_cases = expand(&json!({ "id": 2, "name": "select", "query_type": ["last", "range"] }));
+        assert_eq!(test_cases.len(), 2);
+        assert_eq!(test_cases[0]["name"], "select query_type=last");
+        assert_eq!(test_cases[1]["name"], "select query_type=range");
+        assert_eq!(test_cases[1]["query_type"], "range");
+    }
+    
+    #[test]
+    #[should_panic(expected = "Empty test_case concurrency list")]
+    fn empty_list_is_rejected() {
+        expand(&json!({ "id": 1, "concurrency": [] }));
+    }
+}
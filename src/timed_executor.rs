@@ -0,0 +1,407 @@
+use {is_cancelled, Executor, Point, Query};
+use bench_rng::BenchRng;
+use db::db_raw::query_db::{DBError, PoolConfig, ReconnectEvent};
+use fault_executor::FaultCounts;
+use rand::Rng;
+use std::cmp;
+use std::sync::mpsc;
+use std::sync::mpsc::{RecvTimeoutError, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+static CANCEL_CHECK_INTERVAL_MS: u64 = 100;
+
+/// Sends the responses of a worker with its id
+type ResponseSender = Sender<(u64, Response)>;
+/// Starts a worker thread, with the error of its first connection.
+type NewWorker = Box<dyn Fn(u64, ResponseSender) -> (Sender<Operation>, Option<DBError>)>;
+
+enum Operation {
+    Insert(Vec<Point>),
+    Select(String, Query),
+    Delete(String, i64),
+    Compact,
+    GetHashes(usize),
+    GetLastTimestamp(String),
+    /// Seeds the executor, it has no response
+    Seed(u64)
+}
+
+enum Response {
+    /// Time of an insert or a select, or why it failed, with the reconnects and
+    /// the injected faults during it
+    Time(Result<i32, DBError>, Vec<ReconnectEvent>, FaultCounts),
+    Hashes(Result<Vec<String>, DBError>),
+    LastTimestamp(Result<Option<i64>, DBError>),
+    /// The executor panicked
+    Stopped
+}
+
+pub enum OperationResult {
+    Done(i32),
+    Failed(DBError),
+    TimedOut
+}
+
+/// Operation of a worker that has not responded yet.
+struct Running {
+    tag: usize,
+    start_time: Instant,
+    timeout: Option<Duration>
+}
+
+struct Worker {
+    id: u64,
+    operation_tx: Sender<Operation>,
+    running: Option<Running>
+}
+
+/// Tells the executor that the worker thread is gone when it panics.
+struct StopGuard {
+    worker_id: u64,
+    response_tx: ResponseSender
+}
+
+impl Drop for StopGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let _ = self.response_tx.send((self.worker_id, Response::Stopped));
+        }
+    }
+}
+
+/// Runs every executor on its own thread, so an operation that is stuck in a driver
+/// can be abandoned. The stuck thread finishes on its own and the next operation
+/// goes to a new executor with new connections. An operation that returns an error
+/// fails on its own, the executor keeps its connections for the next one. With a
+/// concurrency above one the operations run on that many executors at once.
+pub struct TimedExecutor {
+    new_worker: NewWorker,
+    workers: Vec<Worker>,
+    next_worker_id: u64,
+    response_tx: ResponseSender,
+    response_rx: Receiver<(u64, Response)>,
+    timeout: Option<Duration>,
+    seed: Option<u64>,
+    reconnect_events: Vec<ReconnectEvent>,
+    fault_counts: FaultCounts
+}
+
+impl TimedExecutor {
+    /// Fails when the first executor can't connect. Executors that are started later
+    /// and can't connect fail their operations and try again with the next one.
+    pub fn new<E, F>(new_executor: F, pool_config: PoolConfig) -> Result<TimedExecutor, DBError>
+        where E: Executor + 'static, F: Fn(PoolConfig) -> Result<E, DBError> + Clone + Send + 'static {
+        
+        let (response_tx, response_rx) = mpsc::channel();
+        let mut executor = TimedExecutor {
+            new_worker: Box::new(move |worker_id, response_tx| start_worker(new_executor.clone(), pool_config, worker_id, response_tx)),
+            workers: Vec::new(),
+            next_worker_id: 0,
+            response_tx: response_tx,
+            response_rx: response_rx,
+            timeout: None,
+            seed: None,
+            reconnect_events: Vec::new(),
+            fault_counts: FaultCounts::default()
+        };
+        let (worker, error) = executor.try_start_worker(0);
+        if let Some(err) = error {
+            return Err(err)
+        }
+        executor.workers.push(worker);
+        return Ok(executor);
+    }
+    
+    /// Sets the timeout of every following insert and select, `None` waits forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+    
+    /// Sets how many operations run at once, the executors of the running
+    /// operations are kept.
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        let concurrency = cmp::max(concurrency, 1);
+        while self.workers.len() > concurrency {
+            match self.workers.iter().rposition(|worker| worker.running.is_none()) {
+                Some(pos) => { self.workers.remove(pos); },
+                None => break
+            }
+        }
+        while self.workers.len() < concurrency {
+            let worker = self.start_worker(self.workers.len());
+            self.workers.push(worker);
+        }
+    }
+    
+    /// Seeds the executors, each one with its own seed derived from `seed` and
+    /// its position. The executors that are started later are seeded too.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        for (pos, worker) in self.workers.iter().enumerate() {
+            worker.operation_tx.send(Operation::Seed(get_worker_seed(seed, pos))).expect("Executor stopped");
+        }
+    }
+    
+    fn start_worker(&mut self, pos: usize) -> Worker {
+        return self.try_start_worker(pos).0
+    }
+    
+    /// Returns the worker for the position `pos` with the error of its executor
+    /// when it could not connect.
+    fn try_start_worker(&mut self, pos: usize) -> (Worker, Option<DBError>) {
+        let worker_id = self.next_worker_id;
+        self.next_worker_id += 1;
+        
+        let (operation_tx, error) = (self.new_worker)(worker_id, self.response_tx.clone());
+        if let Some(seed) = self.seed {
+            operation_tx.send(Operation::Seed(get_worker_seed(seed, pos))).expect("Executor stopped");
+        }
+        let worker = Worker {
+            id: worker_id,
+            operation_tx: operation_tx,
+            running: None
+        };
+        return (worker, error)
+    }
+    
+    pub fn has_idle_worker(&self) -> bool {
+        return self.workers.iter().any(|worker| worker.running.is_none())
+    }
+    
+    fn is_running(&self) -> bool {
+        return self.workers.iter().any(|worker| worker.running.is_some())
+    }
+    
+    /// Starts an insert on an idle executor, `tag` is returned with its result.
+    pub fn start_insert(&mut self, points: Vec<Point>, tag: usize) {
+        let timeout = self.timeout;
+        self.start(Operation::Insert(points), tag, timeout);
+    }
+    
+    /// Starts a select on an idle executor, `tag` is returned with its result.
+    pub fn start_select(&mut self, hash: String, query: Query, tag: usize) {
+        let timeout = self.timeout;
+        self.start(Operation::Select(hash, query), tag, timeout);
+    }
+    
+    /// Starts a delete on an idle executor, `tag` is returned with its result.
+    pub fn start_delete(&mut self, hash: String, before: i64, tag: usize) {
+        let timeout = self.timeout;
+        self.start(Operation::Delete(hash, before), tag, timeout);
+    }
+    
+    /// Starts a compaction on an idle executor, `tag` is returned with its result.
+    pub fn start_compact(&mut self, tag: usize) {
+        let timeout = self.timeout;
+        self.start(Operation::Compact, tag, timeout);
+    }
+    
+    fn start(&mut self, operation: Operation, tag: usize, timeout: Option<Duration>) {
+        let worker = self.workers.iter_mut()
+            .find(|worker| worker.running.is_none())
+            .expect("No idle executor");
+        worker.operation_tx.send(operation).expect("Executor stopped");
+        worker.running = Some(Running {
+            tag: tag,
+            start_time: Instant::now(),
+            timeout: timeout
+        });
+    }
+    
+    /// Waits for the first running operation to finish and returns its tag.
+    /// Cancelling the benchmark doesn't abandon the running operations, so the
+    /// results of everything that was started are counted before a checkpoint.
+    pub fn wait_next(&mut self) -> (usize, OperationResult) {
+        return self.wait_next_until(None).expect("No running operation")
+    }
+    
+    /// Like `wait_next`, but returns `None` when no operation finishes before `deadline`
+    /// or the benchmark is cancelled while none is running.
+    pub fn wait_next_until(&mut self, deadline: Option<Instant>) -> Option<(usize, OperationResult)> {
+        return match self.wait_response(deadline)? {
+            (tag, Ok(Response::Time(Ok(time), _, _))) => Some((tag, OperationResult::Done(time))),
+            (tag, Ok(Response::Time(Err(err), _, _))) => Some((tag, OperationResult::Failed(err))),
+            (_, Ok(_)) => panic!("Unexpected executor response"),
+            (tag, Err(result)) => Some((tag, result))
+        }
+    }
+    
+    /// Returns no hashes when cancelled.
+    pub fn get_hashes(&mut self, sample_size: usize) -> Result<Vec<String>, DBError> {
+        self.start(Operation::GetHashes(sample_size), 0, None);
+        return match self.wait_response(None) {
+            Some((_, Ok(Response::Hashes(hashes_res)))) => hashes_res,
+            _ => Ok(Vec::new())
+        }
+    }
+    
+    /// Returns `Ok(None)` when the series has no points, it timed out or it was cancelled.
+    pub fn get_last_timestamp(&mut self, hash: String) -> Result<Option<i64>, DBError> {
+        let timeout = self.timeout;
+        self.start(Operation::GetLastTimestamp(hash), 0, timeout);
+        return match self.wait_response(None) {
+            Some((_, Ok(Response::LastTimestamp(timestamp_res)))) => timestamp_res,
+            _ => Ok(None)
+        }
+    }
+    
+    /// Returns the reconnects of the executors since the last call.
+    pub fn take_reconnect_events(&mut self) -> Vec<ReconnectEvent> {
+        return self.reconnect_events.drain(..).collect()
+    }
+    
+    /// Returns the faults injected by the executors since the last call.
+    pub fn take_fault_counts(&mut self) -> FaultCounts {
+        let fault_counts = self.fault_counts;
+        self.fault_counts = FaultCounts::default();
+        return fault_counts
+    }
+    
+    /// Fails with `TimedOut` when a running operation doesn't respond in time, its
+    /// executor is replaced. Returns `None` once `deadline` passes or the benchmark
+    /// is cancelled while no operation is running.
+    fn wait_response(&mut self, deadline: Option<Instant>) -> Option<(usize, Result<Response, OperationResult>)> {
+        loop {
+            let mut wait_time = Duration::from_millis(CANCEL_CHECK_INTERVAL_MS);
+            if let Some(deadline) = deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None
+                }
+                wait_time = cmp::min(wait_time, deadline-now);
+            }
+            for pos in 0..self.workers.len() {
+                let (tag, elapsed, timeout) = match self.workers[pos].running {
+                    Some(Running { tag, start_time, timeout: Some(timeout) }) => (tag, start_time.elapsed(), timeout),
+                    _ => continue
+                };
+                if elapsed >= timeout {
+                    self.workers[pos] = self.start_worker(pos);
+                    return Some((tag, Err(OperationResult::TimedOut)))
+                }
+                wait_time = cmp::min(wait_time, timeout-elapsed);
+            }
+            
+            match self.response_rx.recv_timeout(wait_time) {
+                Ok((worker_id, response)) => {
+                    if let Some(result) = self.take_response(worker_id, response) {
+                        return Some(result)
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) if is_cancelled() && !self.is_running() => return None,
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => panic!("Executor stopped")
+            }
+        }
+    }
+    
+    /// Matches a response to its operation, the responses of replaced executors are
+    /// dropped.
+    fn take_response(&mut self, worker_id: u64, response: Response) -> Option<(usize, Result<Response, OperationResult>)> {
+        let worker = self.workers.iter_mut().find(|worker| worker.id == worker_id)?;
+        if let Response::Stopped = response {
+            panic!("Executor stopped");
+        }
+        let running = worker.running.take()?;
+        
+        let response = match response {
+            Response::Time(time, events, fault_counts) => {
+                self.reconnect_events.extend(events);
+                self.fault_counts.add(&fault_counts);
+                Response::Time(time, Vec::new(), FaultCounts::default())
+            },
+            response => response
+        };
+        return Some((running.tag, Ok(response)));
+    }
+}
+
+fn start_worker<E, F>(new_executor: F,
+    pool_config: PoolConfig,
+    worker_id: u64,
+    response_tx: ResponseSender) -> (Sender<Operation>, Option<DBError>)
+    where E: Executor + 'static, F: Fn(PoolConfig) -> Result<E, DBError> + Send + 'static {
+    
+    let (operation_tx, operation_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    
+    thread::spawn(move || {
+        let _guard = StopGuard {
+            worker_id: worker_id,
+            response_tx: response_tx.clone()
+        };
+        let (mut executor, error) = match new_executor(pool_config) {
+            Ok(executor) => (Some(executor), None),
+            Err(err) => (None, Some(err))
+        };
+        if ready_tx.send(error).is_err() {
+            return
+        }
+        
+        let mut seed = None;
+        for operation in operation_rx {
+            if let Operation::Seed(worker_seed) = operation {
+                seed = Some(worker_seed);
+                if let Some(ref mut executor) = executor {
+                    executor.set_seed(worker_seed);
+                }
+                continue
+            }
+            // An executor that could not connect tries again with every operation
+            if executor.is_none() {
+                match new_executor(pool_config) {
+                    Ok(mut new) => {
+                        if let Some(seed) = seed {
+                            new.set_seed(seed);
+                        }
+                        executor = Some(new)
+                    },
+                    Err(err) => {
+                        if response_tx.send((worker_id, get_failed_response(&operation, err))).is_err() {
+                            return
+                        }
+                        continue
+                    }
+                }
+            }
+            let executor = executor.as_mut().expect("No executor");
+            let response = match operation {
+                Operation::Insert(points) => timed_response(executor, |executor| executor.insert(points)),
+                Operation::Select(hash, query) => timed_response(executor, |executor| executor.select(&hash, &query)),
+                Operation::Delete(hash, before) => timed_response(executor, |executor| executor.delete(&hash, before)),
+                Operation::Compact => timed_response(executor, |executor| executor.compact()),
+                Operation::GetHashes(sample_size) => Response::Hashes(executor.get_hashes(sample_size)),
+                Operation::GetLastTimestamp(hash) => Response::LastTimestamp(executor.get_last_timestamp(&hash)),
+                Operation::Seed(_) => continue
+            };
+            if response_tx.send((worker_id, response)).is_err() {
+                return
+            }
+        }
+    });
+    
+    let error = ready_rx.recv().expect("Failed to create executor");
+    return (operation_tx, error)
+}
+
+fn get_worker_seed(seed: u64, pos: usize) -> u64 {
+    return BenchRng::new(seed.wrapping_add(pos as u64)).gen::<u64>()
+}
+
+fn get_failed_response(operation: &Operation, err: DBError) -> Response {
+    return match *operation {
+        Operation::GetHashes(_) => Response::Hashes(Err(err)),
+        Operation::GetLastTimestamp(_) => Response::LastTimestamp(Err(err)),
+        _ => Response::Time(Err(err), Vec::new(), FaultCounts::default())
+    }
+}
+
+/// Runs a timed operation with the reconnects and faults during it.
+fn timed_response<E, F>(executor: &mut E, operation: F) -> Response
+    where E: Executor, F: FnOnce(&mut E) -> Result<i32, DBError> {
+    
+    let time_res = operation(executor);
+    return Response::Time(time_res, executor.take_reconnect_events(), executor.take_fault_counts())
+}